@@ -1,152 +1,3627 @@
-use crate::models::BriefSong;
-use crate::player::{PlayerMetadata, PlayerState, Progress};
-use crate::rpc::{send_request, subscribe_topics, Message};
-use log::info;
-use std::sync::{Arc, Mutex};
+use crate::bookmarks::{stars, Bookmarks, MAX_RATING};
+use crate::commands::Command;
+use crate::config::{Config, ConfigError, Theme, QUALITIES};
+use crate::event::{self, AppEvent};
+use crate::history::{self, Stats};
+use crate::i18n::tr;
+use crate::inspector;
+#[cfg(unix)]
+use crate::ipc;
+use crate::keymap::{Action, Context};
+use crate::logger;
+use crate::lyric::{self, LyricMode, LyricState};
+use crate::m3u;
+use crate::models::{
+    self, AudioDevices, BriefPlaylist, BriefSong, Comment, LoginQrcode, LoginState, LoginStatus,
+    Media, Provider, Song, Status, Video,
+};
+use crate::nowplaying::NowPlaying;
+use crate::osc;
+use crate::page::{
+    CollectionsPage, Cursor, LibraryItem, LoginPage, Page, PlaylistsPage, ProvidersPage, SongsPage,
+    StatsPage, StatsRange,
+};
+use crate::paths;
+use crate::player::{
+    fmt_duration, parse_position, PlaybackMode, PlayerMetadata, PlayerState, Progress,
+};
+use crate::qr::QrCode;
+use crate::rpc::{Client, Message, Pubsub};
+use crate::search;
+use crate::session::Session;
+use crate::store;
+use crate::uri::FuoUri;
+use crossterm::event::{KeyCode, MouseButton, MouseEvent, MouseEventKind};
+use log::{debug, error, info};
+use notify::RecommendedWatcher;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File};
+use std::hash::BuildHasher;
+use std::io::{self, ErrorKind};
+use std::process::{self, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
+use tui::layout::Rect;
+use tui::widgets::TableState;
 
 // Store app states.
 #[allow(dead_code)]
 pub struct AppInner {
     pub metadata: PlayerMetadata,
-    pub lyric_s: String, // Current lyric sentence.
+    pub current_song_uri: Option<FuoUri>,
+    pub lyric: LyricState,
     pub progress: Progress,
     pub duration: Duration,
     pub state: PlayerState,
+    // The song is being resolved or downloaded, it is set when metadata
+    // changes and cleared when the first duration or seeked signal arrives.
+    pub loading: bool,
+    pub playback_mode: PlaybackMode,
+    pub playback_rate: f64,
     pub current_playlist: Vec<BriefSong>,
+    // Latest message body of topics which fust does not understand, such as
+    // signals from forked fuo versions.
+    pub other_messages: HashMap<String, String>,
 }
 
 impl AppInner {
+    pub fn new() -> AppInner {
+        AppInner {
+            metadata: PlayerMetadata::new(),
+            current_song_uri: None,
+            lyric: LyricState::None,
+            progress: Progress::default(),
+            duration: Duration::new(0, 0),
+            state: PlayerState::Stopped,
+            loading: false,
+            playback_mode: PlaybackMode::Sequential,
+            playback_rate: 1.0,
+            current_playlist: vec![],
+            other_messages: HashMap::new(),
+        }
+    }
+
+    // The playing song made up from its metadata.
+    pub fn current_song(&self) -> Option<BriefSong> {
+        let uri = self.current_song_uri.as_ref()?;
+        Some(BriefSong {
+            provider: uri.provider.clone(),
+            identifier: uri.identifier.clone(),
+            title: self.metadata.title.clone(),
+            album_name: self.metadata.album.clone().unwrap_or_default(),
+            artists_name: self.metadata.artists.join(","),
+            duration_ms: fmt_duration(self.duration),
+        })
+    }
+
+    // Apply the response of the `status` command, missing fields mean that
+    // nothing is playing.
+    pub fn on_status(&mut self, status: &Status) {
+        let song = status.song.clone().unwrap_or_default();
+        self.metadata = PlayerMetadata {
+            title: song.title.unwrap_or_default(),
+            album: song.album_name,
+            artists: song.artists_name.into_iter().collect(),
+            uri: None,
+        };
+        let uri = match (&song.provider, &song.identifier) {
+            (Some(provider), Some(identifier)) => Some(FuoUri::new(provider, "songs", identifier)),
+            _ => None,
+        };
+        if uri != self.current_song_uri {
+            self.lyric = LyricState::None;
+        }
+        self.current_song_uri = uri;
+        let secs = |value: Option<f64>| Duration::from_secs_f64(value.unwrap_or(0.0).max(0.0));
+        self.progress.on_seeked(secs(status.position));
+        self.duration = secs(status.duration);
+        // Status only tells whether repeat or random is on.
+        self.playback_mode = if status.random == Some(1) {
+            PlaybackMode::Random
+        } else if status.repeat == Some(1) {
+            PlaybackMode::Loop
+        } else {
+            PlaybackMode::Sequential
+        };
+        // Nothing is played without a song, whatever the state says.
+        let state = status.song.as_ref().and(status.state.as_deref());
+        match state {
+            Some("playing") => {
+                self.state = PlayerState::Playing;
+                self.progress.resume();
+            }
+            Some("paused") => {
+                self.state = PlayerState::Paused;
+                self.progress.pause();
+            }
+            _ => {
+                self.state = PlayerState::Stopped;
+                self.progress.pause();
+            }
+        }
+    }
+
+    // Malformed messages of the server are logged and skipped, so that they
+    // cannot kill the subscriber thread.
     pub fn on_message(&mut self, msg: Message) {
-        let body = String::from_utf8(msg.body.clone()).unwrap();
-        match msg.topic.as_str() {
+        let body = match String::from_utf8(msg.body) {
+            Ok(body) => body,
+            Err(e) => return error!("message of topic {} is not utf-8: {}", msg.topic, e),
+        };
+        if let Err(e) = self.apply_message(&msg.topic, &body) {
+            error!("bad message of topic {}: {}: {:?}", msg.topic, e, body);
+        }
+    }
+
+    fn apply_message(&mut self, topic: &str, body: &str) -> Result<(), String> {
+        match topic {
             "player.state_changed" => {
-                // TODO: maybe use tuple?
-                let value: serde_json::Value = serde_json::from_str(&body).unwrap();
-                match value[0].as_u64().unwrap().try_into() {
-                    Ok(state) => {
-                        self.state = state;
-                        match state {
-                            PlayerState::Paused => self.progress.pause(),
-                            PlayerState::Stopped => self.progress.on_seeked(Duration::new(0, 0)),
-                            PlayerState::Playing => self.progress.resume(),
-                        }
-                    }
-                    Err(_) => panic!("unknown player state"),
+                let value = first_u64(body)?;
+                let state: PlayerState = value
+                    .try_into()
+                    .map_err(|_| format!("unknown player state {}", value))?;
+                self.state = state;
+                match state {
+                    PlayerState::Paused => self.progress.pause(),
+                    PlayerState::Stopped => self.progress.on_seeked(Duration::new(0, 0)),
+                    PlayerState::Playing if !self.loading => self.progress.resume(),
+                    PlayerState::Playing => {}
                 }
             }
             "player.metadata_changed" => {
-                let args: (PlayerMetadata,) = serde_json::from_str(&body).unwrap();
+                let args: (PlayerMetadata,) =
+                    serde_json::from_str(body).map_err(|e| e.to_string())?;
                 self.metadata = args.0;
+                self.lyric = LyricState::None;
+                if let Some(uri) = &self.metadata.uri {
+                    self.current_song_uri = uri.parse().ok();
+                }
                 self.progress.on_seeked(Duration::new(0, 0));
+                self.progress.pause();
+                self.loading = true;
             }
             "player.duration_changed" => {
-                let args: (f64,) = serde_json::from_str(&body).unwrap();
-                self.duration = Duration::from_secs_f64(args.0 as f64);
+                let args: (f64,) = serde_json::from_str(body).map_err(|e| e.to_string())?;
+                self.duration = secs_duration(args.0)?;
+                self.on_loaded();
             }
             "player.seeked" => {
-                let args: (f64,) = serde_json::from_str(&body).unwrap();
-                self.progress
-                    .on_seeked(Duration::from_secs_f64(args.0 as f64));
+                let args: (f64,) = serde_json::from_str(body).map_err(|e| e.to_string())?;
+                self.progress.on_seeked(secs_duration(args.0)?);
+                self.on_loaded();
+            }
+            "playlist.playback_mode_changed" => {
+                let value = first_u64(body)?;
+                self.playback_mode = value
+                    .try_into()
+                    .map_err(|_| format!("unknown playback mode {}", value))?;
             }
             "live_lyric.sentence_changed" => {
-                if !body.is_empty() {
-                    let args: (String,) = serde_json::from_str(&body).unwrap();
-                    self.lyric_s = args.0;
+                // An empty sentence means that the song has a lyric, but
+                // nothing is sung at the moment.
+                self.lyric = match serde_json::from_str::<(String,)>(body) {
+                    Ok((line,)) if !line.trim().is_empty() => LyricState::Line(line),
+                    _ => LyricState::Unsynced,
+                };
+            }
+            _ => self.on_other_message(topic.to_owned(), body.to_owned()),
+        }
+        Ok(())
+    }
+
+    fn on_other_message(&mut self, topic: String, body: String) {
+        debug!("message of topic {}: {}", topic, body);
+        self.other_messages.insert(topic, body);
+    }
+
+    fn on_loaded(&mut self) {
+        if self.loading {
+            self.loading = false;
+            if self.state == PlayerState::Playing {
+                self.progress.resume();
+            }
+        }
+    }
+}
+
+// The first argument of a message, like `[2]` of `player.state_changed`.
+fn first_u64(body: &str) -> Result<u64, String> {
+    let value: serde_json::Value = serde_json::from_str(body).map_err(|e| e.to_string())?;
+    value[0]
+        .as_u64()
+        .ok_or_else(|| "expected an integer".to_owned())
+}
+
+// Seconds sent by the server, which are negative or NaN if it is broken.
+fn secs_duration(secs: f64) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(secs).map_err(|e| format!("{}: {}", e, secs))
+}
+
+// Local playlist edits which can be reverted.
+pub enum PlaylistEdit {
+    Removed(usize, BriefSong),
+    Moved(usize, usize),
+    // The order which the songs are shuffled to.
+    Shuffled(Vec<usize>),
+}
+
+// The log panel, which follows new records unless it is scrolled up.
+pub struct LogPanel {
+    // Number of records between the bottom of the panel and the newest one.
+    pub scroll: usize,
+    // Number of records ever logged when the panel was last updated.
+    seen: usize,
+}
+
+// The rpc inspector, which follows new records unless one is selected.
+pub struct InspectorView {
+    // Index in the filtered records.
+    pub selected: Option<usize>,
+    // Only show records whose topic contains it.
+    pub filter: String,
+    pub paused: bool,
+}
+
+pub enum PickerKind {
+    SearchSources,
+    // The provider to get daily recommendations from.
+    DailySongs,
+    DailyPlaylists,
+    Fm,
+    Charts,
+    // The collection to add the song to.
+    AddToCollection(FuoUri),
+    // The playlist of the current user to add the song to.
+    AddToPlaylist(FuoUri),
+    AudioDevice,
+    // The artist of the playing song to open.
+    Artist,
+}
+
+// The personal FM of the provider, whose songs are added to the playlist
+// by the server as they are played.
+pub struct Fm {
+    pub provider: String,
+    // The song which was playing when the playlist was last fetched.
+    uri: Option<FuoUri>,
+}
+
+// Hot comments of the playing song, shown beside the main view.
+pub struct CommentsPane {
+    // The song which the comments are of.
+    pub uri: Option<FuoUri>,
+    // `None` while they are being fetched.
+    pub comments: Option<Result<Vec<Comment>, String>>,
+    // Number of lines scrolled down.
+    pub scroll: u16,
+    // Keys go to the pane first, like `j` and `k` which scroll it.
+    pub focused: bool,
+}
+
+// The quality which the playing song is resolved to.
+#[derive(Default)]
+pub struct Quality {
+    // The song which the quality is of.
+    pub uri: Option<FuoUri>,
+    // `None` if it is unknown, like when the player has no such info.
+    pub resolved: Option<String>,
+}
+
+// Fade the volume down over the last minute of the sleep timer.
+const SLEEP_FADE: Duration = Duration::from_secs(60);
+
+// Pause when the time is up.
+pub struct SleepTimer {
+    pub deadline: Instant,
+    fade: bool,
+    // The volume before fading, which is restored after pausing.
+    volume: Option<u64>,
+    // The volume which is set last time while fading.
+    faded: Option<u64>,
+}
+
+impl SleepTimer {
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+}
+
+// Repeat the part of the playing song between `a` and `b`, which is dropped
+// when the song changes.
+pub struct AbRepeat {
+    uri: Option<FuoUri>,
+    pub a: Duration,
+    pub b: Option<Duration>,
+}
+
+// Lines of the region of announcements in the accessible mode.
+pub const ANNOUNCEMENTS: usize = 3;
+
+// The state which is announced last.
+#[derive(Default)]
+struct Announced {
+    uri: Option<FuoUri>,
+    state: Option<PlayerState>,
+    mode: Option<PlaybackMode>,
+}
+
+// The longest sleep timer, longer ones are more likely typos.
+const MAX_SLEEP: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Parse a duration like `30m`, `1h30m` or `90s`, a plain number is in
+// minutes. Durations over `MAX_SLEEP` are invalid.
+fn parse_sleep_duration(s: &str) -> Option<Duration> {
+    let secs = match s.parse::<u64>() {
+        Ok(minutes) => minutes.checked_mul(60)?,
+        Err(_) => {
+            let mut secs: u64 = 0;
+            let mut number = String::new();
+            for c in s.chars() {
+                if c.is_ascii_digit() {
+                    number.push(c);
+                    continue;
                 }
+                let n: u64 = number.parse().ok()?;
+                number.clear();
+                let unit = match c {
+                    'h' => 3600,
+                    'm' => 60,
+                    's' => 1,
+                    _ => return None,
+                };
+                secs = secs.checked_add(n.checked_mul(unit)?)?;
             }
-            _ => {}
+            if !number.is_empty() {
+                return None;
+            }
+            secs
+        }
+    };
+    let duration = Duration::from_secs(secs);
+    (secs > 0 && duration <= MAX_SLEEP).then_some(duration)
+}
+
+// Whether the playing song is liked on its provider.
+#[derive(Default)]
+pub struct Liked {
+    // The song which the state is of.
+    uri: Option<FuoUri>,
+    // `None` if it is unknown, like when the provider does not support it.
+    pub state: Option<bool>,
+}
+
+// A popup to choose from items, which captures keys until it is submitted or
+// cancelled. Items can be checked if `checked` is set.
+pub struct Picker {
+    pub kind: PickerKind,
+    pub title: String,
+    pub items: Vec<String>,
+    pub selected: usize,
+    pub checked: Option<Vec<bool>>,
+    // Values of items, which are the items themselves if they are empty.
+    values: Vec<String>,
+}
+
+impl Picker {
+    fn values(&self) -> &[String] {
+        if self.values.is_empty() {
+            &self.items
+        } else {
+            &self.values
+        }
+    }
+}
+
+pub enum ConfirmKind {
+    // The collection to remove the song from.
+    RemoveFromCollection(String, BriefSong),
+}
+
+// A question which is confirmed with `y` or `enter`, and cancelled with `n`.
+pub struct Confirm {
+    pub kind: ConfirmKind,
+    pub text: String,
+}
+
+// Keys of actions, listed by `?`.
+pub struct Help {
+    pub keys: Vec<(String, &'static str)>,
+    pub scroll: usize,
+}
+
+// Dialogs shown over the current view in `App::modals`, the one on top
+// captures keys until it is closed, and `esc` returns to the one below.
+pub enum Modal {
+    Picker(Picker),
+    Confirm(Confirm),
+    Help(Help),
+}
+
+// Send the command and parse the json body of the response, the error is
+// the message of the server if the command fails.
+fn request_json<T: serde::de::DeserializeOwned>(client: &Client, cmd: Command) -> io::Result<T> {
+    let resp = client.send_request(cmd)?;
+    if !resp.ok {
+        let msg = String::from_utf8_lossy(&resp.body).trim().to_owned();
+        return Err(io::Error::other(msg));
+    }
+    serde_json::from_slice(&resp.body).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+// Songs in the response, like the ones of a playlist or an album.
+fn request_songs(client: &Client, cmd: Command) -> io::Result<Vec<BriefSong>> {
+    let resp = client.send_request(cmd)?;
+    if !resp.ok {
+        let msg = String::from_utf8_lossy(&resp.body).trim().to_owned();
+        return Err(io::Error::other(msg));
+    }
+    models::parse_songs(&resp.body).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+// Append the songs to the playlist in batches, returning the added ones.
+// `progress` is called with the number of songs done after each batch.
+fn add_songs(
+    client: &Client,
+    songs: &[BriefSong],
+    mut progress: impl FnMut(usize),
+) -> Vec<BriefSong> {
+    let mut added = vec![];
+    for (i, batch) in songs.chunks(ADD_BATCH).enumerate() {
+        let uris = batch.iter().map(|song| song.uri()).collect();
+        match client.send_request(Command::AddAll(uris)) {
+            Ok(resp) if resp.ok => added.extend_from_slice(batch),
+            _ => error!("failed to add {} songs", batch.len()),
+        }
+        progress((i * ADD_BATCH + batch.len()).min(songs.len()));
+    }
+    added
+}
+
+fn collection_songs(client: &Client, name: &str) -> io::Result<Vec<BriefSong>> {
+    let resp = client.send_request(Command::CollectionSongs(name.to_owned()))?;
+    let output = models::exec_output(&resp.body);
+    if !resp.ok {
+        let msg = String::from_utf8_lossy(&output).trim().to_owned();
+        return Err(io::Error::other(msg));
+    }
+    models::parse_songs(&output).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+// The same as `request_json`, for python code which prints json by `exec`.
+fn exec_json<T: serde::de::DeserializeOwned>(client: &Client, cmd: Command) -> io::Result<T> {
+    let resp = client.send_request(cmd)?;
+    let output = models::exec_output(&resp.body);
+    if !resp.ok {
+        let msg = String::from_utf8_lossy(&output).trim().to_owned();
+        return Err(io::Error::other(msg));
+    }
+    serde_json::from_slice(&output).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+const LOGIN_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// Number of songs appended to the shown playlist at a time.
+const PLAYLIST_PAGE: usize = 500;
+
+// Milliseconds to shift the lyric by at a time.
+const LYRIC_OFFSET_STEP: i64 = 500;
+
+// Number of songs added by a single request.
+const ADD_BATCH: usize = 50;
+
+const TOAST_DURATION: Duration = Duration::from_secs(3);
+
+// A short message shown to the user for a while.
+pub struct Toast {
+    pub text: String,
+    pub ts: Instant,
+}
+
+impl Toast {
+    pub fn is_expired(&self) -> bool {
+        self.ts.elapsed() > TOAST_DURATION
+    }
+
+    fn expires_in(&self) -> Duration {
+        TOAST_DURATION.saturating_sub(self.ts.elapsed())
+    }
+}
+
+pub enum InputKind {
+    JumpToTime,
+    Filter,
+    Command,
+    TopicFilter,
+    Search,
+    HistoryFilter,
+}
+
+impl InputKind {
+    pub fn prompt(&self) -> &'static str {
+        match self {
+            InputKind::JumpToTime => tr!("跳转到 (mm:ss): "),
+            InputKind::Filter => "/",
+            InputKind::Command => ":",
+            InputKind::TopicFilter => "topic: ",
+            InputKind::Search => tr!("搜索："),
+            InputKind::HistoryFilter => "/",
         }
     }
 }
 
+// A single line input which captures keys until it is submitted or cancelled.
+pub struct Input {
+    pub kind: InputKind,
+    pub text: String,
+}
+
 pub struct App {
-    pub inner: Arc<Mutex<AppInner>>,
+    pub inner: Arc<RwLock<AppInner>>,
+    pub config: Config,
+    pub client: Client,
+    pub playlist_state: TableState,
+    // The first row shown in the playlist table.
+    pub playlist_offset: usize,
+    pub playlist_filter: String,
+    // Indices of the songs which match the filter, `None` if not filtered.
+    // Rows in `playlist_state` are positions in this index when it is set.
+    pub playlist_filtered: Option<Vec<usize>>,
+    // The keyword which the page of the history is filtered by.
+    history_filter: String,
+    // Songs of the current playlist which are not shown yet.
+    pending_songs: Vec<BriefSong>,
+    search_index: search::Index,
+    // The lowercased keyword of `playlist_filtered`.
+    searched: String,
+    pub undo_stack: Vec<PlaylistEdit>,
+    pub toast: Option<Toast>,
+    pub input: Option<Input>,
+    pub log_panel: Option<LogPanel>,
+    pub inspector: Option<InspectorView>,
+    // Show the full lyric of the playing song instead of the playlist.
+    pub lyrics_view: bool,
+    // Lines from the current one to the one in the middle of the lyrics
+    // view, negative if it is scrolled up.
+    pub lyrics_scroll: isize,
+    pub pages: Vec<Page>,
+    pub modals: Vec<Modal>,
+    pub fm: Option<Fm>,
+    pub comments: Option<CommentsPane>,
+    pub liked: Liked,
+    pub quality: Quality,
+    pub sleep_timer: Option<SleepTimer>,
+    pub ab_repeat: Option<AbRepeat>,
+    // Description of the audio output device, `None` if it is unknown.
+    pub audio_device: Option<String>,
+    pub lyric_mode: LyricMode,
+    pub session: Session,
+    // Where the time of the progress is drawn, which is clicked to toggle
+    // the remaining time.
+    pub time_area: Rect,
+    lyric_cache: lyric::Cache,
+    lyric_offsets: lyric::Offsets,
+    pub bookmarks: Bookmarks,
+    history: history::Tracker,
+    // The lyric of the playing song, `None` if nothing is playing.
+    pub timed_lyric: Option<lyric::Timed>,
+    // Wall clock time of the last tick, used to detect system suspend.
+    last_tick_ts: SystemTime,
+    // The latest changes told in the accessible mode, the earliest first.
+    pub announcements: VecDeque<String>,
+    announced: Announced,
+    // Events which wake up the main loop, sent by background threads.
+    events_tx: Sender<AppEvent>,
+    events_rx: Receiver<AppEvent>,
+    _theme_watcher: Option<RecommendedWatcher>,
+    // The terminal title which was set last time.
+    last_title: String,
+    // Title and artists of the song which was notified last time.
+    last_notified: Option<(String, String)>,
+    #[cfg(unix)]
+    _ipc_server: Option<ipc::Server>,
 }
 
 impl App {
-    pub fn new() -> App {
+    pub fn new(config: Config) -> App {
+        let client = Client::new(&config.server);
+        let lyric_cache = lyric::Cache::new(config.cache.lyrics_max_kb * 1024);
+        let bookmarks = Bookmarks::load(config.glyphs());
+        let (events_tx, events_rx) = channel();
         App {
-            inner: Arc::new(Mutex::new(AppInner {
-                metadata: PlayerMetadata::new(),
-                lyric_s: "暂无歌词".to_owned(),
-                progress: Progress::default(),
-                duration: Duration::new(0, 0),
-                state: PlayerState::Stopped,
-                current_playlist: vec![],
-            })),
+            inner: Arc::new(RwLock::new(AppInner::new())),
+            config,
+            client,
+            playlist_state: TableState::default(),
+            playlist_offset: 0,
+            playlist_filter: String::new(),
+            playlist_filtered: None,
+            history_filter: String::new(),
+            pending_songs: vec![],
+            search_index: search::Index::default(),
+            searched: String::new(),
+            undo_stack: vec![],
+            toast: None,
+            input: None,
+            log_panel: None,
+            inspector: None,
+            lyrics_view: false,
+            lyrics_scroll: 0,
+            pages: vec![],
+            modals: vec![],
+            fm: None,
+            comments: None,
+            liked: Liked::default(),
+            quality: Quality::default(),
+            sleep_timer: None,
+            ab_repeat: None,
+            audio_device: None,
+            lyric_mode: LyricMode::Original,
+            session: Session::load(),
+            time_area: Rect::default(),
+            lyric_cache,
+            lyric_offsets: lyric::Offsets::load(),
+            bookmarks,
+            history: history::Tracker::default(),
+            timed_lyric: None,
+            last_tick_ts: SystemTime::now(),
+            announcements: VecDeque::new(),
+            announced: Announced::default(),
+            events_tx,
+            events_rx,
+            _theme_watcher: None,
+            last_title: String::new(),
+            last_notified: None,
+            #[cfg(unix)]
+            _ipc_server: None,
         }
     }
 
-    pub fn on_tick(&mut self) {}
-
-    // Sync player status immediattely by sending a request `status --format=json`.
-    pub fn sync_player_status(&mut self) {
-        let resp = send_request("status").unwrap();
-        let value: serde_json::Value = serde_json::from_slice(&resp.body).unwrap();
-        let song = value["song"].clone();
-        let duration = Duration::from_secs_f64(value["duration"].as_f64().unwrap());
-        let position = Duration::from_secs_f64(value["position"].as_f64().unwrap());
-        let metadata = PlayerMetadata {
-            title: song["title"].as_str().unwrap().to_string(),
-            album: Some(song["album_name"].as_str().unwrap().to_string()),
-            artists: vec![song["artists_name"].as_str().unwrap().to_string()],
+    pub fn on_tick(&mut self) {
+        // The monotonic clock used by `Progress` stops while the system is
+        // suspended, so resync from the server when wall clock jumps.
+        let now = SystemTime::now();
+        let jumped = match now.duration_since(self.last_tick_ts) {
+            Ok(elapsed) => elapsed > Duration::from_secs(5),
+            Err(_) => true,
         };
+        self.last_tick_ts = now;
+        if jumped {
+            info!("clock jumped, resync player status");
+            self.sync_player_status();
+        }
+        if self.toast.as_ref().is_some_and(|toast| toast.is_expired()) {
+            self.toast = None;
+        }
         {
-            let mut inner = self.inner.lock().unwrap();
-            inner.metadata = metadata;
-            inner.progress.on_seeked(position);
-            inner.duration = duration;
-            match value["state"].as_str().unwrap() {
-                "paused" => {
-                    inner.state = PlayerState::Paused;
-                    inner.progress.pause();
-                }
-                "stopped" => {
-                    inner.state = PlayerState::Stopped;
-                    inner.progress.pause();
-                }
-                "playing" => {
-                    inner.state = PlayerState::Playing;
-                    inner.progress.resume();
-                }
-                _ => self.inner.lock().unwrap().state = PlayerState::Stopped,
+            let inner = self.inner.read().unwrap();
+            let playing = inner.state == PlayerState::Playing && !inner.loading;
+            let song = inner.current_song();
+            self.history
+                .on_tick(song, inner.progress.current(), playing);
+        }
+        self.sync_timed_lyric();
+        self.sync_fm();
+        self.sync_comments();
+        self.sync_liked();
+        self.sync_quality();
+        self.check_sleep_timer();
+        self.check_ab_repeat();
+        if self.config.accessible {
+            self.announce_changes();
+        }
+        // Keep showing the same records while scrolled up.
+        if let Some(panel) = self.log_panel.as_mut() {
+            let (_, total) = logger::recent();
+            if panel.scroll > 0 {
+                panel.scroll += total - panel.seen;
             }
+            panel.seen = total;
         }
     }
 
-    pub fn sync_current_playlist(&mut self) {
-        let resp = send_request("list").unwrap();
-        let songs: Vec<BriefSong> = serde_json::from_slice(&resp.body).unwrap();
-        {
-            let mut inner = self.inner.lock().unwrap();
-            info!("sync current playlist, first {}", songs[0].title);
-            inner.current_playlist = songs;
+    // Time until something on the screen changes by itself, like the spinner
+    // while loading, the seconds of progress and expiring toasts.
+    pub fn next_timeout(&self) -> Duration {
+        let mut timeout = Duration::from_secs(1);
+        let inner = self.inner.read().unwrap();
+        // The sung part of the lyric grows smoothly.
+        let karaoke = self.lyrics_view && inner.state == PlayerState::Playing;
+        if karaoke || inner.loading && inner.state != PlayerState::Stopped {
+            timeout = timeout.min(self.config.tick_rate());
+        } else if inner.state == PlayerState::Playing && inner.playback_rate > 0.0 {
+            let position = inner.progress.current();
+            let next = Duration::from_secs(position.as_secs() + 1) - position;
+            timeout = timeout.min(next.div_f64(inner.playback_rate));
+            let next_line = self
+                .timed_lyric
+                .as_ref()
+                .filter(|timed| timed.offset != 0 || self.lyrics_view)
+                .and_then(|timed| timed.next_line_in(position));
+            if let Some(next) = next_line {
+                timeout = timeout.min(next.div_f64(inner.playback_rate));
+            }
+            // Seek back as soon as B is passed.
+            if let Some(b) = self.ab_repeat.as_ref().and_then(|ab| ab.b) {
+                let next = b.saturating_sub(position);
+                timeout = timeout.min(next.div_f64(inner.playback_rate));
+            }
         }
+        if let Some(toast) = &self.toast {
+            timeout = timeout.min(toast.expires_in());
+        }
+        timeout
     }
 
-    pub fn toggle_playpause(&self) {
-        let _ = send_request("toggle").unwrap();
-        info!("toggled playpause");
+    // Sender of events for threads started by the caller, like the one which
+    // reads the terminal.
+    pub fn event_sender(&self) -> Sender<AppEvent> {
+        self.events_tx.clone()
     }
 
-    pub fn play_next(&self) {
-        let _ = send_request("next").unwrap();
-        info!("switched to next song");
+    // Wait for the next event, `None` if it times out.
+    pub fn next_event(&self, timeout: Duration) -> Option<AppEvent> {
+        self.events_rx.recv_timeout(timeout).ok()
     }
 
-    pub fn play_previous(&self) {
-        let _ = send_request("previous").unwrap();
-        info!("switched to previous song");
+    // The event which has arrived, if any, so that bursts of events are
+    // handled before a single redraw.
+    pub fn pending_event(&self) -> Option<AppEvent> {
+        self.events_rx.try_recv().ok()
     }
 
-    pub fn subscribe_msgs(&self) {
-        let inner = self.inner.clone();
+    // Messages are missed while the server is down, such as the ones of songs
+    // played after it restarts.
+    pub fn on_reconnected(&mut self) {
+        self.sync_player_status();
+        self.sync_audio_device();
+        self.apply_quality();
+        if !self.inner.read().unwrap().current_playlist.is_empty() {
+            self.sync_current_playlist();
+        }
+        self.show_toast(tr!("已重新连接服务器"));
+    }
+
+    // The terminal is taken over again after the process is continued.
+    pub fn on_resumed(&mut self) {
+        self.last_title.clear();
+        self.sync_player_status();
+    }
+
+    pub fn on_theme(&mut self, theme: Result<Theme, ConfigError>) {
+        match theme {
+            Ok(theme) => {
+                info!("theme reloaded");
+                self.config.theme = theme.degrade(self.config.color_depth());
+            }
+            Err(e) => self.show_toast(e.to_string()),
+        }
+    }
+
+    #[cfg(feature = "mpris")]
+    pub fn start_mpris(&self) {
+        if self.config.mpris.enabled {
+            crate::mpris::serve(self.inner.clone(), self.client.clone());
+        }
+        #[cfg(target_os = "linux")]
+        if self.config.mpris.media_keys {
+            crate::media_keys::grab(self.inner.clone(), self.client.clone());
+        }
+    }
+
+    #[cfg(unix)]
+    pub fn start_discord(&self) {
+        if self.config.discord.enabled {
+            crate::discord::start(self.config.discord.client_id.clone(), self.inner.clone());
+        }
+    }
+
+    pub fn start_scrobbler(&self) {
+        crate::scrobbler::start(&self.config.scrobbler, self.inner.clone());
+    }
+
+    // Start integrations which run in background threads, shared by the
+    // TUI and the daemon.
+    pub fn start_services(&mut self) {
+        self.subscribe_msgs();
+        #[cfg(unix)]
+        self.start_ipc();
+        #[cfg(feature = "mpris")]
+        self.start_mpris();
+        self.start_scrobbler();
+        #[cfg(unix)]
+        self.start_discord();
+    }
+
+    // Accept actions from `fust --send`.
+    #[cfg(unix)]
+    pub fn start_ipc(&mut self) {
+        let (tx, rx) = channel();
+        self._ipc_server = ipc::Server::start(tx);
+        if self._ipc_server.is_some() {
+            event::forward(rx, self.events_tx.clone(), AppEvent::Remote);
+        }
+    }
+
+    // Apply the theme whenever its file changes.
+    pub fn watch_theme(&mut self) {
+        let source = match &self.config.theme_source {
+            Some(source) => source.clone(),
+            None => return,
+        };
+        let (tx, rx) = channel();
+        match source.watch(tx) {
+            Ok(watcher) => {
+                event::forward(rx, self.events_tx.clone(), AppEvent::Theme);
+                self._theme_watcher = Some(watcher);
+            }
+            Err(e) => error!("failed to watch {}: {}", source.path().display(), e),
+        }
+    }
+
+    // The terminal title if it should be updated.
+    pub fn terminal_title(&mut self) -> Option<String> {
+        if !self.config.title.enabled {
+            return None;
+        }
+        let title = {
+            let inner = self.inner.read().unwrap();
+            if inner.metadata.title.is_empty() {
+                "fust".to_owned()
+            } else {
+                NowPlaying::new(&inner).render(&self.config.title.format)
+            }
+        };
+        // Control chars would end the escape sequence early.
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+        if title == self.last_title {
+            return None;
+        }
+        self.last_title = title.clone();
+        Some(title)
+    }
+
+    // The notification escape sequence if the song changed.
+    pub fn song_notification(&mut self) -> Option<String> {
+        if !self.config.notification.enabled {
+            return None;
+        }
+        let (title, artists) = {
+            let inner = self.inner.read().unwrap();
+            (
+                inner.metadata.title.clone(),
+                inner.metadata.artists.join(", "),
+            )
+        };
+        let song = Some((title.clone(), artists.clone()));
+        // The song playing at startup is not notified.
+        let first = self.last_notified.is_none();
+        if title.is_empty() || self.last_notified == song {
+            return None;
+        }
+        self.last_notified = song;
+        if first {
+            return None;
+        }
+        Some(osc::notification(
+            &self.config.notification.protocol,
+            &title,
+            &artists,
+        ))
+    }
+
+    pub fn show_toast(&mut self, text: impl Into<String>) {
+        let text = text.into();
+        self.announce(text.clone());
+        self.toast = Some(Toast {
+            text,
+            ts: Instant::now(),
+        });
+    }
+
+    // Tell a change as a line of plain text in the accessible mode, which
+    // screen readers read as it is printed, unlike the colors and symbols.
+    fn announce(&mut self, text: String) {
+        if !self.config.accessible {
+            return;
+        }
+        if self.announcements.len() == ANNOUNCEMENTS {
+            self.announcements.pop_front();
+        }
+        self.announcements.push_back(text);
+    }
+
+    fn announce_changes(&mut self) {
+        let inner = self.inner.read().unwrap();
+        let now = Announced {
+            uri: inner.current_song_uri.clone(),
+            state: Some(inner.state),
+            mode: Some(inner.playback_mode),
+        };
+        let mut changes = vec![];
+        if now.uri != self.announced.uri && now.uri.is_some() {
+            let metadata = &inner.metadata;
+            let artists = metadata.artists.join(",");
+            changes.push(tr!("正在播放：{} - {}", metadata.title, artists));
+        }
+        if now.state != self.announced.state {
+            changes.push(inner.state.label().to_owned());
+        }
+        if now.mode != self.announced.mode {
+            changes.push(tr!("播放模式：{}", inner.playback_mode.label()));
+        }
+        drop(inner);
+        self.announced = now;
+        for change in changes {
+            self.announce(change);
+        }
+    }
+
+    // Sync player status immediattely by sending a request `status --format=json`.
+    pub fn sync_player_status(&mut self) {
+        let status = self.client.send_request(Command::Status).and_then(|resp| {
+            serde_json::from_slice::<Status>(&resp.body)
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+        });
+        match status {
+            Ok(status) => self.inner.write().unwrap().on_status(&status),
+            Err(e) => {
+                error!("failed to sync player status: {}", e);
+                self.show_toast(tr!("获取播放状态失败：{}", e));
+            }
+        }
+    }
+
+    // Fetch the current playlist in the background, so that huge ones do not
+    // block the UI.
+    pub fn sync_current_playlist(&mut self) {
+        let client = self.client.clone();
+        let tx = self.events_tx.clone();
         thread::spawn(move || {
-            subscribe_topics(vec!["player.*", "live_lyric.*"], &|msg| {
-                inner.lock().unwrap().on_message(msg)
+            let songs = client.send_request(Command::List).and_then(|resp| {
+                serde_json::from_slice(&resp.body)
+                    .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
             });
+            let _ = tx.send(AppEvent::Playlist(songs));
+        });
+    }
+
+    // Show the first page of the fetched playlist, or all of it if it is
+    // being filtered.
+    pub fn on_playlist(&mut self, songs: io::Result<Vec<BriefSong>>) {
+        let mut songs = match songs {
+            Ok(songs) => songs,
+            Err(e) => {
+                error!("failed to fetch the current playlist: {}", e);
+                return self.show_toast(tr!("获取播放列表失败：{}", e));
+            }
+        };
+        info!("sync current playlist, {} songs", songs.len());
+        self.pending_songs = if self.playlist_filter.is_empty() {
+            songs.split_off(songs.len().min(PLAYLIST_PAGE))
+        } else {
+            vec![]
+        };
+        self.inner.write().unwrap().current_playlist = songs;
+        if self.playlist_state.selected().is_none() {
+            self.playlist_state.select(Some(0));
+        }
+        self.on_playlist_changed();
+    }
+
+    // Append the next page of songs when the selection gets near the end, or
+    // all the songs which are left.
+    fn load_more(&mut self, all: bool) {
+        let near_end = self
+            .playlist_state
+            .selected()
+            .is_some_and(|row| row + PLAYLIST_PAGE / 2 >= self.visible_len());
+        if self.pending_songs.is_empty() || !(all || near_end) {
+            return;
+        }
+        let n = if all {
+            self.pending_songs.len()
+        } else {
+            PLAYLIST_PAGE.min(self.pending_songs.len())
+        };
+        let songs: Vec<BriefSong> = self.pending_songs.drain(..n).collect();
+        self.search_index.extend(&songs);
+        self.inner.write().unwrap().current_playlist.extend(songs);
+        self.searched.clear();
+        self.refresh_filter();
+    }
+
+    // Index of the selected song in the current playlist.
+    fn selected_index(&self) -> Option<usize> {
+        let row = self.playlist_state.selected()?;
+        match &self.playlist_filtered {
+            Some(indices) => indices.get(row).copied(),
+            None => Some(row),
+        }
+    }
+
+    // Select the song by its index in the current playlist.
+    fn select_index(&mut self, i: usize) {
+        let row = match &self.playlist_filtered {
+            Some(indices) => indices.iter().position(|&j| j == i),
+            None => Some(i),
+        };
+        if row.is_some() {
+            self.playlist_state.select(row);
+        }
+    }
+
+    fn visible_len(&self) -> usize {
+        match &self.playlist_filtered {
+            Some(indices) => indices.len(),
+            None => self.inner.read().unwrap().current_playlist.len(),
+        }
+    }
+
+    // Rebuild the search index, it should be called whenever the current
+    // playlist changes.
+    pub fn on_playlist_changed(&mut self) {
+        self.search_index = search::Index::new(&self.inner.read().unwrap().current_playlist);
+        self.searched.clear();
+        self.refresh_filter();
+    }
+
+    // Filter the playlist, it should be called whenever the filter changes.
+    pub fn refresh_filter(&mut self) {
+        let keyword = self.playlist_filter.to_lowercase();
+        self.playlist_filtered = if keyword.is_empty() {
+            None
+        } else {
+            // Typing more narrows down the songs which already match.
+            let within = match &self.playlist_filtered {
+                Some(indices) if !self.searched.is_empty() && keyword.contains(&self.searched) => {
+                    Some(indices.as_slice())
+                }
+                _ => None,
+            };
+            Some(self.search_index.search(&keyword, within))
+        };
+        self.searched = keyword;
+        let len = self.visible_len();
+        match self.playlist_state.selected() {
+            _ if len == 0 => self.playlist_state.select(None),
+            Some(row) if row >= len => self.playlist_state.select(Some(len - 1)),
+            None => self.playlist_state.select(Some(0)),
+            Some(_) => {}
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        self.load_more(false);
+        let len = self.visible_len();
+        if len == 0 {
+            return;
+        }
+        let i = match self.playlist_state.selected() {
+            Some(i) if i + 1 < len => i + 1,
+            Some(i) => i,
+            None => 0,
+        };
+        self.playlist_state.select(Some(i));
+    }
+
+    pub fn select_previous(&mut self) {
+        let i = match self.playlist_state.selected() {
+            Some(i) if i > 0 => i - 1,
+            _ => 0,
+        };
+        self.playlist_state.select(Some(i));
+    }
+
+    // Select the song which is being played, the table scrolls to it on next draw.
+    pub fn select_current_song(&mut self) {
+        let inner = self.inner.read().unwrap();
+        let current = match &inner.current_song_uri {
+            Some(uri) => uri,
+            None => return,
+        };
+        let position = |songs: &[BriefSong]| songs.iter().position(|song| &song.uri() == current);
+        let i = match position(&inner.current_playlist) {
+            Some(i) => i,
+            // The song may be in pages which are not shown yet.
+            None => match position(&self.pending_songs) {
+                Some(i) => i + inner.current_playlist.len(),
+                None => return,
+            },
+        };
+        drop(inner);
+        self.load_more(true);
+        self.select_index(i);
+    }
+
+    pub fn play_selected(&mut self) {
+        let uri = {
+            let inner = self.inner.read().unwrap();
+            match self
+                .selected_index()
+                .and_then(|i| inner.current_playlist.get(i))
+            {
+                Some(song) => song.uri(),
+                None => return,
+            }
+        };
+        match self.client.send_request(Command::Play(uri.clone())) {
+            Ok(resp) if resp.ok => {
+                info!("play {}", uri);
+                self.inner.write().unwrap().current_song_uri = Some(uri);
+            }
+            _ => {
+                error!("failed to play {}", uri);
+                self.show_toast(tr!("播放失败"));
+            }
+        }
+    }
+
+    pub fn open_input(&mut self, kind: InputKind) {
+        self.input = Some(Input {
+            kind,
+            text: String::new(),
         });
     }
+
+    pub fn on_input_key(&mut self, code: KeyCode) {
+        let input = match self.input.as_mut() {
+            Some(input) => input,
+            None => return,
+        };
+        match code {
+            KeyCode::Char(c) => input.text.push(c),
+            KeyCode::Backspace => {
+                input.text.pop();
+            }
+            KeyCode::Esc => {
+                match self.input.take().map(|input| input.kind) {
+                    Some(InputKind::Filter) => {
+                        self.playlist_filter.clear();
+                        self.refresh_filter();
+                    }
+                    Some(InputKind::TopicFilter) => self.set_topic_filter(String::new()),
+                    Some(InputKind::HistoryFilter) => self.filter_history(String::new()),
+                    _ => {}
+                }
+                return;
+            }
+            KeyCode::Enter => {
+                if let Some(input) = self.input.take() {
+                    match input.kind {
+                        InputKind::JumpToTime => self.jump_to_time(&input.text),
+                        InputKind::Filter | InputKind::TopicFilter | InputKind::HistoryFilter => {}
+                        InputKind::Command => self.run_command(&input.text),
+                        InputKind::Search => self.search(&input.text),
+                    }
+                }
+                return;
+            }
+            _ => {}
+        }
+        // Narrow the playlist, or the inspector, as the user types.
+        match &self.input {
+            Some(Input {
+                kind: InputKind::Filter,
+                text,
+            }) => {
+                self.playlist_filter = text.clone();
+                self.refresh_filter();
+            }
+            Some(Input {
+                kind: InputKind::TopicFilter,
+                text,
+            }) => self.set_topic_filter(text.clone()),
+            Some(Input {
+                kind: InputKind::HistoryFilter,
+                text,
+            }) => self.filter_history(text.clone()),
+            _ => {}
+        }
+    }
+
+    pub fn open_filter(&mut self) {
+        let input = match &self.inspector {
+            Some(inspector) => Input {
+                kind: InputKind::TopicFilter,
+                text: inspector.filter.clone(),
+            },
+            None if self.history_shown() => Input {
+                kind: InputKind::HistoryFilter,
+                text: self.history_filter.clone(),
+            },
+            None => {
+                // Every song is searched.
+                self.load_more(true);
+                Input {
+                    kind: InputKind::Filter,
+                    text: self.playlist_filter.clone(),
+                }
+            }
+        };
+        self.input = Some(input);
+    }
+
+    fn jump_to_time(&mut self, text: &str) {
+        let duration = self.inner.read().unwrap().duration;
+        match parse_position(text) {
+            Some(position) if position <= duration => {
+                match self.client.send_request(Command::Seek(position)) {
+                    Ok(resp) if resp.ok => info!("seek to {:?}", position),
+                    _ => error!("failed to seek to {:?}", position),
+                }
+            }
+            Some(_) => self.show_toast(tr!("超出歌曲时长")),
+            None => self.show_toast(tr!("无效的时间：{}", text)),
+        }
+    }
+
+    // Run a command typed after `:`, like `export ~/mix.m3u`.
+    fn run_command(&mut self, text: &str) {
+        let text = text.trim();
+        let (name, arg) = match text.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (text, ""),
+        };
+        match name {
+            "" => {}
+            "export" if arg.is_empty() => self.show_toast(tr!("用法：export <文件>")),
+            "export" => self.export_playlist(arg),
+            "lyric-export" => self.export_lyric(arg),
+            "sleep" => self.set_sleep_timer(arg),
+            "quality" => self.set_quality(arg),
+            "rate" => self.rate_song(arg),
+            "cache-clear" => match self.lyric_cache.clear() {
+                Ok(count) => self.show_toast(tr!("已清除 {} 首歌词缓存", count)),
+                Err(e) if e.kind() == ErrorKind::NotFound => self.show_toast(tr!("没有歌词缓存")),
+                Err(e) => self.show_toast(tr!("清除缓存失败：{}", e)),
+            },
+            _ => self.show_toast(tr!("未知命令：{}", name)),
+        }
+    }
+
+    // `sleep <duration> [fade]` starts the timer, and `sleep off` cancels it.
+    fn set_sleep_timer(&mut self, arg: &str) {
+        let mut args = arg.split_whitespace();
+        let ((duration, deadline), fade) = match (args.next(), args.next()) {
+            (Some("off"), None) => {
+                self.cancel_sleep_timer();
+                return self.show_toast(tr!("已取消睡眠定时"));
+            }
+            (Some(duration), fade) if fade.is_none() || fade == Some("fade") => {
+                let parsed = parse_sleep_duration(duration)
+                    .and_then(|d| Some((d, Instant::now().checked_add(d)?)));
+                match parsed {
+                    Some(parsed) => (parsed, fade.is_some()),
+                    None => return self.show_toast(tr!("无效的时长：{}", duration)),
+                }
+            }
+            _ => return self.show_toast(tr!("用法：sleep <时长，如 30m> [fade] 或 sleep off")),
+        };
+        self.cancel_sleep_timer();
+        info!("sleep in {:?}, fade: {}", duration, fade);
+        self.sleep_timer = Some(SleepTimer {
+            deadline,
+            fade,
+            volume: None,
+            faded: None,
+        });
+        self.show_toast(tr!("{} 后暂停播放", fmt_duration(duration)));
+    }
+
+    fn cancel_sleep_timer(&mut self) {
+        if let Some(volume) = self.sleep_timer.take().and_then(|timer| timer.volume) {
+            let _ = self.client.send_request(Command::SetVolume(volume));
+        }
+    }
+
+    // Tell the server the preferred quality, which is left to it unless set.
+    pub fn apply_quality(&self) {
+        let quality = match &self.config.quality {
+            Some(quality) => quality.clone(),
+            None => return,
+        };
+        match self
+            .client
+            .send_request(Command::SetQuality(quality.clone()))
+        {
+            Ok(resp) if resp.ok => info!("prefer {} quality", quality),
+            _ => error!("failed to prefer {} quality", quality),
+        }
+    }
+
+    // `quality hq|sq|lq`, which is kept until fust exits.
+    fn set_quality(&mut self, arg: &str) {
+        if !QUALITIES.contains(&arg) {
+            return self.show_toast(tr!("用法：quality {}", QUALITIES.join("|")));
+        }
+        self.config.quality = Some(arg.to_owned());
+        match self
+            .client
+            .send_request(Command::SetQuality(arg.to_owned()))
+        {
+            Ok(resp) if resp.ok => {
+                info!("prefer {} quality", arg);
+                self.show_toast(tr!("优先播放 {} 音质，下一首歌曲生效", arg));
+            }
+            _ => {
+                error!("failed to prefer {} quality", arg);
+                self.show_toast(tr!("设置音质失败"));
+            }
+        }
+    }
+
+    // Fetch the quality which the song is resolved to once it is loaded.
+    fn sync_quality(&mut self) {
+        let (uri, loading) = {
+            let inner = self.inner.read().unwrap();
+            (inner.current_song_uri.clone(), inner.loading)
+        };
+        if loading || self.quality.uri == uri {
+            return;
+        }
+        self.quality = Quality {
+            uri: uri.clone(),
+            resolved: None,
+        };
+        let uri = match uri {
+            Some(uri) => uri,
+            None => return,
+        };
+        let client = self.client.clone();
+        let tx = self.events_tx.clone();
+        thread::spawn(move || {
+            let media = exec_json(&client, Command::CurrentMedia);
+            let _ = tx.send(AppEvent::Media(uri, media));
+        });
+    }
+
+    pub fn on_media(&mut self, uri: FuoUri, media: io::Result<Media>) {
+        if self.quality.uri.as_ref() != Some(&uri) {
+            return;
+        }
+        match media {
+            Ok(media) => {
+                info!("{} is resolved to {:?}", uri, media);
+                self.quality.resolved = media.quality();
+            }
+            Err(e) => debug!("failed to fetch the media of {}: {}", uri, e),
+        }
+    }
+
+    // Mark A, then B, then stop repeating.
+    fn cycle_ab_repeat(&mut self) {
+        let (uri, position) = {
+            let inner = self.inner.read().unwrap();
+            (inner.current_song_uri.clone(), inner.progress.current())
+        };
+        if uri.is_none() {
+            return self.show_toast(tr!("没有正在播放的歌曲"));
+        }
+        match self.ab_repeat.as_mut() {
+            None => {
+                self.ab_repeat = Some(AbRepeat {
+                    uri,
+                    a: position,
+                    b: None,
+                });
+                self.show_toast(tr!("A 点：{}", fmt_duration(position)));
+            }
+            Some(ab) if ab.b.is_none() => {
+                if position <= ab.a {
+                    return self.show_toast(tr!("B 点需要在 A 点之后"));
+                }
+                ab.b = Some(position);
+                let text = tr!(
+                    "A-B 循环：{} - {}",
+                    fmt_duration(ab.a),
+                    fmt_duration(position)
+                );
+                self.show_toast(text);
+            }
+            Some(_) => {
+                self.ab_repeat = None;
+                self.show_toast(tr!("已取消 A-B 循环"));
+            }
+        }
+    }
+
+    fn check_ab_repeat(&mut self) {
+        let uri = self.inner.read().unwrap().current_song_uri.clone();
+        let (a, b) = match self.ab_repeat.as_ref() {
+            Some(ab) if ab.uri != uri => {
+                self.ab_repeat = None;
+                return;
+            }
+            Some(AbRepeat { a, b: Some(b), .. }) => (*a, *b),
+            _ => return,
+        };
+        if self.inner.read().unwrap().progress.current() < b {
+            return;
+        }
+        match self.client.send_request(Command::Seek(a)) {
+            Ok(resp) if resp.ok => {
+                info!("repeat from {:?}", a);
+                // Do not seek again before the seeked signal arrives.
+                self.inner.write().unwrap().progress.on_seeked(a);
+            }
+            _ => error!("failed to seek to {:?}", a),
+        }
+    }
+
+    fn check_sleep_timer(&mut self) {
+        let timer = match self.sleep_timer.as_mut() {
+            Some(timer) => timer,
+            None => return,
+        };
+        let remaining = timer.remaining();
+        if remaining.is_zero() {
+            info!("sleep timer is up");
+            let _ = self.client.send_request(Command::Pause);
+            self.cancel_sleep_timer();
+            return self.show_toast(tr!("睡眠定时结束，已暂停播放"));
+        }
+        if !timer.fade || remaining > SLEEP_FADE {
+            return;
+        }
+        if timer.volume.is_none() {
+            let status = request_json::<Status>(&self.client, Command::Status);
+            timer.volume = Some(status.ok().and_then(|s| s.volume).unwrap_or(100));
+        }
+        let volume = timer.volume.unwrap_or(100);
+        let faded = volume * remaining.as_secs() / SLEEP_FADE.as_secs();
+        if timer.faded != Some(faded) {
+            timer.faded = Some(faded);
+            let _ = self.client.send_request(Command::SetVolume(faded));
+        }
+    }
+
+    // Write the current playlist to an M3U file.
+    fn export_playlist(&mut self, path: &str) {
+        let path = paths::expand_home(path);
+        // The cached playlist is only synced when it is shown.
+        let songs: Vec<BriefSong> = match self.client.send_request(Command::List) {
+            Ok(resp) if resp.ok => serde_json::from_slice(&resp.body).unwrap_or_default(),
+            _ => self.inner.read().unwrap().current_playlist.clone(),
+        };
+        let result = File::create(&path).and_then(|mut file| m3u::write(&mut file, &songs));
+        match result {
+            Ok(()) => {
+                info!("export playlist to {}", path.display());
+                self.show_toast(tr!("已导出到 {}", path.display()));
+            }
+            Err(e) => self.show_toast(tr!("导出失败：{}", e)),
+        }
+    }
+
+    // Save the lyric of the playing song as `<artist> - <title>.lrc` in the
+    // directory, which is the working directory by default.
+    fn export_lyric(&mut self, dir: &str) {
+        let (uri, title, artists) = {
+            let inner = self.inner.read().unwrap();
+            (
+                inner.current_song_uri.clone(),
+                inner.metadata.title.clone(),
+                inner.metadata.artists.join(", "),
+            )
+        };
+        let uri = match uri {
+            Some(uri) => uri,
+            None => return self.show_toast(tr!("没有正在播放的歌曲")),
+        };
+        let lyric = match lyric::fetch_cached(&self.client, &self.lyric_cache, &uri) {
+            Ok(lyric) if !lyric.content.trim().is_empty() => lyric,
+            Ok(_) => return self.show_toast(tr!("暂无歌词")),
+            Err(e) => return self.show_toast(tr!("获取歌词失败：{}", e)),
+        };
+        let name = format!("{} - {}.lrc", artists, title).replace(['/', '\\'], "_");
+        let path = paths::expand_home(if dir.is_empty() { "." } else { dir }).join(name);
+        match fs::write(&path, lyric.content) {
+            Ok(()) => {
+                info!("export lyric to {}", path.display());
+                self.show_toast(tr!("已导出到 {}", path.display()));
+            }
+            Err(e) => self.show_toast(tr!("导出失败：{}", e)),
+        }
+    }
+
+    // Follow the playing song, whose lyric is fetched if it has an offset or
+    // is shown in full.
+    fn sync_timed_lyric(&mut self) {
+        let uri = self.inner.read().unwrap().current_song_uri.clone();
+        if self.timed_lyric.as_ref().map(|timed| &timed.uri) == uri.as_ref() {
+            return;
+        }
+        self.timed_lyric = uri.map(|uri| {
+            let offset = self.lyric_offsets.get(&uri);
+            lyric::Timed::new(uri, offset)
+        });
+        let has_offset = self
+            .timed_lyric
+            .as_ref()
+            .is_some_and(|timed| timed.offset != 0);
+        if has_offset || self.lyrics_view || self.lyric_mode != LyricMode::Original {
+            self.fetch_timed_lyric();
+        }
+    }
+
+    fn fetch_timed_lyric(&mut self) {
+        let timed = match self.timed_lyric.as_mut() {
+            Some(timed) if timed.lines.is_none() => timed,
+            _ => return,
+        };
+        match lyric::fetch_cached(&self.client, &self.lyric_cache, &timed.uri) {
+            Ok(lyric) => timed.load(&lyric),
+            Err(e) => error!("failed to fetch the lyric of {}: {}", timed.uri, e),
+        }
+    }
+
+    // Shift the lyric of the playing song later, or earlier if it is negative.
+    fn adjust_lyric_offset(&mut self, millis: i64) {
+        self.sync_timed_lyric();
+        let offset = match self.timed_lyric.as_mut() {
+            Some(timed) => {
+                timed.offset += millis;
+                self.lyric_offsets.set(&timed.uri, timed.offset);
+                timed.offset
+            }
+            None => return self.show_toast(tr!("没有正在播放的歌曲")),
+        };
+        self.fetch_timed_lyric();
+        let timed = self
+            .timed_lyric
+            .as_ref()
+            .and_then(|timed| timed.lines.as_ref());
+        match timed {
+            Some(lines) if lines.is_empty() => self.show_toast(tr!("歌词没有时间轴")),
+            Some(_) => {
+                let secs = format!("{:+.1}", offset as f64 / 1000.0);
+                self.show_toast(tr!("歌词偏移 {} 秒", secs))
+            }
+            None => self.show_toast(tr!("获取歌词失败")),
+        }
+    }
+
+    // The lyric to show, which is adjusted by the offset of the song, or
+    // taken from the fetched lyric until the server pushes a sentence.
+    pub fn lyric(&self, inner: &AppInner) -> LyricState {
+        let position = inner.progress.current();
+        let pushed = inner.lyric != LyricState::None;
+        let timed = self
+            .timed_lyric
+            .as_ref()
+            .filter(|timed| inner.current_song_uri.as_ref() == Some(&timed.uri));
+        let lyric = timed
+            .filter(|timed| timed.offset != 0 || !pushed)
+            .and_then(|timed| timed.line_at(position))
+            .unwrap_or_else(|| inner.lyric.clone());
+        // Translations are only known from the fetched lyric.
+        let translation = match timed {
+            Some(timed) if self.lyric_mode != LyricMode::Original => {
+                timed.index_at(position).and_then(|i| timed.translation(i))
+            }
+            _ => None,
+        };
+        match (self.lyric_mode, lyric, translation) {
+            (LyricMode::Translation, LyricState::Line(_), Some(translation)) => {
+                LyricState::Line(translation.to_owned())
+            }
+            (LyricMode::Bilingual, LyricState::Line(line), Some(translation)) => {
+                LyricState::Line(format!("{} / {}", line, translation))
+            }
+            (_, lyric, _) => lyric,
+        }
+    }
+
+    fn toggle_remaining(&mut self) {
+        self.session.remaining_time = !self.session.remaining_time;
+        self.session.save();
+    }
+
+    pub fn on_mouse(&mut self, mouse: MouseEvent) {
+        let area = self.time_area;
+        let clicked = mouse.kind == MouseEventKind::Down(MouseButton::Left)
+            && mouse.row == area.y
+            && (area.left()..area.right()).contains(&mouse.column);
+        if clicked && self.input.is_none() && self.modals.is_empty() {
+            self.toggle_remaining();
+        }
+    }
+
+    fn cycle_lyric_mode(&mut self) {
+        self.lyric_mode = self.lyric_mode.next();
+        self.sync_timed_lyric();
+        self.fetch_timed_lyric();
+        let translated = self
+            .timed_lyric
+            .as_ref()
+            .is_some_and(|timed| timed.has_translations());
+        if translated || self.lyric_mode == LyricMode::Original {
+            self.show_toast(tr!("歌词：{}", self.lyric_mode.name()));
+        } else {
+            self.show_toast(tr!("歌词：{}（这首歌没有翻译）", self.lyric_mode.name()));
+        }
+    }
+
+    pub fn on_action(&mut self, action: Action) {
+        match action {
+            // They are handled by the main loop, which has the terminal and
+            // the keymap, see `main::on_event`.
+            Action::Quit | Action::Suspend | Action::Help => {}
+            Action::PlayPause => self.toggle_playpause(),
+            Action::Next => self.play_next(),
+            Action::Previous => self.play_previous(),
+            Action::SeekForward => self.seek_relative(5.0),
+            Action::SeekBackward => self.seek_relative(-5.0),
+            Action::CycleRepeat => self.cycle_repeat(),
+            Action::ToggleRandom => self.toggle_random(),
+            Action::SpeedUp => self.speed_up(),
+            Action::SlowDown => self.slow_down(),
+            Action::JumpToTime => self.open_input(InputKind::JumpToTime),
+            Action::ShowPlaylist => self.sync_current_playlist(),
+            Action::SelectNext if self.log_panel.is_some() => self.scroll_logs(-1),
+            Action::SelectPrevious if self.log_panel.is_some() => self.scroll_logs(1),
+            Action::SelectNext if self.inspector.is_some() => self.select_record(1),
+            Action::SelectPrevious if self.inspector.is_some() => self.select_record(-1),
+            Action::SelectNext if self.shown_page().is_some() => self.select_in_page(1),
+            Action::SelectPrevious if self.shown_page().is_some() => self.select_in_page(-1),
+            Action::SelectNext => self.select_next(),
+            Action::SelectPrevious => self.select_previous(),
+            Action::SelectCurrent => self.select_current_song(),
+            Action::PlaySelected if self.shown_page().is_some() => self.enter_page(),
+            Action::PlaySelected => self.play_selected(),
+            Action::EnqueueNext if self.shown_page().is_some() => self.enqueue_in_page(),
+            Action::EnqueueNext => self.enqueue_selected_next(),
+            Action::Remove if self.shown_page().is_some() => self.confirm_remove_from_collection(),
+            Action::Remove => self.remove_selected(),
+            Action::MoveUp => self.move_selected_up(),
+            Action::MoveDown => self.move_selected_down(),
+            Action::Undo => self.undo(),
+            Action::Filter => self.open_filter(),
+            Action::Command => self.open_input(InputKind::Command),
+            Action::ToggleLogs => self.toggle_logs(),
+            Action::ToggleInspector => self.toggle_inspector(),
+            Action::PauseInspector => self.pause_inspector(),
+            Action::ClearInspector => self.clear_inspector(),
+            Action::ToggleLyrics => self.toggle_lyrics(),
+            Action::Search => self.open_input(InputKind::Search),
+            Action::SearchSources => self.pick_search_sources(),
+            Action::Back => self.back(),
+            Action::Providers => self.open_providers(),
+            Action::DailySongs => self.pick_provider(PickerKind::DailySongs, tr!("每日推荐歌曲")),
+            Action::DailyPlaylists => {
+                self.pick_provider(PickerKind::DailyPlaylists, tr!("每日推荐歌单"))
+            }
+            Action::ToggleFm if self.fm.is_some() => self.stop_fm(),
+            Action::ToggleFm => self.pick_provider(PickerKind::Fm, tr!("私人 FM")),
+            Action::Dislike => self.dislike(),
+            Action::SimilarSongs => self.open_similar_songs(),
+            Action::EnqueueAll => self.enqueue_all(),
+            Action::ToggleComments => self.toggle_comments(),
+            Action::ScrollCommentsDown => self.scroll_comments(1),
+            Action::ScrollCommentsUp => self.scroll_comments(-1),
+            Action::FocusComments => self.focus_comments(),
+            // Charts of the provider selected in the provider browser.
+            Action::Charts => match self.shown_page() {
+                Some(Page::Providers(page)) => {
+                    if let Some(provider) = page.selected() {
+                        let provider = provider.identifier.clone();
+                        self.open_charts(&provider);
+                    }
+                }
+                _ => self.pick_provider(PickerKind::Charts, tr!("排行榜")),
+            },
+            Action::PlayAll => self.play_all(),
+            Action::Library => self.open_library(),
+            Action::AddToCollection => self.pick_collection(),
+            Action::ToggleLike => self.toggle_like(),
+            Action::AddToPlaylist => self.pick_playlist(),
+            Action::OpenWebPage => self.open_web_page(),
+            Action::PlayMv => self.play_mv(),
+            Action::SleepTimer => {
+                self.open_input(InputKind::Command);
+                if let Some(input) = self.input.as_mut() {
+                    input.text = "sleep ".to_owned();
+                }
+            }
+            Action::AbRepeat => self.cycle_ab_repeat(),
+            Action::AudioDevice => self.pick_audio_device(),
+            Action::ToggleBookmark => self.toggle_bookmark(),
+            Action::ToggleMark => self.toggle_mark(),
+            Action::ArtistRadio => self.play_artist_songs(),
+            Action::Shuffle => self.shuffle_playlist(),
+            Action::Stats => self.open_stats(),
+            Action::History => self.open_history(),
+            Action::Album => self.open_album(),
+            Action::Artist => self.open_artist(),
+            Action::Bookmarks => self.open_bookmarks(),
+            Action::CycleLyricMode => self.cycle_lyric_mode(),
+            Action::ToggleRemaining => self.toggle_remaining(),
+            Action::ScrollLyricDown => self.scroll_lyric(1),
+            Action::ScrollLyricUp => self.scroll_lyric(-1),
+            Action::LyricEarlier => self.adjust_lyric_offset(-LYRIC_OFFSET_STEP),
+            Action::LyricLater => self.adjust_lyric_offset(LYRIC_OFFSET_STEP),
+        }
+    }
+
+    pub fn toggle_inspector(&mut self) {
+        self.inspector = match self.inspector {
+            Some(_) => None,
+            None => Some(InspectorView {
+                selected: None,
+                filter: String::new(),
+                paused: inspector::is_paused(),
+            }),
+        };
+        self.log_panel = None;
+        self.lyrics_view = false;
+    }
+
+    // Move the selected record down, or up if it is negative. It follows
+    // new records again once the newest one is selected.
+    fn select_record(&mut self, offset: isize) {
+        if let Some(view) = self.inspector.as_mut() {
+            let len = inspector::records(&view.filter).len();
+            let last = len.saturating_sub(1);
+            let selected = view
+                .selected
+                .unwrap_or(last)
+                .saturating_add_signed(offset)
+                .min(last);
+            view.selected = if selected == last {
+                None
+            } else {
+                Some(selected)
+            };
+        }
+    }
+
+    fn set_topic_filter(&mut self, filter: String) {
+        if let Some(view) = self.inspector.as_mut() {
+            view.filter = filter;
+            view.selected = None;
+        }
+    }
+
+    fn pause_inspector(&mut self) {
+        if let Some(view) = self.inspector.as_mut() {
+            view.paused = inspector::toggle_paused();
+        }
+    }
+
+    fn clear_inspector(&mut self) {
+        if let Some(view) = self.inspector.as_mut() {
+            inspector::clear();
+            view.selected = None;
+        }
+    }
+
+    // The name of the local store which remembers the chosen providers.
+    const SEARCH_SOURCES: &'static str = "search_sources";
+
+    // Search songs in the background, in the providers chosen last time.
+    fn search(&mut self, keyword: &str) {
+        let keyword = keyword.trim();
+        if keyword.is_empty() {
+            return;
+        }
+        let sources: Vec<String> = store::load(App::SEARCH_SOURCES);
+        let title = if sources.is_empty() {
+            tr!("搜索：{}", keyword)
+        } else {
+            tr!("搜索：{}（{}）", keyword, sources.join(", "))
+        };
+        self.fetch_songs(title, Command::Search(keyword.to_owned(), sources));
+    }
+
+    // Fetch songs in the background, which are shown in a page once they
+    // arrive.
+    fn fetch_songs(&mut self, title: String, cmd: Command) {
+        let client = self.client.clone();
+        let tx = self.events_tx.clone();
+        self.show_toast(format!("{}…", title));
+        thread::spawn(move || {
+            let songs = request_songs(&client, cmd);
+            let _ = tx.send(AppEvent::Songs(title, songs));
+        });
+    }
+
+    fn fetch_playlists(&mut self, title: String, cmd: Command) {
+        let client = self.client.clone();
+        let tx = self.events_tx.clone();
+        self.show_toast(format!("{}…", title));
+        thread::spawn(move || {
+            let playlists = request_json(&client, cmd);
+            let _ = tx.send(AppEvent::Playlists(title, playlists));
+        });
+    }
+
+    pub fn on_playlists(&mut self, title: String, playlists: io::Result<Vec<BriefPlaylist>>) {
+        match playlists {
+            Ok(playlists) => {
+                info!("{}, {} playlists", title, playlists.len());
+                self.show_toast(tr!("{}，{} 个歌单", title, playlists.len()));
+                self.push_page(Page::Playlists(PlaylistsPage::new(title, playlists)));
+            }
+            Err(e) => {
+                error!("{}: {}", title, e);
+                self.show_toast(tr!("{}失败：{}", title, e));
+            }
+        }
+    }
+
+    // Choose a provider for the picker kind, the one of the playing song is
+    // selected first. There is nothing to choose with a single provider.
+    fn pick_provider(&mut self, kind: PickerKind, title: &str) {
+        let providers = self.fetch_providers();
+        if providers.is_empty() {
+            return self.show_toast(tr!("获取音乐来源失败"));
+        }
+        let playing = self.inner.read().unwrap().current_song_uri.clone();
+        let selected = providers
+            .iter()
+            .position(|p| {
+                playing
+                    .as_ref()
+                    .is_some_and(|uri| uri.provider == p.identifier)
+            })
+            .unwrap_or(0);
+        let picker = Picker {
+            kind,
+            title: title.to_owned(),
+            items: providers.iter().map(provider_name).collect(),
+            selected,
+            checked: None,
+            values: providers.into_iter().map(|p| p.identifier).collect(),
+        };
+        if picker.items.len() == 1 {
+            self.on_picked(picker);
+        } else {
+            self.modals.push(Modal::Picker(picker));
+        }
+    }
+
+    // The uri is made up by fust since rpc has no command for it, like the
+    // ones of logging in.
+    fn open_daily_songs(&mut self, provider: &str) {
+        let uri = format!("fuo://{}/rec/daily_songs", provider);
+        self.fetch_songs(tr!("每日推荐：{}", provider), Command::Show(uri));
+    }
+
+    // Toplists like new songs and hot songs, which are shown as playlists.
+    fn open_charts(&mut self, provider: &str) {
+        let uri = format!("fuo://{}/toplists", provider);
+        self.fetch_playlists(tr!("排行榜：{}", provider), Command::Show(uri));
+    }
+
+    fn open_daily_playlists(&mut self, provider: &str) {
+        let uri = format!("fuo://{}/rec/daily_playlists", provider);
+        self.fetch_playlists(tr!("推荐歌单：{}", provider), Command::Show(uri));
+    }
+
+    pub fn on_songs(&mut self, title: String, songs: io::Result<Vec<BriefSong>>) {
+        match songs {
+            Ok(songs) => {
+                info!("{}, {} songs", title, songs.len());
+                self.show_toast(tr!("{}，{} 首歌曲", title, songs.len()));
+                self.push_page(Page::Songs(SongsPage::new(title, songs)));
+            }
+            Err(e) => {
+                error!("{}: {}", title, e);
+                self.show_toast(tr!("{}失败：{}", title, e));
+            }
+        }
+    }
+
+    // Choose the providers to search in, all of them if none is checked.
+    fn pick_search_sources(&mut self) {
+        let providers = self.fetch_providers();
+        if providers.is_empty() {
+            return self.show_toast(tr!("获取音乐来源失败"));
+        }
+        let sources: Vec<String> = store::load(App::SEARCH_SOURCES);
+        self.modals.push(Modal::Picker(Picker {
+            kind: PickerKind::SearchSources,
+            title: tr!("搜索来源").to_owned(),
+            items: providers.iter().map(provider_name).collect(),
+            selected: 0,
+            checked: Some(
+                providers
+                    .iter()
+                    .map(|p| sources.contains(&p.identifier))
+                    .collect(),
+            ),
+            values: providers.into_iter().map(|p| p.identifier).collect(),
+        }));
+    }
+
+    pub fn open_help(&mut self, keys: Vec<(String, &'static str)>) {
+        self.modals.push(Modal::Help(Help { keys, scroll: 0 }));
+    }
+
+    pub fn on_modal_key(&mut self, code: KeyCode) {
+        if code == KeyCode::Esc {
+            self.modals.pop();
+            return;
+        }
+        match self.modals.last_mut() {
+            Some(Modal::Picker(_)) => self.on_picker_key(code),
+            Some(Modal::Confirm(_)) => match code {
+                KeyCode::Char('y') | KeyCode::Enter => {
+                    if let Some(Modal::Confirm(confirm)) = self.modals.pop() {
+                        self.on_confirmed(confirm.kind);
+                    }
+                }
+                KeyCode::Char('n') => {
+                    self.modals.pop();
+                }
+                _ => {}
+            },
+            Some(Modal::Help(help)) => match code {
+                KeyCode::Down | KeyCode::Char('j') => {
+                    let last = help.keys.len().saturating_sub(1);
+                    help.scroll = (help.scroll + 1).min(last);
+                }
+                KeyCode::Up | KeyCode::Char('k') => help.scroll = help.scroll.saturating_sub(1),
+                KeyCode::Char('q') | KeyCode::Char('?') => {
+                    self.modals.pop();
+                }
+                _ => {}
+            },
+            None => {}
+        }
+    }
+
+    fn on_confirmed(&mut self, kind: ConfirmKind) {
+        match kind {
+            ConfirmKind::RemoveFromCollection(name, song) => {
+                self.remove_from_collection(name, song)
+            }
+        }
+    }
+
+    fn on_picker_key(&mut self, code: KeyCode) {
+        let picker = match self.modals.last_mut() {
+            Some(Modal::Picker(picker)) => picker,
+            _ => return,
+        };
+        let last = picker.items.len().saturating_sub(1);
+        match code {
+            KeyCode::Down | KeyCode::Char('j') => picker.selected = (picker.selected + 1).min(last),
+            KeyCode::Up | KeyCode::Char('k') => picker.selected = picker.selected.saturating_sub(1),
+            KeyCode::Char(' ') => {
+                if let Some(checked) = picker
+                    .checked
+                    .as_mut()
+                    .and_then(|c| c.get_mut(picker.selected))
+                {
+                    *checked = !*checked;
+                }
+            }
+            KeyCode::Enter => {
+                if let Some(Modal::Picker(picker)) = self.modals.pop() {
+                    self.on_picked(picker);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_picked(&mut self, picker: Picker) {
+        let values = picker.values();
+        match picker.kind {
+            PickerKind::SearchSources => {
+                let checked = picker.checked.as_deref().unwrap_or_default();
+                let sources: Vec<String> = values
+                    .iter()
+                    .zip(checked)
+                    .filter(|(_, &checked)| checked)
+                    .map(|(value, _)| value.clone())
+                    .collect();
+                store::save(App::SEARCH_SOURCES, &sources);
+                if sources.is_empty() {
+                    self.show_toast(tr!("在所有来源中搜索"));
+                } else {
+                    self.show_toast(tr!("在 {} 中搜索", sources.join(", ")));
+                }
+            }
+            PickerKind::DailySongs => {
+                if let Some(provider) = values.get(picker.selected) {
+                    self.open_daily_songs(&provider.clone());
+                }
+            }
+            PickerKind::DailyPlaylists => {
+                if let Some(provider) = values.get(picker.selected) {
+                    self.open_daily_playlists(&provider.clone());
+                }
+            }
+            PickerKind::Charts => {
+                if let Some(provider) = values.get(picker.selected) {
+                    self.open_charts(&provider.clone());
+                }
+            }
+            PickerKind::AddToCollection(ref uri) => {
+                if let Some(name) = values.get(picker.selected) {
+                    self.add_to_collection(name.clone(), uri.clone());
+                }
+            }
+            PickerKind::AddToPlaylist(ref song) => {
+                let playlist = values.get(picker.selected).and_then(|uri| uri.parse().ok());
+                if let Some(playlist) = playlist {
+                    let name = picker.items[picker.selected].clone();
+                    self.add_to_playlist(playlist, name, song.clone());
+                }
+            }
+            PickerKind::Fm => {
+                if let Some(provider) = values.get(picker.selected) {
+                    self.start_fm(provider.clone());
+                }
+            }
+            PickerKind::AudioDevice => {
+                if let Some(name) = values.get(picker.selected) {
+                    let description = picker.items[picker.selected].clone();
+                    self.set_audio_device(name.clone(), description);
+                }
+            }
+            PickerKind::Artist => {
+                if let Some(uri) = values.get(picker.selected) {
+                    let name = &picker.items[picker.selected];
+                    self.fetch_songs(tr!("歌手：{}", name), Command::Show(uri.clone()));
+                }
+            }
+        }
+    }
+
+    // Contexts of key bindings from the global one to the view on top, in
+    // the same order as `ui` chooses what to draw, and then the focused
+    // widget.
+    pub fn key_contexts(&self) -> Vec<Context> {
+        let view = if self.log_panel.is_some() {
+            Some(Context::Logs)
+        } else if self.inspector.is_some() {
+            Some(Context::Inspector)
+        } else if self.lyrics_view {
+            Some(Context::Lyrics)
+        } else if !self.pages.is_empty() {
+            Some(Context::Page)
+        } else {
+            None
+        };
+        let widget = self
+            .comments
+            .as_ref()
+            .filter(|pane| pane.focused)
+            .map(|_| Context::Comments);
+        std::iter::once(Context::Global)
+            .chain(view)
+            .chain(widget)
+            .collect()
+    }
+
+    // The page on top, unless a panel covers it.
+    pub fn shown_page(&self) -> Option<&Page> {
+        if self.log_panel.is_some() || self.inspector.is_some() || self.lyrics_view {
+            return None;
+        }
+        self.pages.last()
+    }
+
+    fn push_page(&mut self, page: Page) {
+        self.pages.push(page);
+        self.log_panel = None;
+        self.inspector = None;
+        self.lyrics_view = false;
+    }
+
+    fn select_in_page(&mut self, offset: isize) {
+        if let Some(page) = self.pages.last_mut() {
+            page.select(offset);
+        }
+    }
+
+    // Play the selected song, or open what is selected.
+    fn enter_page(&mut self) {
+        match self.pages.last() {
+            Some(Page::Songs(_)) => self.play_in_page(),
+            Some(Page::Collections(page)) => match page.selected() {
+                Some(LibraryItem::Collection(name)) => {
+                    let name = name.clone();
+                    self.fetch_collection(name);
+                }
+                Some(LibraryItem::Smart(i)) => self.open_smart_playlist(i),
+                None => {}
+            },
+            Some(Page::Playlists(page)) => {
+                if let Some(playlist) = page.selected() {
+                    let title = tr!("歌单：{}", playlist.name);
+                    let uri = playlist.uri().to_string();
+                    self.fetch_songs(title, Command::Show(uri));
+                }
+            }
+            Some(Page::Providers(page)) => {
+                if let Some(provider) = page.selected() {
+                    let provider = provider.identifier.clone();
+                    self.login(provider);
+                }
+            }
+            // Get a new QR code, like after the old one expires.
+            Some(Page::Login(page)) => {
+                let provider = page.provider.clone();
+                self.pages.pop();
+                self.login(provider);
+            }
+            Some(Page::Stats(_)) | None => {}
+        }
+    }
+
+    fn play_in_page(&mut self) {
+        let uri = match self.pages.last().and_then(Page::selected_song) {
+            Some(song) => song.uri(),
+            None => return,
+        };
+        match self.client.send_request(Command::Play(uri.clone())) {
+            Ok(resp) if resp.ok => info!("play {}", uri),
+            _ => {
+                error!("failed to play {}", uri);
+                self.show_toast(tr!("播放失败"));
+            }
+        }
+    }
+
+    fn enqueue_in_page(&mut self) {
+        if let Some(song) = self.pages.last().and_then(Page::selected_song) {
+            self.enqueue_next(song.clone());
+        }
+    }
+
+    // Replace the playlist with songs of the page, and play from the first.
+    // The selected playlist of the page is fetched and played as a whole.
+    fn play_all(&mut self) {
+        let songs = match self.shown_page() {
+            Some(Page::Songs(page)) if !page.songs.is_empty() => page.chosen_songs(),
+            Some(Page::Playlists(_)) => return self.enqueue_playlist(true),
+            _ => return,
+        };
+        match self.client.send_request(Command::Clear) {
+            Ok(resp) if resp.ok => {}
+            _ => {
+                error!("failed to clear the playlist");
+                return self.show_toast(tr!("播放失败"));
+            }
+        }
+        let added = add_songs(&self.client, &songs, |_| {});
+        self.clear_marks();
+        self.on_replaced(added);
+    }
+
+    // Append the marked songs of the page, or all of them, to the playlist
+    // in order. The selected playlist of the page is fetched and appended as
+    // a whole.
+    fn enqueue_all(&mut self) {
+        let songs = match self.shown_page() {
+            Some(Page::Songs(page)) => page.chosen_songs(),
+            Some(Page::Playlists(_)) => return self.enqueue_playlist(false),
+            _ => return,
+        };
+        let added = add_songs(&self.client, &songs, |_| {});
+        self.clear_marks();
+        self.on_appended(added);
+    }
+
+    // Fetch the songs of the selected playlist and add them in the
+    // background, since some playlists have thousands of songs.
+    fn enqueue_playlist(&mut self, replace: bool) {
+        let playlist = match self.shown_page() {
+            Some(Page::Playlists(page)) => match page.selected() {
+                Some(playlist) => playlist.clone(),
+                None => return,
+            },
+            _ => return,
+        };
+        let cmd = Command::Show(playlist.uri().to_string());
+        let name = tr!("歌单「{}」", playlist.name);
+        self.enqueue_fetched(name, replace, move |client| request_songs(client, cmd));
+    }
+
+    // Replace the playlist with top songs of the first artist of the chosen
+    // song, which are the songs of the artist shown by the server.
+    fn play_artist_songs(&mut self) {
+        let song = match self.chosen_brief_song() {
+            Some(song) => song,
+            None => return self.show_toast(tr!("没有选中的歌曲")),
+        };
+        let name = tr!("{} 的热门歌曲", song.artists_name);
+        self.enqueue_fetched(name, true, move |client| {
+            let uri = song.uri();
+            let detail: Song = request_json(client, Command::Show(uri.to_string()))?;
+            let artist = detail
+                .artists
+                .first()
+                .ok_or_else(|| io::Error::other(tr!("没有歌手信息")))?;
+            request_songs(client, Command::Show(artist.uri().to_string()))
+        });
+    }
+
+    // Songs of the album of the playing song, which is found in the details
+    // of the song.
+    fn open_album(&mut self) {
+        let uri = self.inner.read().unwrap().current_song_uri.clone();
+        let uri = match uri {
+            Some(uri) => uri,
+            None => return self.show_toast(tr!("没有正在播放的歌曲")),
+        };
+        let client = self.client.clone();
+        let tx = self.events_tx.clone();
+        self.show_toast(tr!("专辑…"));
+        thread::spawn(move || {
+            let album =
+                request_json::<Song>(&client, Command::Show(uri.to_string())).and_then(|song| {
+                    song.album
+                        .ok_or_else(|| io::Error::other(tr!("没有专辑信息")))
+                });
+            let (title, songs) = match album {
+                Ok(album) => {
+                    let cmd = Command::Show(album.uri().to_string());
+                    (tr!("专辑：{}", album.name), request_songs(&client, cmd))
+                }
+                Err(e) => (tr!("专辑").to_owned(), Err(e)),
+            };
+            let _ = tx.send(AppEvent::Songs(title, songs));
+        });
+    }
+
+    // Songs of the artist of the playing song, which is chosen from a popup if
+    // the song has several artists.
+    fn open_artist(&mut self) {
+        let uri = self.inner.read().unwrap().current_song_uri.clone();
+        let uri = match uri {
+            Some(uri) => uri,
+            None => return self.show_toast(tr!("没有正在播放的歌曲")),
+        };
+        let artists = match request_json::<Song>(&self.client, Command::Show(uri.to_string())) {
+            Ok(song) => song.artists,
+            Err(e) => {
+                error!("failed to fetch artists of {}: {}", uri, e);
+                return self.show_toast(tr!("获取歌手失败：{}", e));
+            }
+        };
+        match artists.as_slice() {
+            [] => self.show_toast(tr!("没有歌手信息")),
+            [artist] => {
+                let title = tr!("歌手：{}", artist.name);
+                self.fetch_songs(title, Command::Show(artist.uri().to_string()));
+            }
+            _ => self.modals.push(Modal::Picker(Picker {
+                kind: PickerKind::Artist,
+                title: tr!("歌手").to_owned(),
+                items: artists.iter().map(|artist| artist.name.clone()).collect(),
+                selected: 0,
+                checked: None,
+                values: artists
+                    .iter()
+                    .map(|artist| artist.uri().to_string())
+                    .collect(),
+            })),
+        }
+    }
+
+    // Fetch songs and add them in the background, since some playlists have
+    // thousands of songs. Nothing is replaced if there are no songs.
+    fn enqueue_fetched<F>(&mut self, name: String, replace: bool, fetch: F)
+    where
+        F: FnOnce(&Client) -> io::Result<Vec<BriefSong>> + Send + 'static,
+    {
+        let client = self.client.clone();
+        let tx = self.events_tx.clone();
+        self.show_toast(tr!("获取{}…", name));
+        thread::spawn(move || {
+            let result = fetch(&client).and_then(|songs| {
+                if songs.is_empty() {
+                    return Err(io::Error::other(tr!("没有歌曲")));
+                }
+                if replace {
+                    let resp = client.send_request(Command::Clear)?;
+                    if !resp.ok {
+                        return Err(io::Error::other(tr!("清空播放列表失败")));
+                    }
+                }
+                let total = songs.len();
+                Ok(add_songs(&client, &songs, |added| {
+                    let _ = tx.send(AppEvent::EnqueueProgress(name.clone(), added, total));
+                }))
+            });
+            let _ = tx.send(AppEvent::Enqueued(name, replace, result));
+        });
+    }
+
+    pub fn on_enqueue_progress(&mut self, name: String, added: usize, total: usize) {
+        self.show_toast(tr!("正在添加 {}：{}/{}", name, added, total));
+    }
+
+    pub fn on_enqueued(&mut self, name: String, replace: bool, songs: io::Result<Vec<BriefSong>>) {
+        match songs {
+            Ok(added) if replace => self.on_replaced(added),
+            Ok(added) => self.on_appended(added),
+            Err(e) => {
+                error!("failed to add {}: {}", name, e);
+                self.show_toast(tr!("添加{}失败：{}", name, e));
+            }
+        }
+    }
+
+    // Play the songs which replace the playlist.
+    fn on_replaced(&mut self, added: Vec<BriefSong>) {
+        if let Some(first) = added.first() {
+            let _ = self.client.send_request(Command::Play(first.uri()));
+        }
+        info!("play {} songs", added.len());
+        self.show_toast(tr!("播放 {} 首歌曲", added.len()));
+        self.undo_stack.clear();
+        self.on_playlist(Ok(added));
+    }
+
+    fn on_appended(&mut self, added: Vec<BriefSong>) {
+        {
+            let mut inner = self.inner.write().unwrap();
+            for song in &added {
+                let uri = song.uri();
+                if !inner.current_playlist.iter().any(|s| s.uri() == uri) {
+                    inner.current_playlist.push(song.clone());
+                }
+            }
+        }
+        info!("added {} songs", added.len());
+        self.on_playlist_changed();
+        self.show_toast(tr!("已添加 {} 首歌曲", added.len()));
+    }
+
+    // Mark the selected song of the page to be added, or unmark it, and
+    // select the next one.
+    fn toggle_mark(&mut self) {
+        if !matches!(self.shown_page(), Some(Page::Songs(page)) if !page.songs.is_empty()) {
+            return;
+        }
+        if let Some(Page::Songs(page)) = self.pages.last_mut() {
+            let selected = page.cursor.selected;
+            if !page.marked.remove(&selected) {
+                page.marked.insert(selected);
+            }
+        }
+        self.select_in_page(1);
+    }
+
+    fn clear_marks(&mut self) {
+        if let Some(Page::Songs(page)) = self.pages.last_mut() {
+            page.marked.clear();
+        }
+    }
+
+    // Uri and title of the song selected in the page or the playlist, or the
+    // playing one if nothing is selected.
+    fn chosen_song(&self) -> Option<(FuoUri, String)> {
+        self.chosen_brief_song()
+            .map(|song| (song.uri(), song.title))
+    }
+
+    // The same as `chosen_song`, with the playing one made up from its
+    // metadata.
+    fn chosen_brief_song(&self) -> Option<BriefSong> {
+        if let Some(page) = self.shown_page() {
+            return page.selected_song().cloned();
+        }
+        let inner = self.inner.read().unwrap();
+        let selected = self
+            .selected_index()
+            .and_then(|i| inner.current_playlist.get(i));
+        match selected {
+            Some(song) => Some(song.clone()),
+            None => inner.current_song(),
+        }
+    }
+
+    // Statistics of the history, opening it again cycles the time range.
+    fn open_stats(&mut self) {
+        let range = match self.pages.last() {
+            Some(Page::Stats(page)) if self.shown_page().is_some() => {
+                let range = page.range.next();
+                self.pages.pop();
+                range
+            }
+            _ => StatsRange::Week,
+        };
+        let plays = history::load();
+        let stats = Stats::new(&plays, range.since(history::unix_now()));
+        self.pages.retain(|page| !matches!(page, Page::Stats(_)));
+        self.push_page(Page::Stats(StatsPage { range, stats }));
+    }
+
+    fn history_shown(&self) -> bool {
+        matches!(self.shown_page(), Some(Page::Songs(page)) if page.played_at.is_some())
+    }
+
+    // Songs which have been played, the latest first.
+    fn open_history(&mut self) {
+        self.history_filter.clear();
+        self.pages.retain(|page| match page {
+            Page::Songs(page) => page.played_at.is_none(),
+            _ => true,
+        });
+        let mut page = SongsPage::new(String::new(), vec![]);
+        page.played_at = Some(vec![]);
+        self.push_page(Page::Songs(page));
+        self.filter_history(String::new());
+    }
+
+    // Show plays whose titles, artists or albums contain the keyword.
+    fn filter_history(&mut self, keyword: String) {
+        let page = match self.pages.last_mut() {
+            Some(Page::Songs(page)) if page.played_at.is_some() => page,
+            _ => return,
+        };
+        let lowercase = keyword.to_lowercase();
+        let (songs, played_at) = history::load()
+            .into_iter()
+            .rev()
+            .filter(|play| {
+                let song = &play.song;
+                [&song.title, &song.artists_name, &song.album_name]
+                    .iter()
+                    .any(|text| text.to_lowercase().contains(&lowercase))
+            })
+            .map(|play| (play.song, history::fmt_time(play.started_at)))
+            .unzip();
+        page.songs = songs;
+        page.played_at = Some(played_at);
+        page.cursor = Cursor::default();
+        page.marked.clear();
+        page.title = if keyword.is_empty() {
+            tr!("播放历史（/ 搜索）").to_owned()
+        } else {
+            tr!("播放历史：{}（{} 首）", keyword, page.songs.len())
+        };
+        self.history_filter = keyword;
+    }
+
+    fn toggle_bookmark(&mut self) {
+        let song = match self.chosen_brief_song() {
+            Some(song) => song,
+            None => return self.show_toast(tr!("没有选中的歌曲")),
+        };
+        if self.bookmarks.toggle(&song) {
+            info!("bookmarked {}", song.uri());
+            self.show_toast(tr!("已加入书签：{}", song.title));
+        } else {
+            info!("removed the bookmark of {}", song.uri());
+            self.show_toast(tr!("已移除书签：{}", song.title));
+        }
+        self.refresh_bookmarks();
+    }
+
+    // `rate <0-5>` for the chosen song, 0 clears the rating but keeps the
+    // bookmark.
+    fn rate_song(&mut self, arg: &str) {
+        let rating = match arg.parse::<u8>() {
+            Ok(rating) if rating <= MAX_RATING => rating,
+            _ => return self.show_toast(tr!("用法：rate <0-{}>", MAX_RATING)),
+        };
+        let song = match self.chosen_brief_song() {
+            Some(song) => song,
+            None => return self.show_toast(tr!("没有选中的歌曲")),
+        };
+        info!("rated {} {}", song.uri(), rating);
+        self.bookmarks.rate(&song, rating);
+        self.show_toast(tr!(
+            "{}：{}",
+            song.title,
+            stars(rating, self.config.glyphs())
+        ));
+        self.refresh_bookmarks();
+    }
+
+    fn open_bookmarks(&mut self) {
+        let songs = self.bookmarks.songs();
+        if songs.is_empty() {
+            return self.show_toast(tr!("没有书签，按 m 将歌曲加入书签"));
+        }
+        self.pages.retain(|page| match page {
+            Page::Songs(page) => !page.bookmarks,
+            _ => true,
+        });
+        let mut page = SongsPage::new(tr!("书签").to_owned(), songs);
+        page.bookmarks = true;
+        self.push_page(Page::Songs(page));
+    }
+
+    // Show the changed bookmarks in their page, keeping the selected row.
+    fn refresh_bookmarks(&mut self) {
+        for page in self.pages.iter_mut() {
+            if let Page::Songs(page) = page {
+                if page.bookmarks {
+                    page.songs = self.bookmarks.songs();
+                    let last = page.songs.len().saturating_sub(1);
+                    page.cursor.selected = page.cursor.selected.min(last);
+                }
+            }
+        }
+    }
+
+    // Songs of the library which match the rules, the library being songs of
+    // all collections and the bookmarks.
+    fn open_smart_playlist(&mut self, i: usize) {
+        let playlist = match self.config.smart_playlists.get(i) {
+            Some(playlist) => playlist.clone(),
+            None => return,
+        };
+        let bookmarked = self.bookmarks.songs();
+        let client = self.client.clone();
+        let tx = self.events_tx.clone();
+        let title = tr!("智能歌单：{}", playlist.name);
+        self.show_toast(format!("{}…", title));
+        thread::spawn(move || {
+            let mut library = vec![];
+            match exec_json::<Vec<String>>(&client, Command::Collections) {
+                Ok(names) => {
+                    for name in names {
+                        match collection_songs(&client, &name) {
+                            Ok(songs) => library.extend(songs),
+                            Err(e) => error!("failed to fetch the collection {}: {}", name, e),
+                        }
+                    }
+                }
+                Err(e) => error!("failed to list collections: {}", e),
+            }
+            library.extend(bookmarked);
+            let mut seen = HashSet::new();
+            let songs = library
+                .into_iter()
+                .filter(|song| playlist.matches(song) && seen.insert(song.uri()))
+                .collect();
+            let _ = tx.send(AppEvent::Songs(title, Ok(songs)));
+        });
+    }
+
+    fn fetch_collections(&self) -> io::Result<Vec<String>> {
+        exec_json(&self.client, Command::Collections)
+    }
+
+    fn open_library(&mut self) {
+        match self.fetch_collections() {
+            Ok(names) => {
+                self.pages
+                    .retain(|page| !matches!(page, Page::Collections(_)));
+                let smart = self
+                    .config
+                    .smart_playlists
+                    .iter()
+                    .map(|playlist| playlist.name.clone())
+                    .collect();
+                self.push_page(Page::Collections(CollectionsPage::new(names, smart)));
+            }
+            Err(e) => {
+                error!("failed to list collections: {}", e);
+                self.show_toast(tr!("获取收藏集失败：{}", e));
+            }
+        }
+    }
+
+    fn fetch_collection(&mut self, name: String) {
+        let client = self.client.clone();
+        let tx = self.events_tx.clone();
+        thread::spawn(move || {
+            let songs = collection_songs(&client, &name);
+            let _ = tx.send(AppEvent::Collection(name, songs));
+        });
+    }
+
+    pub fn on_collection(&mut self, name: String, songs: io::Result<Vec<BriefSong>>) {
+        match songs {
+            Ok(songs) => {
+                let mut page = SongsPage::new(tr!("收藏集：{}", name), songs);
+                page.collection = Some(name);
+                self.push_page(Page::Songs(page));
+            }
+            Err(e) => {
+                error!("failed to fetch the collection {}: {}", name, e);
+                self.show_toast(tr!("获取收藏集失败：{}", e));
+            }
+        }
+    }
+
+    // Choose the collection to add the chosen song to.
+    fn pick_collection(&mut self) {
+        let (uri, title) = match self.chosen_song() {
+            Some(song) => song,
+            None => return self.show_toast(tr!("没有选中的歌曲")),
+        };
+        match self.fetch_collections() {
+            Ok(names) if names.is_empty() => self.show_toast(tr!("没有收藏集")),
+            Ok(names) => {
+                self.modals.push(Modal::Picker(Picker {
+                    kind: PickerKind::AddToCollection(uri),
+                    title: tr!("将 {} 添加到收藏集", title),
+                    items: names,
+                    selected: 0,
+                    checked: None,
+                    values: vec![],
+                }));
+            }
+            Err(e) => {
+                error!("failed to list collections: {}", e);
+                self.show_toast(tr!("获取收藏集失败：{}", e));
+            }
+        }
+    }
+
+    fn add_to_collection(&mut self, name: String, uri: FuoUri) {
+        match self
+            .client
+            .send_request(Command::CollectionAdd(name.clone(), uri.clone()))
+        {
+            Ok(resp) if resp.ok => {
+                info!("added {} to the collection {}", uri, name);
+                self.show_toast(tr!("已添加到收藏集：{}", name));
+                // Fetch the shown collection again for the new song.
+                let shown = self.pages.iter().any(|page| match page {
+                    Page::Songs(page) => page.collection.as_ref() == Some(&name),
+                    _ => false,
+                });
+                if shown {
+                    self.pages.retain(|page| match page {
+                        Page::Songs(page) => page.collection.as_ref() != Some(&name),
+                        _ => true,
+                    });
+                    self.fetch_collection(name);
+                }
+            }
+            _ => {
+                error!("failed to add {} to the collection {}", uri, name);
+                self.show_toast(tr!("添加到收藏集失败"));
+            }
+        }
+    }
+
+    // Ask before removing the selected song, which cannot be undone unlike
+    // removing from the playlist.
+    fn confirm_remove_from_collection(&mut self) {
+        let (name, song) = match self.pages.last() {
+            Some(Page::Songs(page)) => {
+                match (&page.collection, page.songs.get(page.cursor.selected)) {
+                    (Some(name), Some(song)) => (name.clone(), song.clone()),
+                    _ => return,
+                }
+            }
+            _ => return,
+        };
+        self.modals.push(Modal::Confirm(Confirm {
+            text: tr!("将 {} 从收藏集 {} 移除？", song.title, name),
+            kind: ConfirmKind::RemoveFromCollection(name, song),
+        }));
+    }
+
+    fn remove_from_collection(&mut self, name: String, song: BriefSong) {
+        let uri = song.uri();
+        match self
+            .client
+            .send_request(Command::CollectionRemove(name.clone(), uri.clone()))
+        {
+            Ok(resp) if resp.ok => {
+                info!("removed {} from the collection {}", uri, name);
+                if let Some(Page::Songs(page)) = self.pages.last_mut() {
+                    page.songs.retain(|s| s.uri() != uri);
+                    let last = page.songs.len().saturating_sub(1);
+                    page.cursor.selected = page.cursor.selected.min(last);
+                }
+                self.show_toast(tr!("已从收藏集移除：{}", song.title));
+            }
+            _ => {
+                error!("failed to remove {} from the collection {}", uri, name);
+                self.show_toast(tr!("从收藏集移除失败"));
+            }
+        }
+    }
+
+    // Fetch the current audio output device in the background, which is only
+    // known with the mpv player backend.
+    pub fn sync_audio_device(&mut self) {
+        let client = self.client.clone();
+        let tx = self.events_tx.clone();
+        thread::spawn(move || {
+            let devices = exec_json(&client, Command::AudioDevices);
+            let _ = tx.send(AppEvent::AudioDevices(devices));
+        });
+    }
+
+    pub fn on_audio_devices(&mut self, devices: io::Result<AudioDevices>) {
+        match devices {
+            Ok(devices) => self.audio_device = Some(devices.current_description()),
+            Err(e) => {
+                debug!("failed to fetch audio devices: {}", e);
+                self.audio_device = None;
+            }
+        }
+    }
+
+    fn pick_audio_device(&mut self) {
+        let devices = match exec_json::<AudioDevices>(&self.client, Command::AudioDevices) {
+            Ok(devices) if devices.devices.is_empty() => {
+                return self.show_toast(tr!("没有音频输出设备"))
+            }
+            Ok(devices) => devices,
+            Err(e) => {
+                error!("failed to fetch audio devices: {}", e);
+                return self.show_toast(tr!("获取音频输出设备失败：{}", e));
+            }
+        };
+        self.audio_device = Some(devices.current_description());
+        let selected = devices
+            .devices
+            .iter()
+            .position(|device| device.name == devices.current)
+            .unwrap_or(0);
+        self.modals.push(Modal::Picker(Picker {
+            kind: PickerKind::AudioDevice,
+            title: tr!("音频输出设备").to_owned(),
+            items: devices
+                .devices
+                .iter()
+                .map(|device| match device.description.is_empty() {
+                    true => device.name.clone(),
+                    false => device.description.clone(),
+                })
+                .collect(),
+            selected,
+            checked: None,
+            values: devices.devices.into_iter().map(|d| d.name).collect(),
+        }));
+    }
+
+    fn set_audio_device(&mut self, name: String, description: String) {
+        match self
+            .client
+            .send_request(Command::SetAudioDevice(name.clone()))
+        {
+            Ok(resp) if resp.ok => {
+                info!("switched audio device to {}", name);
+                self.show_toast(tr!("已切换音频输出：{}", description));
+                self.audio_device = Some(description);
+            }
+            _ => {
+                error!("failed to switch audio device to {}", name);
+                self.show_toast(tr!("切换音频输出设备失败"));
+            }
+        }
+    }
+
+    // Choose one of the playlists of the current user on the provider of the
+    // chosen song, from the uri made up like the one of daily
+    // recommendations.
+    fn pick_playlist(&mut self) {
+        let (uri, title) = match self.chosen_song() {
+            Some(song) => song,
+            None => return self.show_toast(tr!("没有选中的歌曲")),
+        };
+        let cmd = Command::Show(format!("fuo://{}/current_user/playlists", uri.provider));
+        match request_json::<Vec<BriefPlaylist>>(&self.client, cmd) {
+            Ok(playlists) if playlists.is_empty() => self.show_toast(tr!("没有可添加的歌单")),
+            Ok(playlists) => {
+                self.modals.push(Modal::Picker(Picker {
+                    kind: PickerKind::AddToPlaylist(uri),
+                    title: tr!("将 {} 添加到歌单", title),
+                    items: playlists.iter().map(|p| p.name.clone()).collect(),
+                    selected: 0,
+                    checked: None,
+                    values: playlists.iter().map(|p| p.uri().to_string()).collect(),
+                }));
+            }
+            Err(e) => {
+                error!("failed to list playlists of {}: {}", uri.provider, e);
+                self.show_toast(tr!("获取歌单失败：{}", e));
+            }
+        }
+    }
+
+    fn add_to_playlist(&mut self, playlist: FuoUri, name: String, song: FuoUri) {
+        match self
+            .client
+            .send_request(Command::PlaylistAdd(playlist.clone(), song.clone()))
+        {
+            Ok(resp) if resp.ok => {
+                info!("added {} to {}", song, playlist);
+                self.show_toast(tr!("已添加到歌单：{}", name));
+            }
+            Ok(resp) => {
+                let msg = String::from_utf8_lossy(&resp.body).trim().to_owned();
+                error!("failed to add {} to {}: {}", song, playlist, msg);
+                self.show_toast(tr!("添加到歌单失败：{}", msg));
+            }
+            Err(e) => {
+                error!("failed to add {} to {}: {}", song, playlist, e);
+                self.show_toast(tr!("添加到歌单失败：{}", e));
+            }
+        }
+    }
+
+    // Songs similar to the selected one, or the playing one if nothing is
+    // selected.
+    fn open_similar_songs(&mut self) {
+        let (uri, title) = match self.chosen_song() {
+            Some(song) => song,
+            None => return self.show_toast(tr!("没有选中的歌曲")),
+        };
+        // Made up by fust like the uris of daily recommendations.
+        let cmd = Command::Show(format!("{}/similar", uri));
+        self.fetch_songs(tr!("相似歌曲：{}", title), cmd);
+    }
+
+    fn fetch_providers(&self) -> Vec<Provider> {
+        match self.client.send_request(Command::Show("fuo://".to_owned())) {
+            Ok(resp) if resp.ok => models::parse_providers(&resp.body),
+            _ => vec![],
+        }
+    }
+
+    fn open_providers(&mut self) {
+        let providers = self.fetch_providers();
+        if providers.is_empty() {
+            return self.show_toast(tr!("获取音乐来源失败"));
+        }
+        self.pages
+            .retain(|page| !matches!(page, Page::Providers(_)));
+        self.push_page(Page::Providers(ProvidersPage::new(providers)));
+    }
+
+    // Show the QR code of the provider, and poll whether it is scanned in
+    // the background until the page is closed.
+    fn login(&mut self, provider: String) {
+        let uri = format!("fuo://{}/login/qrcode", provider);
+        let qrcode = match request_json::<LoginQrcode>(&self.client, Command::Show(uri)) {
+            Ok(qrcode) => qrcode,
+            Err(e) => {
+                error!("failed to get the login QR code of {}: {}", provider, e);
+                return self.show_toast(tr!("{} 不支持扫码登录：{}", provider, e));
+            }
+        };
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let client = self.client.clone();
+        let tx = self.events_tx.clone();
+        let uri = format!("fuo://{}/login/qrcode/{}", provider, qrcode.key);
+        let (flag, name) = (cancelled.clone(), provider.clone());
+        thread::spawn(move || {
+            while !flag.load(Ordering::SeqCst) {
+                thread::sleep(LOGIN_POLL_INTERVAL);
+                let status = client
+                    .send_request(Command::Show(uri.clone()))
+                    .and_then(|resp| {
+                        serde_json::from_slice::<LoginStatus>(&resp.body)
+                            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+                    });
+                let done = match &status {
+                    Ok(status) => matches!(status.state, LoginState::Ok | LoginState::Expired),
+                    Err(_) => true,
+                };
+                let _ = tx.send(AppEvent::Login(name.clone(), status));
+                if done {
+                    return;
+                }
+            }
+        });
+        self.push_page(Page::Login(LoginPage {
+            provider,
+            qr: QrCode::encode(qrcode.url.as_bytes()),
+            status: LoginStatus::default(),
+            cancelled,
+        }));
+    }
+
+    pub fn on_login(&mut self, provider: String, status: io::Result<LoginStatus>) {
+        let page = match self.pages.last_mut() {
+            Some(Page::Login(page)) if page.provider == provider => page,
+            // The page is closed.
+            _ => return,
+        };
+        let status = match status {
+            Ok(status) => status,
+            Err(e) => {
+                error!("failed to poll the login status of {}: {}", provider, e);
+                return self.show_toast(tr!("登录失败：{}", e));
+            }
+        };
+        page.status = status.clone();
+        if status.state != LoginState::Ok {
+            return;
+        }
+        let name = status.user.map(|user| user.name).unwrap_or_default();
+        info!("logged in {} as {}", provider, name);
+        self.pages.pop();
+        for page in self.pages.iter_mut() {
+            if let Page::Providers(page) = page {
+                let items = page.providers.iter_mut();
+                for item in items.filter(|item| item.provider.identifier == provider) {
+                    item.user = Some(name.clone());
+                }
+            }
+        }
+        self.show_toast(tr!("已登录 {}：{}", provider, name));
+    }
+
+    fn start_fm(&mut self, provider: String) {
+        match self.client.send_request(Command::StartFm(provider.clone())) {
+            Ok(resp) if resp.ok => {
+                info!("started the FM of {}", provider);
+                self.show_toast(tr!("已开启私人 FM：{}", provider));
+                // Show the upcoming songs in the playlist.
+                self.pages.clear();
+                self.log_panel = None;
+                self.inspector = None;
+                self.lyrics_view = false;
+                self.fm = Some(Fm {
+                    provider,
+                    uri: None,
+                });
+            }
+            _ => {
+                error!("failed to start the FM of {}", provider);
+                self.show_toast(tr!("{} 不支持私人 FM", provider));
+            }
+        }
+    }
+
+    fn stop_fm(&mut self) {
+        match self.client.send_request(Command::StopFm) {
+            Ok(resp) if resp.ok => {
+                info!("stopped the FM");
+                self.fm = None;
+                self.show_toast(tr!("已关闭私人 FM"));
+            }
+            _ => error!("failed to stop the FM"),
+        }
+    }
+
+    // Fetch the playlist again when the song changes, which is when the FM
+    // adds new songs.
+    fn sync_fm(&mut self) {
+        let uri = self.inner.read().unwrap().current_song_uri.clone();
+        match self.fm.as_mut() {
+            Some(fm) if fm.uri != uri => fm.uri = uri,
+            _ => return,
+        }
+        self.sync_current_playlist();
+    }
+
+    fn dislike(&mut self) {
+        match self.client.send_request(Command::Dislike) {
+            Ok(resp) if resp.ok => {
+                info!("disliked the current song");
+                self.show_toast(tr!("已标记为不喜欢"));
+            }
+            _ => {
+                error!("failed to dislike the current song");
+                self.show_toast(tr!("标记不喜欢失败"));
+            }
+        }
+    }
+
+    // Fetch whether the song is liked when it changes, from the uri made up
+    // like the one of comments.
+    fn sync_liked(&mut self) {
+        let uri = self.inner.read().unwrap().current_song_uri.clone();
+        if self.liked.uri == uri {
+            return;
+        }
+        self.liked = Liked {
+            uri: uri.clone(),
+            state: None,
+        };
+        let uri = match uri {
+            Some(uri) => uri,
+            None => return,
+        };
+        let client = self.client.clone();
+        let tx = self.events_tx.clone();
+        thread::spawn(move || {
+            let liked = request_json(&client, Command::Show(format!("{}/liked", uri)));
+            let _ = tx.send(AppEvent::Liked(uri, liked));
+        });
+    }
+
+    pub fn on_liked(&mut self, uri: FuoUri, liked: io::Result<bool>) {
+        if self.liked.uri.as_ref() != Some(&uri) {
+            return;
+        }
+        match liked {
+            Ok(liked) => self.liked.state = Some(liked),
+            Err(e) => debug!("failed to fetch whether {} is liked: {}", uri, e),
+        }
+    }
+
+    fn toggle_like(&mut self) {
+        let uri = match &self.liked.uri {
+            Some(uri) => uri.clone(),
+            None => return self.show_toast(tr!("没有正在播放的歌曲")),
+        };
+        let like = self.liked.state != Some(true);
+        match self.client.send_request(Command::Like(uri.clone(), like)) {
+            Ok(resp) if resp.ok => {
+                info!("{} {}", if like { "liked" } else { "unliked" }, uri);
+                self.liked.state = Some(like);
+                self.show_toast(if like {
+                    tr!("已喜欢")
+                } else {
+                    tr!("已取消喜欢")
+                });
+            }
+            _ => {
+                error!("failed to like {}", uri);
+                self.show_toast(tr!("操作失败，来源可能不支持喜欢歌曲"));
+            }
+        }
+    }
+
+    // Open the page of the playing song in the browser.
+    fn open_web_page(&mut self) {
+        let uri = self.inner.read().unwrap().current_song_uri.clone();
+        let uri = match uri {
+            Some(uri) => uri,
+            None => return self.show_toast(tr!("没有正在播放的歌曲")),
+        };
+        let url = match request_json::<Song>(&self.client, Command::Show(uri.to_string())) {
+            Ok(song) if !song.web_url.is_empty() => song.web_url,
+            Ok(_) => return self.show_toast(tr!("这首歌曲没有网页")),
+            Err(e) => {
+                error!("failed to show {}: {}", uri, e);
+                return self.show_toast(tr!("获取歌曲信息失败：{}", e));
+            }
+        };
+        let opener = if cfg!(target_os = "macos") {
+            "open"
+        } else {
+            "xdg-open"
+        };
+        let child = process::Command::new(opener)
+            .arg(&url)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match child {
+            Ok(mut child) => {
+                info!("open {}", url);
+                self.show_toast(tr!("已在浏览器中打开：{}", url));
+                // Reap it so that it does not become a zombie.
+                thread::spawn(move || child.wait());
+            }
+            Err(e) => {
+                error!("failed to run {}: {}", opener, e);
+                self.show_toast(tr!("无法打开浏览器：{}", e));
+            }
+        }
+    }
+
+    // Play the MV of the playing song with the video player, the song is
+    // paused until the player exits.
+    fn play_mv(&mut self) {
+        let uri = self.inner.read().unwrap().current_song_uri.clone();
+        let uri = match uri {
+            Some(uri) => uri,
+            None => return self.show_toast(tr!("没有正在播放的歌曲")),
+        };
+        let cmd = Command::Show(format!("{}/mv", uri));
+        let (url, title) = match request_json::<Option<Video>>(&self.client, cmd) {
+            Ok(Some(video)) => match video.stream_url() {
+                Some(url) => (url.to_owned(), video.title.clone()),
+                None => return self.show_toast(tr!("无法获取 MV 的播放地址")),
+            },
+            Ok(None) => return self.show_toast(tr!("这首歌曲没有 MV")),
+            Err(e) => {
+                error!("failed to get the MV of {}: {}", uri, e);
+                return self.show_toast(tr!("获取 MV 失败：{}", e));
+            }
+        };
+        let (program, args) = self.config.video.command.split_first().unwrap();
+        let child = process::Command::new(program)
+            .args(args)
+            .arg(&url)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                error!("failed to run {}: {}", program, e);
+                return self.show_toast(tr!("无法运行 {}：{}", program, e));
+            }
+        };
+        info!("play the MV of {} with {}", uri, program);
+        let playing = self.inner.read().unwrap().state == PlayerState::Playing;
+        if playing {
+            let _ = self.client.send_request(Command::Pause);
+        }
+        self.show_toast(tr!("正在播放 MV：{}", title));
+        let tx = self.events_tx.clone();
+        thread::spawn(move || {
+            let _ = child.wait();
+            let _ = tx.send(AppEvent::VideoExited(playing));
+        });
+    }
+
+    pub fn on_video_exited(&mut self, resume: bool) {
+        info!("the video player exited");
+        if resume {
+            let _ = self.client.send_request(Command::Resume);
+        }
+    }
+
+    fn toggle_comments(&mut self) {
+        self.comments = match self.comments {
+            Some(_) => None,
+            None => Some(CommentsPane {
+                uri: None,
+                comments: None,
+                scroll: 0,
+                focused: false,
+            }),
+        };
+        self.sync_comments();
+    }
+
+    // Move keys to the comments pane, or back to the main view.
+    fn focus_comments(&mut self) {
+        if let Some(pane) = self.comments.as_mut() {
+            pane.focused = !pane.focused;
+        }
+    }
+
+    // Fetch the comments again when the song changes.
+    fn sync_comments(&mut self) {
+        let uri = self.inner.read().unwrap().current_song_uri.clone();
+        let pane = match self.comments.as_mut() {
+            Some(pane) if pane.uri != uri => pane,
+            _ => return,
+        };
+        pane.uri = uri.clone();
+        pane.comments = None;
+        pane.scroll = 0;
+        let uri = match uri {
+            Some(uri) => uri,
+            None => return,
+        };
+        let client = self.client.clone();
+        let tx = self.events_tx.clone();
+        thread::spawn(move || {
+            let cmd = Command::Show(format!("{}/hot_comments", uri));
+            let comments = request_json(&client, cmd);
+            let _ = tx.send(AppEvent::Comments(uri, comments));
+        });
+    }
+
+    pub fn on_comments(&mut self, uri: FuoUri, comments: io::Result<Vec<Comment>>) {
+        let pane = match self.comments.as_mut() {
+            Some(pane) if pane.uri.as_ref() == Some(&uri) => pane,
+            // The song changed or the pane is closed.
+            _ => return,
+        };
+        if let Err(e) = &comments {
+            error!("failed to fetch comments of {}: {}", uri, e);
+        }
+        pane.comments = Some(comments.map_err(|e| e.to_string()));
+    }
+
+    fn scroll_comments(&mut self, lines: i16) {
+        if let Some(pane) = self.comments.as_mut() {
+            pane.scroll = pane.scroll.saturating_add_signed(lines * 3);
+        }
+    }
+
+    // Close the view on top, like the log panel or a page.
+    fn back(&mut self) {
+        if self.log_panel.is_some() {
+            self.log_panel = None;
+        } else if self.inspector.is_some() {
+            self.inspector = None;
+        } else if self.lyrics_view {
+            self.lyrics_view = false;
+        } else {
+            self.pages.pop();
+        }
+    }
+
+    pub fn toggle_lyrics(&mut self) {
+        self.lyrics_view = !self.lyrics_view;
+        self.lyrics_scroll = 0;
+        if self.lyrics_view {
+            self.log_panel = None;
+            self.inspector = None;
+            self.sync_timed_lyric();
+            self.fetch_timed_lyric();
+        }
+    }
+
+    pub fn toggle_logs(&mut self) {
+        self.inspector = None;
+        self.lyrics_view = false;
+        self.log_panel = match self.log_panel {
+            Some(_) => None,
+            None => Some(LogPanel {
+                scroll: 0,
+                seen: logger::recent().1,
+            }),
+        };
+    }
+
+    // Move the middle line of the lyrics view down by lines, or up if it is
+    // negative, at most the number of the lines away from the current one.
+    fn scroll_lyric(&mut self, lines: isize) {
+        let len = self
+            .timed_lyric
+            .as_ref()
+            .and_then(|timed| timed.lines.as_ref())
+            .map_or(0, |lines| lines.len()) as isize;
+        self.lyrics_scroll = (self.lyrics_scroll + lines).clamp(-len, len);
+    }
+
+    // Scroll the log panel up by lines, or down if it is negative. It
+    // follows new records again once it is scrolled to the bottom.
+    fn scroll_logs(&mut self, lines: isize) {
+        if let Some(panel) = self.log_panel.as_mut() {
+            let (entries, _) = logger::recent();
+            let max = entries.len().saturating_sub(1);
+            panel.scroll = panel.scroll.saturating_add_signed(lines).min(max);
+        }
+    }
+
+    // Seek forward, or backward if `secs` is negative.
+    pub fn seek_relative(&self, secs: f64) {
+        let (position, duration) = {
+            let inner = self.inner.read().unwrap();
+            (inner.progress.current(), inner.duration)
+        };
+        let position = (position.as_secs_f64() + secs).clamp(0.0, duration.as_secs_f64());
+        let position = Duration::from_secs_f64(position);
+        match self.client.send_request(Command::Seek(position)) {
+            Ok(resp) if resp.ok => info!("seek to {:?}", position),
+            _ => error!("failed to seek to {:?}", position),
+        }
+    }
+
+    pub fn toggle_playpause(&mut self) {
+        match self.client.send_request(Command::Toggle) {
+            Ok(resp) if resp.ok => info!("toggled playpause"),
+            _ => {
+                error!("failed to toggle playpause");
+                self.show_toast(tr!("切换播放状态失败"));
+            }
+        }
+    }
+
+    pub fn play_next(&mut self) {
+        match self.client.send_request(Command::Next) {
+            Ok(resp) if resp.ok => info!("switched to next song"),
+            _ => {
+                error!("failed to switch to next song");
+                self.show_toast(tr!("切换歌曲失败"));
+            }
+        }
+    }
+
+    pub fn play_previous(&mut self) {
+        match self.client.send_request(Command::Previous) {
+            Ok(resp) if resp.ok => info!("switched to previous song"),
+            _ => {
+                error!("failed to switch to previous song");
+                self.show_toast(tr!("切换歌曲失败"));
+            }
+        }
+    }
+
+    pub fn cycle_repeat(&mut self) {
+        let mode = self.inner.read().unwrap().playback_mode.next_repeat();
+        self.set_playback_mode(mode);
+    }
+
+    pub fn toggle_random(&mut self) {
+        let mode = self.inner.read().unwrap().playback_mode.toggle_random();
+        self.set_playback_mode(mode);
+    }
+
+    fn set_playback_mode(&mut self, mode: PlaybackMode) {
+        match self.client.send_request(Command::SetPlaybackMode(mode)) {
+            Ok(resp) if resp.ok => info!("set playback mode to {}", mode.name()),
+            _ => {
+                error!("failed to set playback mode to {}", mode.name());
+                self.show_toast(tr!("设置播放模式失败"));
+            }
+        }
+    }
+
+    // Remove the selected song from the current playlist.
+    //
+    // The local list is updated before the server responds so that the
+    // view does not lag behind, and is restored if the request fails.
+    pub fn remove_selected(&mut self) {
+        let i = match self.selected_index() {
+            Some(i) => i,
+            None => return,
+        };
+        let song = {
+            let mut inner = self.inner.write().unwrap();
+            if i >= inner.current_playlist.len() {
+                return;
+            }
+            inner.current_playlist.remove(i)
+        };
+        self.on_playlist_changed();
+        let uri = song.uri();
+        match self.client.send_request(Command::Remove(uri.clone())) {
+            Ok(resp) if resp.ok => {
+                info!("removed {}", uri);
+                self.undo_stack.push(PlaylistEdit::Removed(i, song));
+            }
+            _ => {
+                error!("failed to remove {}", uri);
+                self.inner.write().unwrap().current_playlist.insert(i, song);
+                self.on_playlist_changed();
+            }
+        }
+    }
+
+    pub fn enqueue_selected_next(&mut self) {
+        let song = {
+            let inner = self.inner.read().unwrap();
+            match self
+                .selected_index()
+                .and_then(|i| inner.current_playlist.get(i))
+            {
+                Some(song) => song.clone(),
+                None => return,
+            }
+        };
+        self.enqueue_next(song);
+    }
+
+    // Insert the song after the current one in the play queue.
+    pub fn enqueue_next(&mut self, song: BriefSong) {
+        let uri = song.uri();
+        match self.client.send_request(Command::Insert(uri.clone())) {
+            Ok(resp) if resp.ok => {
+                info!("inserted {}", uri);
+                {
+                    let mut inner = self.inner.write().unwrap();
+                    let current = inner.current_song_uri.clone();
+                    let playlist = &mut inner.current_playlist;
+                    // The server moves the song if it is already in the playlist.
+                    playlist.retain(|s| s.uri() != uri);
+                    let i = match playlist.iter().position(|s| Some(s.uri()) == current) {
+                        Some(i) => i + 1,
+                        None => 0,
+                    };
+                    playlist.insert(i, song.clone());
+                }
+                self.on_playlist_changed();
+                self.show_toast(tr!("下一首播放：{}", song.title));
+            }
+            _ => {
+                error!("failed to insert {}", uri);
+                self.show_toast(tr!("添加失败：{}", song.title));
+            }
+        }
+    }
+
+    pub fn move_selected_up(&mut self) {
+        if self.playlist_filtered.is_some() {
+            self.show_toast(tr!("过滤时无法移动歌曲"));
+            return;
+        }
+        if let Some(i) = self.playlist_state.selected() {
+            if i > 0 && self.move_song(i, i - 1) {
+                self.undo_stack.push(PlaylistEdit::Moved(i, i - 1));
+            }
+        }
+    }
+
+    pub fn move_selected_down(&mut self) {
+        if self.playlist_filtered.is_some() {
+            self.show_toast(tr!("过滤时无法移动歌曲"));
+            return;
+        }
+        if let Some(i) = self.playlist_state.selected() {
+            if self.move_song(i, i + 1) {
+                self.undo_stack.push(PlaylistEdit::Moved(i, i + 1));
+            }
+        }
+    }
+
+    // Shuffle the shown songs once, with the playing one first, so they are
+    // played in the new order by the sequential mode.
+    fn shuffle_playlist(&mut self) {
+        if self.playlist_filtered.is_some() {
+            return self.show_toast(tr!("过滤时无法打乱歌曲"));
+        }
+        let (len, current) = {
+            let inner = self.inner.read().unwrap();
+            let current = inner
+                .current_song_uri
+                .as_ref()
+                .and_then(|uri| inner.current_playlist.iter().position(|s| &s.uri() == uri));
+            (inner.current_playlist.len(), current)
+        };
+        if len < 2 {
+            return;
+        }
+        let mut order: Vec<usize> = (0..len).collect();
+        // Hashers of `RandomState` are keyed randomly, which is enough here.
+        let state = RandomState::new();
+        for i in (1..len).rev() {
+            let j = (state.hash_one(i) % (i as u64 + 1)) as usize;
+            order.swap(i, j);
+        }
+        if let Some(current) = current {
+            let i = order.iter().position(|&i| i == current).unwrap_or(0);
+            order[..=i].rotate_right(1);
+        }
+        if self.reorder(order.clone()) {
+            info!("shuffled {} songs", len);
+            self.undo_stack.push(PlaylistEdit::Shuffled(order));
+            self.select_index(0);
+            self.show_toast(tr!("已打乱 {} 首歌曲", len));
+        } else {
+            self.show_toast(tr!("打乱歌曲失败"));
+        }
+    }
+
+    // Put the shown songs in the order of their indices.
+    fn reorder(&mut self, order: Vec<usize>) -> bool {
+        if order.len() != self.inner.read().unwrap().current_playlist.len() {
+            return false;
+        }
+        match self.client.send_request(Command::Reorder(order.clone())) {
+            Ok(resp) if resp.ok => {
+                {
+                    let mut inner = self.inner.write().unwrap();
+                    let songs = &inner.current_playlist;
+                    let reordered = order.iter().map(|&i| songs[i].clone()).collect();
+                    inner.current_playlist = reordered;
+                }
+                self.on_playlist_changed();
+                true
+            }
+            _ => {
+                error!("failed to reorder {} songs", order.len());
+                false
+            }
+        }
+    }
+
+    // Move a song in the play queue and keep it selected.
+    fn move_song(&mut self, from: usize, to: usize) -> bool {
+        let len = self.inner.read().unwrap().current_playlist.len();
+        if from >= len || to >= len {
+            return false;
+        }
+        match self.client.send_request(Command::Move(from, to)) {
+            Ok(resp) if resp.ok => {
+                {
+                    let mut inner = self.inner.write().unwrap();
+                    let song = inner.current_playlist.remove(from);
+                    inner.current_playlist.insert(to, song);
+                }
+                self.on_playlist_changed();
+                self.select_index(to);
+                true
+            }
+            _ => {
+                error!("failed to move song from {} to {}", from, to);
+                false
+            }
+        }
+    }
+
+    pub fn undo(&mut self) {
+        match self.undo_stack.pop() {
+            Some(PlaylistEdit::Removed(i, song)) => {
+                let uri = song.uri();
+                let (i, len) = {
+                    let inner = self.inner.read().unwrap();
+                    let len = inner.current_playlist.len();
+                    (i.min(len), len + self.pending_songs.len())
+                };
+                match self.client.send_request(Command::Add(uri.clone())) {
+                    Ok(resp) if resp.ok => {}
+                    _ => {
+                        error!("failed to add {}", uri);
+                        self.undo_stack.push(PlaylistEdit::Removed(i, song));
+                        return;
+                    }
+                }
+                // `add` appends, so move the song back to where it was.
+                match self.client.send_request(Command::Move(len, i)) {
+                    Ok(resp) if resp.ok => {
+                        info!("undo removing {}", uri);
+                        self.inner.write().unwrap().current_playlist.insert(i, song);
+                        self.on_playlist_changed();
+                        self.select_index(i);
+                    }
+                    _ => {
+                        // The song is added to the end, show it there.
+                        error!("failed to move {} from {} to {}", uri, len, i);
+                        self.show_toast(tr!("撤销失败"));
+                        self.sync_current_playlist();
+                    }
+                }
+            }
+            Some(PlaylistEdit::Moved(from, to)) => {
+                let restored = self.move_song(to, from);
+                if !restored {
+                    self.undo_stack.push(PlaylistEdit::Moved(from, to));
+                }
+            }
+            Some(PlaylistEdit::Shuffled(order)) => {
+                let mut restore = vec![0; order.len()];
+                for (i, &from) in order.iter().enumerate() {
+                    restore[from] = i;
+                }
+                if self.reorder(restore) {
+                    info!("undo shuffling");
+                } else {
+                    self.undo_stack.push(PlaylistEdit::Shuffled(order));
+                }
+            }
+            None => {}
+        }
+    }
+
+    pub fn speed_up(&self) {
+        let rate = self.inner.read().unwrap().playback_rate + 0.25;
+        self.set_playback_rate(rate);
+    }
+
+    pub fn slow_down(&self) {
+        let rate = self.inner.read().unwrap().playback_rate - 0.25;
+        self.set_playback_rate(rate);
+    }
+
+    fn set_playback_rate(&self, rate: f64) {
+        let rate = rate.clamp(0.25, 4.0);
+        match self.client.send_request(Command::SetPlaybackRate(rate)) {
+            // The server emits no signal for rate changes, so update it here.
+            Ok(resp) if resp.ok => {
+                info!("set playback rate to {}", rate);
+                let mut inner = self.inner.write().unwrap();
+                inner.playback_rate = rate;
+                inner.progress.set_rate(rate);
+            }
+            _ => error!("failed to set playback rate to {}", rate),
+        }
+    }
+
+    pub fn subscribe_msgs(&self) {
+        let inner = self.inner.clone();
+        let client = self.client.clone();
+        let topics = self.config.topics.clone();
+        let tx = self.events_tx.clone();
+        thread::spawn(move || {
+            let topics = topics.iter().map(|topic| topic.as_str()).collect();
+            client.subscribe_pubsub(topics, &|event| match event {
+                Pubsub::Messages(msgs) => {
+                    let mut inner = inner.write().unwrap();
+                    msgs.into_iter().for_each(|msg| inner.on_message(msg));
+                    let _ = tx.send(AppEvent::Message);
+                }
+                Pubsub::Reconnected => {
+                    let _ = tx.send(AppEvent::Reconnected);
+                }
+            });
+        });
+    }
+}
+
+// Name of the provider shown in pickers.
+fn provider_name(provider: &Provider) -> String {
+    match provider.name.as_str() {
+        "" => provider.identifier.clone(),
+        name => format!("{} ({})", name, provider.identifier),
+    }
 }
@@ -0,0 +1,123 @@
+use crate::glyphs::Glyphs;
+use crate::models::BriefSong;
+use crate::store;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const BOOKMARKS: &str = "bookmarks";
+
+// Highest rating, which is a number of stars.
+pub const MAX_RATING: u8 = 5;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Bookmark {
+    pub song: BriefSong,
+    // 0 if the song is bookmarked without a rating.
+    #[serde(default)]
+    pub rating: u8,
+}
+
+// A rating like `★★★☆☆`, or a hollow star if the song is only bookmarked.
+pub fn stars(rating: u8, glyphs: &Glyphs) -> String {
+    if rating == 0 {
+        return glyphs.hollow_star.to_owned();
+    }
+    let mut stars = glyphs.star.repeat(rating as usize);
+    stars.push_str(&glyphs.hollow_star.repeat((MAX_RATING - rating) as usize));
+    stars
+}
+
+// Songs bookmarked locally, which are kept no matter which providers they
+// are of, in the order they are added.
+pub struct Bookmarks {
+    bookmarks: Vec<Bookmark>,
+    glyphs: &'static Glyphs,
+    // Ratings as they are shown after the titles, by providers and then
+    // identifiers, which are looked up for every song row on the screen.
+    // They are formatted again when the bookmarks change.
+    cells: HashMap<String, HashMap<String, String>>,
+}
+
+impl Bookmarks {
+    pub fn load(glyphs: &'static Glyphs) -> Bookmarks {
+        let mut bookmarks = Bookmarks {
+            bookmarks: store::load(BOOKMARKS),
+            glyphs,
+            cells: HashMap::new(),
+        };
+        bookmarks.format_cells();
+        bookmarks
+    }
+
+    // The rating of the song if it is bookmarked.
+    pub fn cell(&self, song: &BriefSong) -> Option<&str> {
+        self.cells
+            .get(&song.provider)?
+            .get(&song.identifier)
+            .map(String::as_str)
+    }
+
+    fn format_cells(&mut self) {
+        self.cells.clear();
+        for bookmark in &self.bookmarks {
+            self.cells
+                .entry(bookmark.song.provider.clone())
+                .or_default()
+                .insert(
+                    bookmark.song.identifier.clone(),
+                    stars(bookmark.rating, self.glyphs),
+                );
+        }
+    }
+
+    // Keep the change and the cells of it.
+    fn save(&mut self) {
+        store::save(BOOKMARKS, &self.bookmarks);
+        self.format_cells();
+    }
+
+    // Bookmark the song, or remove it if it is bookmarked. Return whether it
+    // is bookmarked now.
+    pub fn toggle(&mut self, song: &BriefSong) -> bool {
+        let uri = song.uri();
+        let len = self.bookmarks.len();
+        self.bookmarks.retain(|bookmark| bookmark.song.uri() != uri);
+        let added = self.bookmarks.len() == len;
+        if added {
+            self.bookmarks.push(Bookmark {
+                song: song.clone(),
+                rating: 0,
+            });
+        }
+        self.save();
+        added
+    }
+
+    // Rate the song, which bookmarks it if it is not yet.
+    pub fn rate(&mut self, song: &BriefSong, rating: u8) {
+        let uri = song.uri();
+        match self
+            .bookmarks
+            .iter_mut()
+            .find(|bookmark| bookmark.song.uri() == uri)
+        {
+            Some(bookmark) => bookmark.rating = rating,
+            None => self.bookmarks.push(Bookmark {
+                song: song.clone(),
+                rating,
+            }),
+        }
+        self.save();
+    }
+
+    // Songs with higher ratings first, and earlier bookmarked ones first
+    // among the same rating.
+    pub fn songs(&self) -> Vec<BriefSong> {
+        let mut bookmarks: Vec<&Bookmark> = self.bookmarks.iter().collect();
+        bookmarks.sort_by_key(|bookmark| std::cmp::Reverse(bookmark.rating));
+        bookmarks
+            .into_iter()
+            .map(|bookmark| bookmark.song.clone())
+            .collect()
+    }
+}
@@ -0,0 +1,187 @@
+use crate::player::parse_position;
+use crate::uri::FuoUri;
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A FeelUOwn client for the terminal.
+///
+/// Options can also be set by `FUST_*` environment variables, which take
+/// precedence over config but not over command line arguments.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+    /// Address of the fuo server, like `127.0.0.1` or `127.0.0.1:23333`.
+    ///
+    /// The pubsub port is assumed to be the rpc port plus one.
+    #[arg(long, env = "FUST_SERVER", value_name = "HOST[:PORT]")]
+    pub server: Option<String>,
+
+    /// Name of the server profile in config, like `home` for `[servers.home]`.
+    ///
+    /// The first profile is used by default.
+    #[arg(long, env = "FUST_PROFILE", value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Path to the config file [default: $XDG_CONFIG_HOME/fust/config.toml]
+    #[arg(short, long, env = "FUST_CONFIG", value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Path to a theme file, which overrides the `[theme]` section of config.
+    #[arg(long, env = "FUST_THEME", value_name = "FILE")]
+    pub theme: Option<PathBuf>,
+
+    /// Only show the now playing bar.
+    #[arg(long, env = "FUST_MINI")]
+    pub mini: bool,
+
+    /// Path to the log file [default: $XDG_DATA_HOME/fust/fust.log]
+    #[arg(long, env = "FUST_LOG_FILE", value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// Log level, one of off, error, warn, info, debug and trace.
+    ///
+    /// Levels of modules can be set like `info,rpc=trace`.
+    #[arg(long, env = "FUST_LOG", value_name = "LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Log more, `-v` for debug and `-vv` for trace.
+    #[arg(short, long, action = ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Log less, `-q` for warn and `-qq` for error.
+    #[arg(short, long, action = ArgAction::Count)]
+    pub quiet: u8,
+
+    /// Send an action, like `next` or `play_pause`, to the running TUI and exit.
+    ///
+    /// Action names are the same as the ones in the `[keys]` section.
+    #[arg(long, value_name = "ACTION")]
+    pub send: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the terminal UI, which is the default.
+    Tui,
+
+    /// Print the playing song, for tmux status lines and shell prompts.
+    Nowplaying {
+        /// Format of the line, which accepts `{state}`, `{title}`,
+        /// `{artists}`, `{album}`, `{position}` and `{duration}`.
+        #[arg(long, default_value = "{title} - {artists}")]
+        format: String,
+
+        /// Keep running and print a new line whenever it changes.
+        #[arg(long)]
+        follow: bool,
+    },
+
+    /// Print waybar json whenever the playing song or state changes.
+    Bar {
+        /// Format of the text, same as `nowplaying --format`.
+        #[arg(long, default_value = "{state} {title} - {artists}")]
+        format: String,
+
+        /// Format of the tooltip.
+        #[arg(long, default_value = "{title}\n{artists}\n{album}")]
+        tooltip: String,
+    },
+
+    /// Print a tab separated line, like `state\tplaying`, for every
+    /// message from the server.
+    Pipe,
+
+    /// Print the player status, exit with 1 if the server is unreachable.
+    Status,
+
+    /// Print the lyric of the playing song.
+    Lyric {
+        /// Keep running and print each lyric sentence as it is sung.
+        #[arg(long)]
+        follow: bool,
+
+        /// Prefix followed sentences with the position, like `[01:30]`.
+        #[arg(long, requires = "follow")]
+        timestamps: bool,
+    },
+
+    /// Print the current playlist.
+    Playlist {
+        #[arg(long, value_enum, default_value_t = PlaylistFormat::Tsv)]
+        format: PlaylistFormat,
+    },
+
+    /// Run scrobbling, MPRIS and other integrations without the TUI.
+    Daemon,
+
+    /// Send raw commands to the server interactively, for debugging.
+    Repl,
+
+    /// Resume playing, or play the song of the uri.
+    Play {
+        /// Like `fuo://netease/songs/1`.
+        uri: Option<FuoUri>,
+    },
+
+    /// Pause playing.
+    Pause,
+
+    /// Toggle between playing and paused.
+    Toggle,
+
+    /// Play the next song.
+    Next,
+
+    /// Play the previous song.
+    #[command(alias = "previous")]
+    Prev,
+
+    /// Seek to a position like `1:30`, or by an offset like `+10` or `-10`.
+    Seek {
+        #[arg(allow_hyphen_values = true, value_parser = parse_seek)]
+        position: Seek,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum PlaylistFormat {
+    /// An array of songs.
+    Json,
+    /// A line of uri, title, artists, album and duration per song.
+    Tsv,
+}
+
+// Seek target of the `seek` subcommand.
+#[derive(Debug, Clone)]
+pub enum Seek {
+    To(Duration),
+    Forward(Duration),
+    Backward(Duration),
+}
+
+pub fn parse_seek(s: &str) -> Result<Seek, String> {
+    let invalid = || format!("invalid position {:?}", s);
+    let (f, position): (fn(Duration) -> Seek, &str) = match s.strip_prefix('+') {
+        Some(rest) => (Seek::Forward, rest),
+        None => match s.strip_prefix('-') {
+            Some(rest) => (Seek::Backward, rest),
+            None => (Seek::To, s),
+        },
+    };
+    parse_position(position).map(f).ok_or_else(invalid)
+}
+
+// Split `host[:port]` into host and port.
+pub fn parse_server(s: &str) -> Result<(String, Option<u16>), String> {
+    match s.rsplit_once(':') {
+        Some((host, port)) => match port.parse() {
+            Ok(port) => Ok((host.to_owned(), Some(port))),
+            Err(_) => Err(format!("invalid port in server address {:?}", s)),
+        },
+        None => Ok((s.to_owned(), None)),
+    }
+}
@@ -0,0 +1,39 @@
+use crate::logger;
+use std::time::SystemTime;
+
+// The current time in the `strftime` format, like `%H:%M` or `%m-%d %a`, in
+// the local time zone.
+#[cfg(unix)]
+pub fn now(format: &str) -> String {
+    let format = match std::ffi::CString::new(format) {
+        Ok(format) => format,
+        Err(_) => return String::new(),
+    };
+    let t = crate::history::unix_now() as libc::time_t;
+    // SAFETY: `localtime_r` only writes to the given `tm`, and `strftime`
+    // writes at most the length of the buffer.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+        return fallback();
+    }
+    let mut buf = [0u8; 128];
+    let len = unsafe {
+        libc::strftime(
+            buf.as_mut_ptr() as *mut libc::c_char,
+            buf.len(),
+            format.as_ptr(),
+            &tm,
+        )
+    };
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+// Formats are not supported without `strftime`, show `HH:MM` in UTC.
+#[cfg(not(unix))]
+pub fn now(_format: &str) -> String {
+    fallback()
+}
+
+fn fallback() -> String {
+    logger::fmt_timestamp(SystemTime::now())[11..16].to_owned()
+}
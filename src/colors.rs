@@ -0,0 +1,123 @@
+use crate::config::Theme;
+use std::env;
+use tui::style::Color;
+
+pub const COLOR_DEPTHS: [&str; 4] = ["auto", "truecolor", "256", "16"];
+
+// Colors the terminal supports, theme colors beyond them are mapped to the
+// closest ones it has.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorDepth {
+    Basic,
+    Indexed,
+    TrueColor,
+}
+
+impl ColorDepth {
+    // One of `COLOR_DEPTHS`, which is detected from the environment if it is
+    // `auto`.
+    pub fn from_name(name: &str) -> ColorDepth {
+        match name {
+            "truecolor" => ColorDepth::TrueColor,
+            "256" => ColorDepth::Indexed,
+            "16" => ColorDepth::Basic,
+            _ => ColorDepth::detect(),
+        }
+    }
+
+    // Terminals announce true colors with `COLORTERM`, and 256 colors with
+    // `TERM` like `xterm-256color`. Others like the linux console are taken
+    // as 16 colors.
+    fn detect() -> ColorDepth {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return ColorDepth::TrueColor;
+        }
+        let term = env::var("TERM").unwrap_or_default();
+        if term.contains("256color") {
+            ColorDepth::Indexed
+        } else {
+            ColorDepth::Basic
+        }
+    }
+}
+
+// The 16 colors with their usual RGB values in xterm.
+const BASIC: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+// Levels of the 6x6x6 color cube of the 256 colors.
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn indexed_rgb(i: u8) -> (u8, u8, u8) {
+    match i {
+        0..=15 => BASIC[i as usize].1,
+        16..=231 => {
+            let i = i - 16;
+            let level = |n: u8| CUBE[n as usize];
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let v = 8 + 10 * (i - 232);
+            (v, v, v)
+        }
+    }
+}
+
+fn nearest_basic(rgb: (u8, u8, u8)) -> Color {
+    BASIC
+        .iter()
+        .min_by_key(|(_, basic)| distance(rgb, *basic))
+        .map_or(Color::Reset, |(color, _)| *color)
+}
+
+fn nearest_indexed(rgb: (u8, u8, u8)) -> Color {
+    // Colors of the cube and the gray ramp, the 16 ones are left out since
+    // terminals often change them.
+    (16..=255u8)
+        .min_by_key(|&i| distance(rgb, indexed_rgb(i)))
+        .map_or(Color::Reset, Color::Indexed)
+}
+
+pub fn degrade(color: Color, depth: ColorDepth) -> Color {
+    match (color, depth) {
+        (Color::Rgb(r, g, b), ColorDepth::Indexed) => nearest_indexed((r, g, b)),
+        (Color::Rgb(r, g, b), ColorDepth::Basic) => nearest_basic((r, g, b)),
+        (Color::Indexed(i), ColorDepth::Basic) => nearest_basic(indexed_rgb(i)),
+        _ => color,
+    }
+}
+
+impl Theme {
+    pub fn degrade(&self, depth: ColorDepth) -> Theme {
+        Theme {
+            accent: degrade(self.accent, depth),
+            playing: degrade(self.playing, depth),
+            paused: degrade(self.paused, depth),
+            dim: degrade(self.dim, depth),
+            message: degrade(self.message, depth),
+            progress: self.progress,
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use crate::player::PlaybackMode;
+use crate::uri::FuoUri;
+use std::fmt;
+use std::time::Duration;
+
+// Commands understood by the fuo rpc server.
+pub enum Command {
+    Status,
+    List,
+    Toggle,
+    Pause,
+    Resume,
+    Next,
+    Previous,
+    Play(FuoUri),
+    Add(FuoUri),
+    // Append the songs in order by a single request.
+    AddAll(Vec<FuoUri>),
+    Remove(FuoUri),
+    Insert(FuoUri),
+    Clear,
+    // Move the song at the first index to the second one.
+    Move(usize, usize),
+    // Put the first songs in the order of their indices, the rest are kept.
+    Reorder(Vec<usize>),
+    SetPlaybackMode(PlaybackMode),
+    SetPlaybackRate(f64),
+    SetVolume(u64),
+    Seek(Duration),
+    // Show the resource of the uri, like `fuo://p/songs/1/lyric`.
+    Show(String),
+    // Search songs in the providers, or all of them if there are none.
+    Search(String, Vec<String>),
+    // Fill the playlist with songs of the provider's personal FM as it plays.
+    StartFm(String),
+    StopFm,
+    // Tell the provider that the current song is disliked, and skip it.
+    Dislike,
+    // Like or unlike the song on its provider for the current user.
+    Like(FuoUri, bool),
+    // Add the song to the playlist of the current user.
+    PlaylistAdd(FuoUri, FuoUri),
+    // Local collections of fuo, which are listed by their names.
+    Collections,
+    CollectionSongs(String),
+    CollectionAdd(String, FuoUri),
+    CollectionRemove(String, FuoUri),
+    // Audio output devices of the mpv player backend, and the current one.
+    AudioDevices,
+    SetAudioDevice(String),
+    // Prefer the quality like `hq`, falling back to the closest one.
+    SetQuality(String),
+    // The media which the playing song is resolved to.
+    CurrentMedia,
+}
+
+// Quote a string with `quote`, which is `"` for an argument of a command
+// and `'` for a string in python code. Backslashes, the quote and line breaks,
+// which end a command, are escaped.
+fn quote(s: &str, quote: char) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push(quote);
+    for c in s.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c if c == quote => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push(quote);
+    quoted
+}
+
+// A string literal in python code.
+fn py_str(s: impl fmt::Display) -> String {
+    quote(&s.to_string(), '\'')
+}
+
+// Run python code on the server, which is the quoted argument of `exec`.
+fn exec(f: &mut fmt::Formatter, code: String) -> fmt::Result {
+    write!(f, "exec {}", quote(&code, '"'))
+}
+
+// Python code which finds the collection by its name.
+fn find_collection(name: &str) -> String {
+    format!(
+        "coll = next(c for c in app.coll_mgr.listall() if c.name == {})",
+        py_str(name)
+    )
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Status => write!(f, "status"),
+            Command::List => write!(f, "list"),
+            Command::Toggle => write!(f, "toggle"),
+            Command::Pause => write!(f, "pause"),
+            Command::Resume => write!(f, "resume"),
+            Command::Next => write!(f, "next"),
+            Command::Previous => write!(f, "previous"),
+            Command::Play(uri) => write!(f, "play {}", uri),
+            Command::Add(uri) => write!(f, "add {}", uri),
+            Command::AddAll(uris) => {
+                write!(f, "add")?;
+                for uri in uris {
+                    write!(f, " {}", uri)?;
+                }
+                Ok(())
+            }
+            Command::Remove(uri) => write!(f, "remove {}", uri),
+            Command::Clear => write!(f, "clear"),
+            // `add` always appends, while `insert` puts the song after the current one.
+            Command::Insert(uri) => exec(
+                f,
+                format!(
+                    "from feeluown.models.uri import resolve; \
+                     app.playlist.insert(resolve({}))",
+                    py_str(uri)
+                ),
+            ),
+            Command::Move(from, to) => exec(
+                f,
+                format!(
+                    "songs = app.playlist._songs; songs.insert({}, songs.pop({}))",
+                    to, from
+                ),
+            ),
+            Command::Reorder(order) => {
+                let order: Vec<String> = order.iter().map(|i| i.to_string()).collect();
+                exec(
+                    f,
+                    format!(
+                        "songs = app.playlist._songs; songs[:{}] = [songs[i] for i in [{}]]",
+                        order.len(),
+                        order.join(", ")
+                    ),
+                )
+            }
+            // fuo has no dedicated command for this, so run it as python code.
+            Command::SetPlaybackMode(mode) => exec(
+                f,
+                format!(
+                    "from feeluown.player import PlaybackMode; \
+                     app.playlist.playback_mode = PlaybackMode.{}",
+                    mode.name()
+                ),
+            ),
+            // Only the mpv player backend supports changing speed.
+            Command::SetPlaybackRate(rate) => exec(f, format!("app.player._mpv.speed = {}", rate)),
+            Command::SetVolume(volume) => exec(f, format!("app.player.volume = {}", volume)),
+            Command::Show(uri) => write!(f, "show {}", uri),
+            Command::Search(keyword, sources) => {
+                write!(f, "search {}", quote(keyword, '"'))?;
+                for source in sources {
+                    write!(f, " --source={}", source)?;
+                }
+                Ok(())
+            }
+            Command::StartFm(provider) => exec(
+                f,
+                format!(
+                    "provider = app.library.get({}); \
+                     app.fm.activate(provider.current_user_list_radio_songs)",
+                    py_str(provider)
+                ),
+            ),
+            Command::StopFm => exec(f, "app.fm.deactivate()".to_owned()),
+            Command::Dislike => exec(
+                f,
+                "song = app.playlist.current_song; \
+                 app.library.get(song.source).current_user_dislike_add_song(song); \
+                 app.playlist.next()"
+                    .to_owned(),
+            ),
+            Command::Like(uri, like) => exec(
+                f,
+                format!(
+                    "from feeluown.models.uri import resolve; song = resolve({}); \
+                     app.library.get(song.source).current_user_{}_song(song)",
+                    py_str(uri),
+                    if *like { "like" } else { "unlike" }
+                ),
+            ),
+            Command::PlaylistAdd(playlist, song) => exec(
+                f,
+                format!(
+                    "from feeluown.models.uri import resolve; playlist = resolve({}); \
+                     app.library.get(playlist.source).playlist_add_song(playlist, resolve({}))",
+                    py_str(playlist),
+                    py_str(song)
+                ),
+            ),
+            Command::Collections => exec(
+                f,
+                "import json; print(json.dumps([c.name for c in app.coll_mgr.listall()]))"
+                    .to_owned(),
+            ),
+            Command::CollectionSongs(name) => exec(
+                f,
+                format!(
+                    "import json; {}; print(json.dumps([{{'provider': m.source, \
+                     'identifier': m.identifier, 'title': m.title, 'album_name': m.album_name, \
+                     'artists_name': m.artists_name, 'duration_ms': m.duration_ms}} \
+                     for m in coll.models if hasattr(m, 'duration_ms')]))",
+                    find_collection(name)
+                ),
+            ),
+            Command::CollectionAdd(name, uri) => exec(
+                f,
+                format!(
+                    "from feeluown.models.uri import resolve; {}; coll.add(resolve({}))",
+                    find_collection(name),
+                    py_str(uri)
+                ),
+            ),
+            Command::CollectionRemove(name, uri) => exec(
+                f,
+                format!(
+                    "from feeluown.models.uri import resolve; {}; coll.remove(resolve({}))",
+                    find_collection(name),
+                    py_str(uri)
+                ),
+            ),
+            Command::AudioDevices => exec(
+                f,
+                "import json; mpv = app.player._mpv; \
+                 print(json.dumps({'current': mpv.audio_device, \
+                 'devices': mpv.audio_device_list}))"
+                    .to_owned(),
+            ),
+            Command::SetAudioDevice(name) => exec(
+                f,
+                format!("app.player._mpv.audio_device = {}", py_str(name)),
+            ),
+            Command::SetQuality(quality) => exec(
+                f,
+                format!(
+                    "app.config.AUDIO_SELECT_POLICY = {}",
+                    py_str(format!("{}<>", quality))
+                ),
+            ),
+            Command::CurrentMedia => exec(
+                f,
+                "import json; m = app.player.current_media; \
+                 print(json.dumps({'url': m.url, 'format': getattr(m, 'format', None), \
+                 'bitrate': getattr(m, 'bitrate', None)}))"
+                    .to_owned(),
+            ),
+            Command::Seek(position) => exec(
+                f,
+                format!("app.player.position = {}", position.as_secs_f64()),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_arguments() {
+        let search = Command::Search("say \"hi\"\n".to_owned(), vec![]);
+        assert_eq!(search.to_string(), r#"search "say \"hi\"\n""#);
+    }
+
+    // Identifiers are quoted as python strings inside the quoted code.
+    #[test]
+    fn quote_uris_in_code() {
+        let uri = FuoUri::new("local", "songs", "it's\\a");
+        assert_eq!(
+            Command::Insert(uri.clone()).to_string(),
+            r#"exec "from feeluown.models.uri import resolve; app.playlist.insert(resolve('fuo://local/songs/it\\'s\\\\a'))""#
+        );
+        let like = Command::Like(uri.clone(), true).to_string();
+        assert!(like.contains(r#"song = resolve('fuo://local/songs/it\\'s\\\\a')"#));
+        let playlist = FuoUri::new("local", "playlists", "mine");
+        let add = Command::PlaylistAdd(playlist, uri).to_string();
+        assert!(add.contains(r#"playlist = resolve('fuo://local/playlists/mine')"#));
+        assert!(add.contains(r#"resolve('fuo://local/songs/it\\'s\\\\a'))"#));
+    }
+}
@@ -0,0 +1,641 @@
+use crate::colors::{ColorDepth, COLOR_DEPTHS};
+use crate::glyphs::{self, Glyphs, SYMBOLS};
+use crate::i18n::LOCALES;
+use crate::keymap::KeySpecs;
+use crate::logger::LogFilter;
+use crate::paths;
+use crate::smart::SmartPlaylist;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use tui::layout::Alignment;
+use tui::style::Color;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "invalid config {}: {}", path.display(), e),
+            ConfigError::Invalid(msg) => write!(f, "invalid config: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    #[serde(alias = "port")]
+    pub rpc_port: u16,
+    // Defaults to the rpc port plus one.
+    pub pubsub_port: Option<u16>,
+    pub auth: Option<String>,
+    pub format: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            host: "127.0.0.1".to_owned(),
+            rpc_port: 23333,
+            pubsub_port: None,
+            auth: None,
+            format: "json".to_owned(),
+        }
+    }
+}
+
+impl ServerConfig {
+    // None if it is not given and the rpc port is the last one.
+    pub fn pubsub_port(&self) -> Option<u16> {
+        self.pubsub_port.or_else(|| self.rpc_port.checked_add(1))
+    }
+
+    pub fn validate(&self, name: &str) -> Result<(), ConfigError> {
+        if self.format != "json" {
+            return Err(ConfigError::Invalid(format!(
+                "unsupported format {:?} of {:?}, only \"json\" is supported",
+                self.format, name
+            )));
+        }
+        if self.pubsub_port().is_none() {
+            return Err(ConfigError::Invalid(format!(
+                "pubsub_port of {:?} is required since rpc_port is {}",
+                name, self.rpc_port
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Server profiles in the order they are written in config.
+#[derive(Debug, Clone, Default)]
+pub struct ServerProfiles(pub Vec<(String, ServerConfig)>);
+
+impl<'de> Deserialize<'de> for ServerProfiles {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ProfilesVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ProfilesVisitor {
+            type Value = ServerProfiles;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a table of server profiles")
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut profiles = vec![];
+                while let Some((name, server)) = map.next_entry()? {
+                    profiles.push((name, server));
+                }
+                Ok(ServerProfiles(profiles))
+            }
+        }
+
+        deserializer.deserialize_map(ProfilesVisitor)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    // Highlights, such as the song being played.
+    #[serde(deserialize_with = "de_color")]
+    pub accent: Color,
+    #[serde(deserialize_with = "de_color")]
+    pub playing: Color,
+    #[serde(deserialize_with = "de_color")]
+    pub paused: Color,
+    // Less important texts, such as separators and indicators.
+    #[serde(deserialize_with = "de_color")]
+    pub dim: Color,
+    // Toasts.
+    #[serde(deserialize_with = "de_color")]
+    pub message: Color,
+    #[serde(deserialize_with = "de_progress_style")]
+    pub progress: ProgressStyle,
+}
+
+// How the progress bar is drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressStyle {
+    // A thin line.
+    Line,
+    // Blocks filled with the color, with the label in the middle.
+    Block,
+    // Braille dots, which fill half cells.
+    Braille,
+    // Plain text like `[====>    ]`.
+    Text,
+}
+
+impl Theme {
+    pub fn load_from(path: PathBuf) -> Result<Theme, ConfigError> {
+        let s = fs::read_to_string(&path).map_err(|e| ConfigError::Io(path.clone(), e))?;
+        toml::from_str(&s).map_err(|e| ConfigError::Parse(path, e))
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            accent: Color::Yellow,
+            playing: Color::LightCyan,
+            paused: Color::Gray,
+            dim: Color::Gray,
+            message: Color::LightGreen,
+            progress: ProgressStyle::Line,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    // Only show the now playing bar.
+    pub mini: bool,
+    pub margin: u16,
+    #[serde(deserialize_with = "de_alignment")]
+    pub lyric_alignment: Alignment,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> LayoutConfig {
+        LayoutConfig {
+            mini: false,
+            margin: 1,
+            lyric_alignment: Alignment::Right,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct MprisConfig {
+    pub enabled: bool,
+    // Grab media keys from gnome-settings-daemon.
+    pub media_keys: bool,
+}
+
+// Show the playing song in the terminal title, the format accepts
+// `{state}`, `{title}`, `{artists}`, `{album}`, `{position}` and `{duration}`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TitleConfig {
+    pub enabled: bool,
+    pub format: String,
+}
+
+impl Default for TitleConfig {
+    fn default() -> TitleConfig {
+        TitleConfig {
+            enabled: false,
+            format: "{state} {title} – {artists}".to_owned(),
+        }
+    }
+}
+
+// Send a notification by terminal escape sequences when the song changes,
+// `protocol` is either `osc777` or `osc9`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationConfig {
+    pub enabled: bool,
+    pub protocol: String,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> NotificationConfig {
+        NotificationConfig {
+            enabled: false,
+            protocol: "osc777".to_owned(),
+        }
+    }
+}
+
+// Publish the current song as presence, `client_id` is the id of a
+// Discord application which is shown as "Listening to <name>".
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscordConfig {
+    pub enabled: bool,
+    pub client_id: String,
+}
+
+// Rotation of the log file.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub max_size_kb: u64,
+    // Number of rotated files to keep, like `fust.log.1`.
+    pub max_files: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> LogConfig {
+        LogConfig {
+            max_size_kb: 5 * 1024,
+            max_files: 3,
+        }
+    }
+}
+
+// Caches in `$XDG_CACHE_HOME/fust`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    // Total size of cached lyrics, the least recently used ones are removed
+    // beyond it.
+    pub lyrics_max_kb: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> CacheConfig {
+        CacheConfig {
+            lyrics_max_kb: 2 * 1024,
+        }
+    }
+}
+
+// Get a session key with the desktop auth flow of the Last.fm API.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LastfmConfig {
+    pub api_key: String,
+    pub api_secret: String,
+    pub session_key: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ListenBrainzConfig {
+    pub token: String,
+}
+
+// The program which plays MVs, the url of the video is appended to the
+// command.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct VideoConfig {
+    pub command: Vec<String>,
+}
+
+impl Default for VideoConfig {
+    fn default() -> VideoConfig {
+        VideoConfig {
+            command: vec!["mpv".to_owned(), "--force-window".to_owned()],
+        }
+    }
+}
+
+// Scrobbling is enabled for services which are configured.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScrobblerConfig {
+    pub lastfm: Option<LastfmConfig>,
+    pub listenbrainz: Option<ListenBrainzConfig>,
+}
+
+// Audio qualities of fuo, from high to low.
+pub const QUALITIES: [&str; 3] = ["hq", "sq", "lq"];
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub servers: ServerProfiles,
+    pub theme: Theme,
+    pub keymap: String,
+    // Action names to key specs, overriding the keymap.
+    pub keys: HashMap<String, KeySpecs>,
+    pub tick_rate_ms: u64,
+    pub topics: Vec<String>,
+    pub layout: LayoutConfig,
+    pub log_level: String,
+    pub log: LogConfig,
+    pub cache: CacheConfig,
+    pub mpris: MprisConfig,
+    pub scrobbler: ScrobblerConfig,
+    pub discord: DiscordConfig,
+    pub title: TitleConfig,
+    pub notification: NotificationConfig,
+    pub video: VideoConfig,
+    // Media quality which the server prefers, one of `QUALITIES`.
+    pub quality: Option<String>,
+    // Language of the UI, one of `LOCALES`, or detected from the environment.
+    pub locale: Option<String>,
+    // One of `SYMBOLS`.
+    pub symbols: String,
+    // Colors which the terminal supports, one of `COLOR_DEPTHS`.
+    pub colors: String,
+    // Work with screen readers, see `App::announce`.
+    pub accessible: bool,
+    // `strftime` format of the clock in the status row, hidden if `None`.
+    pub clock: Option<String>,
+    pub smart_playlists: Vec<SmartPlaylist>,
+    #[serde(skip)]
+    pub theme_source: Option<ThemeSource>,
+}
+
+// The file which the theme is loaded from.
+#[derive(Debug, Clone)]
+pub enum ThemeSource {
+    ThemeFile(PathBuf),
+    ConfigFile(PathBuf),
+}
+
+impl ThemeSource {
+    pub fn path(&self) -> &Path {
+        match self {
+            ThemeSource::ThemeFile(path) | ThemeSource::ConfigFile(path) => path,
+        }
+    }
+
+    pub fn load(&self) -> Result<Theme, ConfigError> {
+        match self {
+            ThemeSource::ThemeFile(path) => Theme::load_from(path.clone()),
+            ThemeSource::ConfigFile(path) => Ok(Config::load_from(path.clone())?.theme),
+        }
+    }
+
+    // Reload the theme whenever the file changes and send the result.
+    //
+    // The parent directory is watched since editors usually replace the
+    // file instead of writing to it. The watcher stops when it is dropped.
+    pub fn watch(
+        &self,
+        tx: Sender<Result<Theme, ConfigError>>,
+    ) -> notify::Result<RecommendedWatcher> {
+        let source = self.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+                if let Ok(event) = res {
+                    let changed = (event.kind.is_create() || event.kind.is_modify())
+                        && event
+                            .paths
+                            .iter()
+                            .any(|p| p.file_name() == source.path().file_name());
+                    if changed {
+                        let _ = tx.send(source.load());
+                    }
+                }
+            })?;
+        let dir = match self.path().parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(watcher)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            server: ServerConfig::default(),
+            servers: ServerProfiles::default(),
+            theme: Theme::default(),
+            keymap: "default".to_owned(),
+            keys: HashMap::new(),
+            tick_rate_ms: 250,
+            topics: vec![
+                "player.*".to_owned(),
+                "playlist.*".to_owned(),
+                "live_lyric.*".to_owned(),
+            ],
+            layout: LayoutConfig::default(),
+            log_level: "info".to_owned(),
+            log: LogConfig::default(),
+            cache: CacheConfig::default(),
+            mpris: MprisConfig::default(),
+            scrobbler: ScrobblerConfig::default(),
+            discord: DiscordConfig::default(),
+            title: TitleConfig::default(),
+            notification: NotificationConfig::default(),
+            video: VideoConfig::default(),
+            quality: None,
+            locale: None,
+            symbols: "unicode".to_owned(),
+            colors: "auto".to_owned(),
+            accessible: false,
+            clock: None,
+            smart_playlists: vec![],
+            theme_source: None,
+        }
+    }
+}
+
+impl Config {
+    // Load config from the default path, a missing file means all defaults.
+    pub fn load() -> Result<Config, ConfigError> {
+        let path = paths::config_file();
+        if path.exists() {
+            Config::load_from(path)
+        } else {
+            Ok(Config::default())
+        }
+    }
+
+    pub fn load_from(path: PathBuf) -> Result<Config, ConfigError> {
+        let s = fs::read_to_string(&path).map_err(|e| ConfigError::Io(path.clone(), e))?;
+        let mut config: Config =
+            toml::from_str(&s).map_err(|e| ConfigError::Parse(path.clone(), e))?;
+        config.validate()?;
+        config.theme_source = Some(ThemeSource::ConfigFile(path));
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.tick_rate_ms == 0 {
+            return Err(ConfigError::Invalid(
+                "tick_rate_ms should be greater than 0".to_owned(),
+            ));
+        }
+        for (name, server) in self
+            .servers
+            .0
+            .iter()
+            .map(|(name, server)| (name.as_str(), server))
+            .chain([("server", &self.server)])
+        {
+            server.validate(name)?;
+        }
+        self.log_filter()?;
+        if self.topics.is_empty() {
+            return Err(ConfigError::Invalid(
+                "topics should not be empty".to_owned(),
+            ));
+        }
+        if !["osc777", "osc9"].contains(&self.notification.protocol.as_str()) {
+            return Err(ConfigError::Invalid(format!(
+                "unknown notification protocol {:?}, expected \"osc777\" or \"osc9\"",
+                self.notification.protocol
+            )));
+        }
+        if let Some(quality) = self.quality.as_deref().filter(|q| !QUALITIES.contains(q)) {
+            return Err(ConfigError::Invalid(format!(
+                "unknown quality {:?}, expected one of {:?}",
+                quality, QUALITIES
+            )));
+        }
+        if let Some(locale) = self.locale.as_deref().filter(|l| !LOCALES.contains(l)) {
+            return Err(ConfigError::Invalid(format!(
+                "unknown locale {:?}, expected one of {:?}",
+                locale, LOCALES
+            )));
+        }
+        if !SYMBOLS.contains(&self.symbols.as_str()) {
+            return Err(ConfigError::Invalid(format!(
+                "unknown symbols {:?}, expected one of {:?}",
+                self.symbols, SYMBOLS
+            )));
+        }
+        if !COLOR_DEPTHS.contains(&self.colors.as_str()) {
+            return Err(ConfigError::Invalid(format!(
+                "unknown colors {:?}, expected one of {:?}",
+                self.colors, COLOR_DEPTHS
+            )));
+        }
+        if self.video.command.is_empty() {
+            return Err(ConfigError::Invalid(
+                "video.command should not be empty".to_owned(),
+            ));
+        }
+        if self.discord.enabled && self.discord.client_id.is_empty() {
+            return Err(ConfigError::Invalid(
+                "discord.client_id is required when discord is enabled".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    // Use the named server profile, or the first one if no name is given.
+    // The `[server]` section is used when there is no profile.
+    pub fn select_profile(&mut self, name: Option<&str>) -> Result<(), ConfigError> {
+        let profiles = &self.servers.0;
+        let server = match name {
+            Some(name) => match profiles.iter().find(|(n, _)| n == name) {
+                Some((_, server)) => server,
+                None => {
+                    let names: Vec<&str> = profiles.iter().map(|(n, _)| n.as_str()).collect();
+                    return Err(ConfigError::Invalid(format!(
+                        "unknown server profile {:?}, expected one of {:?}",
+                        name, names
+                    )));
+                }
+            },
+            None => match profiles.first() {
+                Some((_, server)) => server,
+                None => return Ok(()),
+            },
+        };
+        self.server = server.clone();
+        Ok(())
+    }
+
+    pub fn color_depth(&self) -> ColorDepth {
+        ColorDepth::from_name(&self.colors)
+    }
+
+    pub fn glyphs(&self) -> &'static Glyphs {
+        glyphs::glyphs(&self.symbols)
+    }
+
+    pub fn log_filter(&self) -> Result<LogFilter, ConfigError> {
+        LogFilter::parse(&self.log_level).ok_or_else(|| {
+            ConfigError::Invalid(format!(
+                "invalid log level {:?}, expected levels like \"info\" or \"info,rpc=debug\", \
+                 which are one of off, error, warn, info, debug and trace",
+                self.log_level
+            ))
+        })
+    }
+
+    pub fn tick_rate(&self) -> Duration {
+        Duration::from_millis(self.tick_rate_ms)
+    }
+}
+
+// Parse colors like `yellow`, `#ffcc00` or `214`.
+pub fn parse_color(s: &str) -> Option<Color> {
+    let color = match s.to_lowercase().replace(['_', '-', ' '], "").as_str() {
+        "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        s => match s.strip_prefix('#') {
+            Some(hex) if hex.len() == 6 => {
+                let v = u32::from_str_radix(hex, 16).ok()?;
+                Color::Rgb((v >> 16) as u8, (v >> 8) as u8, v as u8)
+            }
+            Some(_) => return None,
+            None => Color::Indexed(s.parse().ok()?),
+        },
+    };
+    Some(color)
+}
+
+fn de_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_color(&s).ok_or_else(|| serde::de::Error::custom(format!("invalid color {:?}", s)))
+}
+
+fn de_progress_style<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ProgressStyle, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    match s.as_str() {
+        "line" => Ok(ProgressStyle::Line),
+        "block" => Ok(ProgressStyle::Block),
+        "braille" => Ok(ProgressStyle::Braille),
+        "text" => Ok(ProgressStyle::Text),
+        _ => Err(serde::de::Error::custom(format!(
+            "invalid progress {:?}, expected line, block, braille or text",
+            s
+        ))),
+    }
+}
+
+fn de_alignment<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Alignment, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    match s.as_str() {
+        "left" => Ok(Alignment::Left),
+        "center" => Ok(Alignment::Center),
+        "right" => Ok(Alignment::Right),
+        _ => Err(serde::de::Error::custom(format!(
+            "invalid alignment {:?}, expected left, center or right",
+            s
+        ))),
+    }
+}
@@ -0,0 +1,167 @@
+use crate::app::AppInner;
+use crate::player::PlayerState;
+use log::{error, info};
+use std::env;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+
+// Discord is usually not running, so do not try to connect too often.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(15);
+
+// Discord listens on `discord-ipc-{0..9}` in the runtime or temp directory.
+fn socket_paths() -> Vec<PathBuf> {
+    let dir = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .find_map(env::var_os)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/tmp"));
+    (0..10)
+        .map(|i| dir.join(format!("discord-ipc-{}", i)))
+        .collect()
+}
+
+// Client of the local Discord IPC, frames are `op`, `len` and a json body.
+struct Ipc {
+    stream: UnixStream,
+    nonce: u64,
+}
+
+impl Ipc {
+    fn connect(client_id: &str) -> Result<Ipc, Error> {
+        let stream = socket_paths()
+            .iter()
+            .find_map(|path| UnixStream::connect(path).ok())
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "discord is not running"))?;
+        let mut ipc = Ipc { stream, nonce: 0 };
+        ipc.send(
+            OP_HANDSHAKE,
+            serde_json::json!({ "v": 1, "client_id": client_id }),
+        )?;
+        ipc.recv()?;
+        Ok(ipc)
+    }
+
+    fn send(&mut self, op: u32, body: serde_json::Value) -> Result<(), Error> {
+        let body = body.to_string();
+        let mut frame = Vec::with_capacity(8 + body.len());
+        frame.extend_from_slice(&op.to_le_bytes());
+        frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        frame.extend_from_slice(body.as_bytes());
+        self.stream.write_all(&frame)
+    }
+
+    fn recv(&mut self) -> Result<serde_json::Value, Error> {
+        let mut header = [0; 8];
+        self.stream.read_exact(&mut header)?;
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let mut body = vec![0; len as usize];
+        self.stream.read_exact(&mut body)?;
+        serde_json::from_slice(&body).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    // Set the activity, `null` clears it.
+    fn set_activity(&mut self, activity: serde_json::Value) -> Result<(), Error> {
+        self.nonce += 1;
+        self.send(
+            OP_FRAME,
+            serde_json::json!({
+                "cmd": "SET_ACTIVITY",
+                "args": { "pid": std::process::id(), "activity": activity },
+                "nonce": self.nonce.to_string(),
+            }),
+        )?;
+        self.recv()?;
+        Ok(())
+    }
+}
+
+// What is shown in the presence, used to find out whether it changed.
+#[derive(PartialEq, Clone)]
+struct Presence {
+    title: String,
+    artists: String,
+    state: PlayerState,
+    // Unix timestamps of when the song started and will end.
+    start: u64,
+    end: u64,
+}
+
+impl Presence {
+    fn new(inner: &AppInner) -> Presence {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let start = now.saturating_sub(inner.progress.current().as_secs());
+        Presence {
+            title: inner.metadata.title.clone(),
+            artists: inner.metadata.artists.join(", "),
+            state: inner.state,
+            start,
+            end: start + inner.duration.as_secs(),
+        }
+    }
+
+    // Timestamps drift a little between ticks, only seeks matter. They are
+    // not shown unless playing, and the start moves every second meanwhile.
+    fn same_as(&self, other: &Presence) -> bool {
+        self.title == other.title
+            && self.artists == other.artists
+            && self.state == other.state
+            && (self.state != PlayerState::Playing
+                || (self.start.abs_diff(other.start) <= 2 && self.end.abs_diff(other.end) <= 2))
+    }
+
+    fn activity(&self) -> serde_json::Value {
+        if self.title.is_empty() || self.state == PlayerState::Stopped {
+            return serde_json::Value::Null;
+        }
+        let mut activity = serde_json::json!({
+            "details": self.title,
+            "state": self.artists,
+        });
+        // Discord counts elapsed and remaining time from the timestamps,
+        // which are only meaningful while playing.
+        if self.state == PlayerState::Playing {
+            activity["timestamps"] = serde_json::json!({ "start": self.start, "end": self.end });
+        } else {
+            activity["details"] = serde_json::Value::from(format!("⏸ {}", self.title));
+        }
+        activity
+    }
+}
+
+// Publish the current song as Discord presence in a background thread.
+pub fn start(client_id: String, inner: Arc<RwLock<AppInner>>) {
+    thread::spawn(move || loop {
+        match Ipc::connect(&client_id) {
+            Ok(ipc) => {
+                info!("discord: connected");
+                if let Err(e) = run(ipc, &inner) {
+                    error!("discord: {}", e);
+                }
+            }
+            Err(e) => info!("discord: {}", e),
+        }
+        thread::sleep(RECONNECT_INTERVAL);
+    });
+}
+
+fn run(mut ipc: Ipc, inner: &Arc<RwLock<AppInner>>) -> Result<(), Error> {
+    let mut last: Option<Presence> = None;
+    loop {
+        let current = Presence::new(&inner.read().unwrap());
+        if !last.as_ref().is_some_and(|last| last.same_as(&current)) {
+            ipc.set_activity(current.activity())?;
+            last = Some(current);
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
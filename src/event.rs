@@ -0,0 +1,97 @@
+use crate::config::{ConfigError, Theme};
+use crate::keymap::Action;
+use crate::models::{AudioDevices, BriefPlaylist, BriefSong, Comment, LoginStatus, Media};
+use crate::terminal;
+use crate::uri::FuoUri;
+use crossterm::event::{self, Event};
+use log::error;
+use std::io;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+// Things which wake up the main loop, which otherwise sleeps until a timer
+// fires.
+pub enum AppEvent {
+    // Keys and mouse events of the terminal.
+    Input(Event),
+    // The terminal is resized to the columns and rows.
+    Resize(u16, u16),
+    // The state is changed by a pubsub message.
+    Message,
+    // The pubsub connection is set up again after it was lost.
+    Reconnected,
+    // An action sent by `fust --send`.
+    Remote(Action),
+    Theme(Result<Theme, ConfigError>),
+    // The current playlist fetched in the background.
+    Playlist(io::Result<Vec<BriefSong>>),
+    // Songs fetched in the background to be shown in a page with the title.
+    Songs(String, io::Result<Vec<BriefSong>>),
+    Playlists(String, io::Result<Vec<BriefPlaylist>>),
+    // Songs of the collection with the name.
+    Collection(String, io::Result<Vec<BriefSong>>),
+    // Whether the song is liked by the current user.
+    Liked(FuoUri, io::Result<bool>),
+    // The video player exits, and the song should be resumed if it was
+    // paused for the video.
+    VideoExited(bool),
+    // Songs of the playlist done being added, out of all.
+    EnqueueProgress(String, usize, usize),
+    // Songs of the playlist which are added, and whether they replace the
+    // playlist.
+    Enqueued(String, bool, io::Result<Vec<BriefSong>>),
+    // The media which the song is resolved to.
+    Media(FuoUri, io::Result<Media>),
+    // Audio output devices of the player.
+    AudioDevices(io::Result<AudioDevices>),
+    // Hot comments of the song.
+    Comments(FuoUri, io::Result<Vec<Comment>>),
+    // The login status of the provider, polled while its QR code is shown.
+    Login(String, io::Result<LoginStatus>),
+}
+
+// How often to check whether the terminal is given back to the TUI.
+const INPUT_POLL: Duration = Duration::from_millis(100);
+
+// Read terminal events in a background thread. Nothing is read while the
+// terminal is given away, so that programs run meanwhile get their input.
+pub fn spawn_input(tx: Sender<AppEvent>) {
+    thread::spawn(move || loop {
+        if !terminal::is_active() {
+            thread::sleep(INPUT_POLL);
+            continue;
+        }
+        let event = match event::poll(INPUT_POLL) {
+            Ok(true) => event::read(),
+            Ok(false) => continue,
+            Err(e) => Err(e),
+        };
+        match event {
+            Ok(event) => {
+                let event = match event {
+                    Event::Resize(cols, rows) => AppEvent::Resize(cols, rows),
+                    event => AppEvent::Input(event),
+                };
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+            Err(e) => {
+                error!("failed to read terminal events: {}", e);
+                return;
+            }
+        }
+    });
+}
+
+// Forward everything received from `rx` as events, until either side is gone.
+pub fn forward<T: Send + 'static>(rx: Receiver<T>, tx: Sender<AppEvent>, f: fn(T) -> AppEvent) {
+    thread::spawn(move || {
+        for value in rx {
+            if tx.send(f(value)).is_err() {
+                return;
+            }
+        }
+    });
+}
@@ -0,0 +1,107 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::symbols::line;
+use tui::widgets::Widget;
+
+pub const SYMBOLS: [&str; 2] = ["unicode", "ascii"];
+
+// Decorative characters of the UI, which can be plain ASCII for fonts and
+// terminals which render them as tofu.
+pub struct Glyphs {
+    pub ascii: bool,
+    pub note: &'static str,
+    pub unsynced: &'static str,
+    pub heart: &'static str,
+    pub check: &'static str,
+    pub star: &'static str,
+    pub hollow_star: &'static str,
+    pub bar: &'static str,
+    pub dot: &'static str,
+    pub sleep: &'static str,
+    pub ellipsis: &'static str,
+    pub request: &'static str,
+    pub response: &'static str,
+    pub message: &'static str,
+    pub spinner: &'static [&'static str],
+    pub line_set: line::Set,
+}
+
+pub const UNICODE: Glyphs = Glyphs {
+    ascii: false,
+    note: "♫",
+    unsynced: "♪",
+    heart: "♥",
+    check: "✓",
+    star: "★",
+    hollow_star: "☆",
+    bar: "█",
+    dot: tui::symbols::DOT,
+    sleep: "⏾",
+    ellipsis: "…",
+    request: "→",
+    response: "←",
+    message: "•",
+    spinner: &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
+    line_set: line::THICK,
+};
+
+pub const ASCII: Glyphs = Glyphs {
+    ascii: true,
+    note: "#",
+    unsynced: "~",
+    heart: "<3",
+    check: "v",
+    star: "*",
+    hollow_star: ".",
+    bar: "=",
+    dot: "-",
+    sleep: "Zz",
+    ellipsis: "...",
+    request: ">",
+    response: "<",
+    message: "*",
+    spinner: &["|", "/", "-", "\\"],
+    line_set: line::Set {
+        vertical: "|",
+        horizontal: "=",
+        top_right: "+",
+        top_left: "+",
+        bottom_right: "+",
+        bottom_left: "+",
+        vertical_left: "+",
+        vertical_right: "+",
+        horizontal_down: "+",
+        horizontal_up: "+",
+        cross: "+",
+    },
+};
+
+pub fn glyphs(symbols: &str) -> &'static Glyphs {
+    match symbols {
+        "ascii" => &ASCII,
+        _ => &UNICODE,
+    }
+}
+
+// Replace box drawing characters of borders drawn by tui, which cannot be
+// configured, with ASCII ones. It is rendered over the whole frame last.
+pub struct AsciiBorders;
+
+impl Widget for AsciiBorders {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                let cell = buf.get_mut(x, y);
+                let ascii = match cell.symbol.as_str() {
+                    "─" | "━" | "═" => "-",
+                    "│" | "┃" | "║" => "|",
+                    "┌" | "┐" | "└" | "┘" | "╭" | "╮" | "╰" | "╯" | "┼" | "├" | "┤" | "┬" | "┴" => {
+                        "+"
+                    }
+                    _ => continue,
+                };
+                cell.set_symbol(ascii);
+            }
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use crate::app::AppInner;
+use crate::cli::{PlaylistFormat, Seek};
+use crate::commands::Command;
+use crate::config::Config;
+use crate::lyric;
+use crate::models::{BriefSong, Status};
+use crate::nowplaying::NowPlaying;
+use crate::player::fmt_duration;
+use crate::rpc::{Client, Pubsub};
+use log::error;
+use std::error::Error;
+use std::io::{self, Write};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+// Modes which print to stdout instead of drawing the TUI.
+
+fn fetch_player_status(client: &Client) -> Result<Status, Box<dyn Error>> {
+    let resp = client.send_request(Command::Status)?;
+    Ok(serde_json::from_slice(&resp.body)?)
+}
+
+// Fetch the player status once.
+fn fetch_status(client: &Client) -> Result<AppInner, Box<dyn Error>> {
+    let mut inner = AppInner::new();
+    inner.on_status(&fetch_player_status(client)?);
+    Ok(inner)
+}
+
+// Keep the state in sync with the server by subscribing to its messages.
+fn follow_status(
+    config: &Config,
+    client: &Client,
+) -> Result<Arc<RwLock<AppInner>>, Box<dyn Error>> {
+    let inner = Arc::new(RwLock::new(fetch_status(client)?));
+    let topics = config.topics.clone();
+    let client = client.clone();
+    let inner_clone = inner.clone();
+    thread::spawn(move || {
+        let topics = topics.iter().map(|topic| topic.as_str()).collect();
+        client.subscribe_pubsub(topics, &|event| match event {
+            Pubsub::Messages(msgs) => {
+                let mut inner = inner_clone.write().unwrap();
+                msgs.into_iter().for_each(|msg| inner.on_message(msg));
+            }
+            Pubsub::Reconnected => match fetch_status(&client) {
+                Ok(status) => *inner_clone.write().unwrap() = status,
+                Err(e) => error!("failed to sync player status: {}", e),
+            },
+        });
+    });
+    Ok(inner)
+}
+
+// Print a line rendered from the state whenever it changes, forever.
+fn print_changes(
+    inner: &Arc<RwLock<AppInner>>,
+    render: impl Fn(&AppInner) -> String,
+) -> Result<(), Box<dyn Error>> {
+    let mut stdout = io::stdout();
+    let mut last = String::new();
+    loop {
+        let line = render(&inner.read().unwrap());
+        if line != last {
+            writeln!(stdout, "{}", line)?;
+            stdout.flush()?;
+            last = line;
+        }
+        thread::sleep(Duration::from_millis(200));
+    }
+}
+
+// Print the playing song, and print it again whenever it changes if
+// `follow` is set.
+pub fn nowplaying(config: &Config, format: &str, follow: bool) -> Result<(), Box<dyn Error>> {
+    let client = Client::new(&config.server);
+    if !follow {
+        println!(
+            "{}",
+            NowPlaying::new(&fetch_status(&client)?).render(format)
+        );
+        return Ok(());
+    }
+    let inner = follow_status(config, &client)?;
+    print_changes(&inner, |inner| NowPlaying::new(inner).render(format))
+}
+
+// Print the full lyric, or each sentence as it is sung if `follow` is set.
+pub fn lyric(config: &Config, follow: bool, timestamps: bool) -> Result<(), Box<dyn Error>> {
+    let client = Client::new(&config.server);
+    let inner = fetch_status(&client)?;
+    if !follow {
+        let uri = inner.current_song_uri.ok_or("no song is playing")?;
+        let cache = lyric::Cache::new(config.cache.lyrics_max_kb * 1024);
+        print!("{}", lyric::fetch_cached(&client, &cache, &uri)?.content);
+        return Ok(());
+    }
+    let inner = RwLock::new(inner);
+    let topics = config.topics.iter().map(|topic| topic.as_str()).collect();
+    client.subscribe_topics(topics, &|msg| {
+        let is_lyric = msg.topic == "live_lyric.sentence_changed";
+        let line = {
+            let mut inner = inner.write().unwrap();
+            inner.on_message(msg);
+            match (is_lyric, timestamps) {
+                (false, _) => return,
+                (true, false) => inner.lyric.text().to_owned(),
+                (true, true) => format!(
+                    "[{}] {}",
+                    fmt_duration(inner.progress.current()),
+                    inner.lyric.text()
+                ),
+            }
+        };
+        let mut stdout = io::stdout();
+        if writeln!(stdout, "{}", line)
+            .and_then(|_| stdout.flush())
+            .is_err()
+        {
+            std::process::exit(0);
+        }
+    });
+    Ok(())
+}
+
+pub fn playlist(config: &Config, format: PlaylistFormat) -> Result<(), Box<dyn Error>> {
+    let client = Client::new(&config.server);
+    let resp = client.send_request(Command::List)?;
+    let songs: Vec<BriefSong> = serde_json::from_slice(&resp.body)?;
+    let mut stdout = io::stdout().lock();
+    match format {
+        PlaylistFormat::Json => writeln!(stdout, "{}", serde_json::to_string_pretty(&songs)?)?,
+        PlaylistFormat::Tsv => {
+            for song in songs.iter() {
+                let fields = [
+                    song.uri().to_string(),
+                    song.title.clone(),
+                    song.artists_name.clone(),
+                    song.album_name.clone(),
+                    song.duration_ms.clone(),
+                ];
+                // Tabs and newlines in names would break the columns.
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|field| field.replace(['\t', '\r', '\n'], " "))
+                    .collect();
+                writeln!(stdout, "{}", fields.join("\t"))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Send a command and fail if the server does not accept it.
+fn send(client: &Client, cmd: Command) -> Result<(), Box<dyn Error>> {
+    let resp = client.send_request(cmd)?;
+    if !resp.ok {
+        return Err(String::from_utf8_lossy(&resp.body).trim().to_owned().into());
+    }
+    Ok(())
+}
+
+// Send a single playback control command, like `fust next`.
+pub fn control(config: &Config, cmd: Command) -> Result<(), Box<dyn Error>> {
+    send(&Client::new(&config.server), cmd)
+}
+
+pub fn seek(config: &Config, seek: Seek) -> Result<(), Box<dyn Error>> {
+    let client = Client::new(&config.server);
+    let inner = fetch_status(&client)?;
+    let position = match seek {
+        Seek::To(position) => position,
+        Seek::Forward(offset) => inner.progress.current().saturating_add(offset),
+        Seek::Backward(offset) => inner.progress.current().saturating_sub(offset),
+    };
+    send(&client, Command::Seek(position.min(inner.duration)))
+}
+
+pub fn status(config: &Config) -> Result<(), Box<dyn Error>> {
+    let client = Client::new(&config.server);
+    let status = fetch_player_status(&client)?;
+    let mut inner = AppInner::new();
+    inner.on_status(&status);
+    let nowplaying = NowPlaying::new(&inner);
+    if inner.metadata.title.is_empty() {
+        println!("{}", inner.state.name());
+    } else {
+        println!("{}", nowplaying.render("{state} {title} - {artists}"));
+        println!("album:    {}", nowplaying.album);
+        if let Some(uri) = &inner.current_song_uri {
+            println!("uri:      {}", uri);
+        }
+    }
+    println!("state:    {}", inner.state.name());
+    println!("position: {}", nowplaying.render("{position} / {duration}"));
+    println!("mode:     {}", inner.playback_mode.name());
+    if let Some(volume) = status.volume {
+        println!("volume:   {}", volume);
+    }
+    Ok(())
+}
+
+// Print waybar custom module json, which polybar scripts can use as well.
+pub fn bar(config: &Config, format: &str, tooltip: &str) -> Result<(), Box<dyn Error>> {
+    let client = Client::new(&config.server);
+    let inner = follow_status(config, &client)?;
+    print_changes(&inner, |inner| {
+        let nowplaying = NowPlaying::new(inner);
+        let class = inner.state.name();
+        // Nothing is shown when there is no song.
+        let (text, tooltip) = if inner.metadata.title.is_empty() {
+            (String::new(), String::new())
+        } else {
+            (nowplaying.render(format), nowplaying.render(tooltip))
+        };
+        serde_json::json!({
+            "text": text,
+            "tooltip": tooltip,
+            "class": class,
+            "alt": class,
+        })
+        .to_string()
+    })
+}
+
+// The line printed by `pipe` for a message, after it is applied.
+fn pipe_line(inner: &AppInner, topic: &str, body: &[u8]) -> String {
+    let (name, value) = match topic {
+        "player.metadata_changed" => ("metadata", serde_json::json!(inner.metadata).to_string()),
+        "player.state_changed" => ("state", inner.state.name().to_owned()),
+        "player.duration_changed" => ("duration", format!("{:.3}", inner.duration.as_secs_f64())),
+        "player.seeked" => (
+            "position",
+            format!("{:.3}", inner.progress.current().as_secs_f64()),
+        ),
+        "playlist.playback_mode_changed" => ("mode", inner.playback_mode.name().to_owned()),
+        "live_lyric.sentence_changed" => ("lyric", inner.lyric.text().to_owned()),
+        topic => (topic, String::from_utf8_lossy(body).into_owned()),
+    };
+    // Each message takes exactly one line.
+    format!("{}\t{}", name, value.replace(['\r', '\n'], " "))
+}
+
+// Print a line for every message from the server, forever.
+pub fn pipe(config: &Config) -> Result<(), Box<dyn Error>> {
+    let client = Client::new(&config.server);
+    let inner = RwLock::new(fetch_status(&client)?);
+    {
+        let inner = inner.read().unwrap();
+        println!("metadata\t{}", serde_json::json!(inner.metadata));
+        println!("state\t{}", inner.state.name());
+    }
+    let topics = config.topics.iter().map(|topic| topic.as_str()).collect();
+    client.subscribe_topics(topics, &|msg| {
+        let line = {
+            let mut inner = inner.write().unwrap();
+            let (topic, body) = (msg.topic.clone(), msg.body.clone());
+            inner.on_message(msg);
+            pipe_line(&inner, &topic, &body)
+        };
+        let mut stdout = io::stdout();
+        // The reader is gone, like `fust pipe | head`.
+        if writeln!(stdout, "{}", line)
+            .and_then(|_| stdout.flush())
+            .is_err()
+        {
+            std::process::exit(0);
+        }
+    });
+    Ok(())
+}
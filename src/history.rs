@@ -0,0 +1,189 @@
+use crate::logger;
+use crate::models::BriefSong;
+use crate::paths;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Plays shorter than this are not recorded, like skipped songs.
+const MIN_PLAYED: Duration = Duration::from_secs(30);
+
+// A song which has been played, and how long it has actually been played.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Play {
+    pub song: BriefSong,
+    // Unix time in seconds.
+    pub started_at: u64,
+    pub played_secs: u64,
+}
+
+// Plays are appended as json lines to `$XDG_DATA_HOME/fust/history.jsonl`,
+// since the history only grows.
+fn path() -> PathBuf {
+    paths::data_dir().join("history.jsonl")
+}
+
+// All plays, the earliest first. Broken lines are skipped.
+pub fn load() -> Vec<Play> {
+    match fs::read_to_string(path()) {
+        Ok(text) => text
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+fn append(play: &Play) {
+    let path = path();
+    let result = serde_json::to_string(play)
+        .map_err(std::io::Error::from)
+        .and_then(|line| {
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            writeln!(file, "{}", line)
+        });
+    if let Err(e) = result {
+        error!("failed to save {}: {}", path.display(), e);
+    }
+}
+
+// `YYYY-MM-DD HH:MM` of a unix time in the local time zone, or in UTC if the
+// time zone is unknown.
+pub fn fmt_time(secs: u64) -> String {
+    #[cfg(unix)]
+    {
+        let t = secs as libc::time_t;
+        // SAFETY: `localtime_r` only writes to the given `tm`.
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        if !unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+            return format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}",
+                tm.tm_year + 1900,
+                tm.tm_mon + 1,
+                tm.tm_mday,
+                tm.tm_hour,
+                tm.tm_min
+            );
+        }
+    }
+    let mut time = logger::fmt_timestamp(UNIX_EPOCH + Duration::from_secs(secs));
+    time.truncate(16);
+    time
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+struct Playing {
+    song: BriefSong,
+    started_at: u64,
+    played: Duration,
+    last_position: Duration,
+}
+
+// Follow the playing song, which is recorded when another one starts or
+// fust exits.
+#[derive(Default)]
+pub struct Tracker {
+    playing: Option<Playing>,
+}
+
+impl Tracker {
+    pub fn on_tick(&mut self, song: Option<BriefSong>, position: Duration, playing: bool) {
+        let changed = match (&self.playing, &song) {
+            (Some(current), Some(song)) => current.song.uri() != song.uri(),
+            (None, None) => false,
+            _ => true,
+        };
+        if changed {
+            self.finish();
+            self.playing = song.map(|song| Playing {
+                song,
+                started_at: unix_now(),
+                played: Duration::ZERO,
+                last_position: position,
+            });
+            return;
+        }
+        if let (Some(current), Some(song)) = (self.playing.as_mut(), song) {
+            // Metadata like the duration may arrive later.
+            current.song = song;
+            // Only count normal playback, jumps are seeks.
+            if playing && position > current.last_position {
+                let delta = position - current.last_position;
+                if delta < Duration::from_secs(5) {
+                    current.played += delta;
+                }
+            }
+            current.last_position = position;
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(playing) = self.playing.take() {
+            if playing.played >= MIN_PLAYED {
+                append(&Play {
+                    song: playing.song,
+                    started_at: playing.started_at,
+                    played_secs: playing.played.as_secs(),
+                });
+            }
+        }
+    }
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+// Most played songs and artists since a time, with the times they are
+// played, the most played first.
+pub struct Stats {
+    pub plays: usize,
+    pub listened: Duration,
+    pub songs: Vec<(BriefSong, usize)>,
+    pub artists: Vec<(String, usize)>,
+}
+
+impl Stats {
+    pub fn new(plays: &[Play], since: u64) -> Stats {
+        let mut listened = Duration::ZERO;
+        let mut count = 0;
+        let mut songs: HashMap<String, (BriefSong, usize)> = HashMap::new();
+        let mut artists: HashMap<String, usize> = HashMap::new();
+        for play in plays.iter().filter(|play| play.started_at >= since) {
+            count += 1;
+            listened += Duration::from_secs(play.played_secs);
+            songs
+                .entry(play.song.uri().to_string())
+                .or_insert_with(|| (play.song.clone(), 0))
+                .1 += 1;
+            for artist in play.song.artists_name.split(',') {
+                let artist = artist.trim();
+                if !artist.is_empty() {
+                    *artists.entry(artist.to_owned()).or_default() += 1;
+                }
+            }
+        }
+        let mut songs: Vec<(BriefSong, usize)> = songs.into_values().collect();
+        songs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.title.cmp(&b.0.title)));
+        let mut artists: Vec<(String, usize)> = artists.into_iter().collect();
+        artists.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Stats {
+            plays: count,
+            listened,
+            songs,
+            artists,
+        }
+    }
+}
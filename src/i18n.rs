@@ -0,0 +1,307 @@
+// Translations of the user-visible strings, which are written in Chinese in
+// the code and looked up by the Chinese text, like gettext does.
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Locale {
+    ZhCn,
+    EnUs,
+}
+
+pub const LOCALES: [&str; 2] = ["zh-CN", "en-US"];
+
+impl Locale {
+    pub fn from_name(name: &str) -> Option<Locale> {
+        match name {
+            "zh-CN" => Some(Locale::ZhCn),
+            "en-US" => Some(Locale::EnUs),
+            _ => None,
+        }
+    }
+
+    // The locale of the environment, English ones are en-US and others are
+    // zh-CN, which fust has been in.
+    pub fn detect() -> Locale {
+        let lang = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        if lang.starts_with("en") {
+            Locale::EnUs
+        } else {
+            Locale::ZhCn
+        }
+    }
+}
+
+static LOCALE: AtomicU8 = AtomicU8::new(Locale::ZhCn as u8);
+
+pub fn set_locale(locale: Locale) {
+    LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+fn locale() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        x if x == Locale::EnUs as u8 => Locale::EnUs,
+        _ => Locale::ZhCn,
+    }
+}
+
+// The text in the current locale, the Chinese one if it is not translated.
+pub fn translate(text: &'static str) -> &'static str {
+    match locale() {
+        Locale::ZhCn => text,
+        Locale::EnUs => en_us(text).unwrap_or(text),
+    }
+}
+
+// Replace each `{}` of the template with the next argument.
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    let mut text = parts.next().unwrap_or_default().to_owned();
+    for part in parts {
+        if let Some(arg) = args.next() {
+            text.push_str(&arg.to_string());
+        }
+        text.push_str(part);
+    }
+    text
+}
+
+// `tr!("歌手")` translates the text, and `tr!("歌手：{}", name)` formats the
+// translated template with `{}` placeholders like `format!` does.
+macro_rules! tr {
+    ($text:literal) => {
+        $crate::i18n::translate($text)
+    };
+    ($text:literal, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::fill(
+            $crate::i18n::translate($text),
+            &[$(&$arg as &dyn std::fmt::Display),+],
+        )
+    };
+}
+pub(crate) use tr;
+
+// Placeholders are kept in the same order as the Chinese texts.
+fn en_us(text: &str) -> Option<&'static str> {
+    let translated = match text {
+        // Playback.
+        "没有正在播放的歌曲" => "Nothing is playing",
+        "没有选中的歌曲" => "No song is selected",
+        "播放失败" => "Failed to play",
+        "切换播放状态失败" => "Failed to play or pause",
+        "切换歌曲失败" => "Failed to switch the song",
+        "已停止" => "Stopped",
+        "已暂停" => "Paused",
+        "播放中" => "Playing",
+        "正在播放：{} - {}" => "Playing: {} - {}",
+        "播放模式：{}" => "Mode: {}",
+        "设置播放模式失败" => "Failed to set the playback mode",
+        "（正在播放）" => " (playing)",
+        "失败" => "failed",
+        "单曲循环" => "Repeat one",
+        "顺序播放" => "Sequential",
+        "列表循环" => "Repeat all",
+        "随机播放" => "Random",
+        "已重新连接服务器" => "Reconnected to the server",
+        "获取播放状态失败：{}" => "Failed to fetch the player status: {}",
+        "获取播放列表失败：{}" => "Failed to fetch the playlist: {}",
+        "跳转到 (mm:ss): " => "Jump to (mm:ss): ",
+        "超出歌曲时长" => "Beyond the duration of the song",
+        "无效的时间：{}" => "Invalid time: {}",
+        "不支持挂起" => "Suspending is not supported",
+        "A 点：{}" => "A: {}",
+        "B 点需要在 A 点之后" => "B should be after A",
+        "A-B 循环：{} - {}" => "A-B repeat: {} - {}",
+        "已取消 A-B 循环" => "A-B repeat is off",
+        "下一首播放：{}" => "Play next: {}",
+        "添加失败：{}" => "Failed to add: {}",
+        "过滤时无法移动歌曲" => "Songs cannot be moved while filtering",
+        "过滤时无法打乱歌曲" => "Songs cannot be shuffled while filtering",
+        "已打乱 {} 首歌曲" => "Shuffled {} songs",
+        "打乱歌曲失败" => "Failed to shuffle the songs",
+        "撤销失败" => "Failed to undo",
+        "/{} ({} 首匹配)" => "/{} ({} matched)",
+        "[{} 加载中]" => "[loading {}]",
+        // Commands.
+        "用法：export <文件>" => "Usage: export <file>",
+        "已清除 {} 首歌词缓存" => "Cleared {} cached lyrics",
+        "没有歌词缓存" => "No cached lyrics",
+        "清除缓存失败：{}" => "Failed to clear the cache: {}",
+        "未知命令：{}" => "Unknown command: {}",
+        "已取消睡眠定时" => "Sleep timer is off",
+        "无效的时长：{}" => "Invalid duration: {}",
+        "用法：sleep <时长，如 30m> [fade] 或 sleep off" => {
+            "Usage: sleep <duration, like 30m> [fade] or sleep off"
+        }
+        "{} 后暂停播放" => "Pause in {}",
+        "睡眠定时结束，已暂停播放" => "Sleep timer is done, paused",
+        "用法：quality {}" => "Usage: quality {}",
+        "优先播放 {} 音质，下一首歌曲生效" => "Prefer {} quality from the next song",
+        "设置音质失败" => "Failed to set the quality",
+        "已导出到 {}" => "Exported to {}",
+        "导出失败：{}" => "Failed to export: {}",
+        "用法：rate <0-{}>" => "Usage: rate <0-{}>",
+        // Lyrics.
+        "歌词" => "Lyrics",
+        "暂无歌词" => "No lyrics",
+        "获取歌词失败" => "Failed to fetch the lyrics",
+        "获取歌词失败：{}" => "Failed to fetch the lyrics: {}",
+        "歌词没有时间轴" => "The lyrics are not timed",
+        "歌词偏移 {} 秒" => "Lyrics offset {}s",
+        "歌词：{}" => "Lyrics: {}",
+        "歌词：{}（这首歌没有翻译）" => "Lyrics: {} (no translation for this song)",
+        "原文" => "original",
+        "译文" => "translation",
+        "双语" => "bilingual",
+        // Pages.
+        "歌曲标题" => "Title",
+        "歌曲" => "Song",
+        "歌手" => "Artist",
+        "专辑" => "Album",
+        "时长" => "Duration",
+        "播放时间" => "Played at",
+        "播放次数" => "Plays",
+        "歌单" => "Playlist",
+        "创建者" => "Creator",
+        "名称" => "Name",
+        "类型" => "Type",
+        "标识" => "ID",
+        "状态" => "Status",
+        "收藏集" => "Collection",
+        "智能歌单" => "Smart playlist",
+        "加载中…" => "Loading…",
+        "搜索：" => "Search: ",
+        "搜索：{}" => "Search: {}",
+        "搜索：{}（{}）" => "Search: {} ({})",
+        "搜索来源" => "Search sources",
+        "在所有来源中搜索" => "Search in all sources",
+        "在 {} 中搜索" => "Search in {}",
+        "{}，{} 个歌单" => "{}, {} playlists",
+        "{}，{} 首歌曲" => "{}, {} songs",
+        "{}失败：{}" => "{} failed: {}",
+        "{}（已标记 {} 首）" => "{} ({} marked)",
+        "{}（enter 查看歌曲，ctrl+a 添加全部，ctrl+p 播放全部）" => {
+            "{} (enter to open, ctrl+a to add all, ctrl+p to play all)"
+        }
+        "每日推荐歌曲" => "Daily songs",
+        "每日推荐歌单" => "Daily playlists",
+        "每日推荐：{}" => "Daily songs: {}",
+        "推荐歌单：{}" => "Daily playlists: {}",
+        "排行榜" => "Charts",
+        "排行榜：{}" => "Charts: {}",
+        "歌单：{}" => "Playlist: {}",
+        "歌单「{}」" => "playlist \"{}\"",
+        "歌手：{}" => "Artist: {}",
+        "专辑…" => "Album…",
+        "专辑：{}" => "Album: {}",
+        "相似歌曲：{}" => "Similar songs: {}",
+        "{} 的热门歌曲" => "top songs of {}",
+        "没有歌手信息" => "No artist of the song",
+        "没有专辑信息" => "No album of the song",
+        "获取歌手失败：{}" => "Failed to fetch the artists: {}",
+        "获取{}…" => "Fetching {}…",
+        "没有歌曲" => "No songs",
+        "清空播放列表失败" => "Failed to clear the playlist",
+        "正在添加 {}：{}/{}" => "Adding {}: {}/{}",
+        "添加{}失败：{}" => "Failed to add {}: {}",
+        "播放 {} 首歌曲" => "Playing {} songs",
+        "已添加 {} 首歌曲" => "Added {} songs",
+        "播放历史（/ 搜索）" => "History (/ to search)",
+        "播放历史：{}（{} 首）" => "History: {} ({} songs)",
+        "播放统计：{}，播放 {} 次，共收听 {}（g S 切换时间范围）" => {
+            "Stats: {}, {} plays, listened {} (g S to switch the range)"
+        }
+        "还没有播放记录" => "Nothing has been played yet",
+        "最近 7 天" => "last 7 days",
+        "最近 30 天" => "last 30 days",
+        "全部" => "all time",
+        // Bookmarks and collections.
+        "书签" => "Bookmarks",
+        "已加入书签：{}" => "Bookmarked: {}",
+        "已移除书签：{}" => "Removed the bookmark: {}",
+        "没有书签，按 m 将歌曲加入书签" => "No bookmarks, press m to bookmark a song",
+        "{}：{}" => "{}: {}",
+        "智能歌单：{}" => "Smart playlist: {}",
+        "收藏集（enter 查看歌曲，+ 添加歌曲）" => {
+            "Collections (enter to open, + to add songs)"
+        }
+        "收藏集：{}" => "Collection: {}",
+        "获取收藏集失败：{}" => "Failed to fetch the collections: {}",
+        "没有收藏集" => "No collections",
+        "将 {} 添加到收藏集" => "Add {} to a collection",
+        "已添加到收藏集：{}" => "Added to the collection: {}",
+        "添加到收藏集失败" => "Failed to add to the collection",
+        "已从收藏集移除：{}" => "Removed from the collection: {}",
+        "从收藏集移除失败" => "Failed to remove from the collection",
+        "没有可添加的歌单" => "No playlists to add to",
+        "将 {} 添加到歌单" => "Add {} to a playlist",
+        "获取歌单失败：{}" => "Failed to fetch the playlists: {}",
+        "已添加到歌单：{}" => "Added to the playlist: {}",
+        "添加到歌单失败：{}" => "Failed to add to the playlist: {}",
+        // Audio devices.
+        "音频输出设备" => "Audio output devices",
+        "没有音频输出设备" => "No audio output devices",
+        "获取音频输出设备失败：{}" => "Failed to fetch the audio output devices: {}",
+        "已切换音频输出：{}" => "Audio output: {}",
+        "切换音频输出设备失败" => "Failed to switch the audio output device",
+        // Providers.
+        "获取音乐来源失败" => "Failed to fetch the providers",
+        "音乐来源（enter 扫码登录，g t 排行榜）" => {
+            "Providers (enter to log in, g t for charts)"
+        }
+        "{} 不支持扫码登录：{}" => "{} does not support logging in by QR code: {}",
+        "登录 {}" => "Log in {}",
+        "登录失败：{}" => "Failed to log in: {}",
+        "已登录" => "Logged in",
+        "已登录：{}" => "Logged in: {}",
+        "已登录 {}：{}" => "Logged in {}: {}",
+        "请使用 {} 的手机客户端扫码" => "Scan with the phone app of {}",
+        "已扫码，请在手机上确认" => "Scanned, please confirm on the phone",
+        "二维码已过期，按 enter 刷新" => "The QR code expired, press enter to refresh",
+        "二维码内容太长" => "The QR code is too long",
+        "私人 FM" => "Personal FM",
+        "私人 FM：{}（即将播放）" => "Personal FM: {} (up next)",
+        "已开启私人 FM：{}" => "Personal FM is on: {}",
+        "{} 不支持私人 FM" => "{} does not support personal FM",
+        "已关闭私人 FM" => "Personal FM is off",
+        "已标记为不喜欢" => "Disliked",
+        "标记不喜欢失败" => "Failed to dislike",
+        "已喜欢" => "Liked",
+        "已取消喜欢" => "Unliked",
+        "操作失败，来源可能不支持喜欢歌曲" => {
+            "Failed, the provider may not support liking songs"
+        }
+        // Web pages and videos.
+        "这首歌曲没有网页" => "The song has no web page",
+        "获取歌曲信息失败：{}" => "Failed to fetch the song: {}",
+        "已在浏览器中打开：{}" => "Opened in the browser: {}",
+        "无法打开浏览器：{}" => "Failed to open the browser: {}",
+        "无法获取 MV 的播放地址" => "Failed to get the url of the MV",
+        "这首歌曲没有 MV" => "The song has no MV",
+        "获取 MV 失败：{}" => "Failed to fetch the MV: {}",
+        "无法运行 {}：{}" => "Failed to run {}: {}",
+        "正在播放 MV：{}" => "Playing the MV: {}",
+        // Comments.
+        "获取评论失败：{}" => "Failed to fetch the comments: {}",
+        "暂无评论" => "No comments",
+        "匿名用户" => "Anonymous",
+        "热门评论：{}" => "Hot comments: {}",
+        // Logs.
+        "日志 (跟随)" => "Logs (following)",
+        "日志 (↑{})" => "Logs (↑{})",
+        " (已暂停)" => " (paused)",
+        " (跟随)" => " (following)",
+        // Dialogs.
+        "确认" => "Confirm",
+        "y 确定，n 取消" => "y to confirm, n to cancel",
+        "将 {} 从收藏集 {} 移除？" => "Remove {} from the collection {}?",
+        "按键" => "Keys",
+        _ => return None,
+    };
+    Some(translated)
+}
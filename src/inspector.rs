@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// Number of requests, responses and messages kept for the inspector.
+const CAPACITY: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Request,
+    Response { ok: bool },
+    Message,
+}
+
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub ts: SystemTime,
+    pub kind: Kind,
+    // The command of requests and responses, or the topic of messages.
+    pub topic: String,
+    pub body: Vec<u8>,
+}
+
+impl Record {
+    // The body, pretty printed if it is json.
+    pub fn pretty_body(&self) -> String {
+        match serde_json::from_slice::<serde_json::Value>(&self.body) {
+            Ok(value) => serde_json::to_string_pretty(&value).unwrap(),
+            Err(_) => String::from_utf8_lossy(&self.body).into_owned(),
+        }
+    }
+}
+
+struct Traffic {
+    records: VecDeque<Record>,
+    paused: bool,
+}
+
+static TRAFFIC: Mutex<Traffic> = Mutex::new(Traffic {
+    records: VecDeque::new(),
+    paused: false,
+});
+
+// Record traffic with the server, which is ignored while paused.
+pub fn record(kind: Kind, topic: &str, body: &[u8]) {
+    let mut traffic = TRAFFIC.lock().unwrap();
+    if traffic.paused {
+        return;
+    }
+    if traffic.records.len() >= CAPACITY {
+        traffic.records.pop_front();
+    }
+    traffic.records.push_back(Record {
+        ts: SystemTime::now(),
+        kind,
+        topic: topic.to_owned(),
+        body: body.to_vec(),
+    });
+}
+
+// Recorded traffic whose topic contains the filter, oldest first.
+pub fn records(filter: &str) -> Vec<Record> {
+    TRAFFIC
+        .lock()
+        .unwrap()
+        .records
+        .iter()
+        .filter(|record| record.topic.contains(filter))
+        .cloned()
+        .collect()
+}
+
+pub fn clear() {
+    TRAFFIC.lock().unwrap().records.clear();
+}
+
+pub fn is_paused() -> bool {
+    TRAFFIC.lock().unwrap().paused
+}
+
+pub fn toggle_paused() -> bool {
+    let mut traffic = TRAFFIC.lock().unwrap();
+    traffic.paused = !traffic.paused;
+    traffic.paused
+}
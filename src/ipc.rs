@@ -0,0 +1,87 @@
+use crate::keymap::{parse_action, Action};
+use crate::paths;
+use log::{error, info, warn};
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+// The running TUI listens on a unix socket, where each line is an action
+// name like `next`, and replies `ok` or an error.
+pub struct Server {
+    path: PathBuf,
+}
+
+impl Server {
+    // Listen for actions sent by `fust --send`, `None` if another instance
+    // is already listening.
+    pub fn start(tx: Sender<Action>) -> Option<Server> {
+        let path = paths::socket_file();
+        if UnixStream::connect(&path).is_ok() {
+            warn!("ipc: another instance listens on {}", path.display());
+            return None;
+        }
+        // The socket is left over by an instance which did not exit cleanly.
+        let _ = fs::remove_file(&path);
+        let listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("ipc: failed to listen on {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        info!("ipc: listening on {}", path.display());
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = handle(stream, &tx) {
+                    error!("ipc: {}", e);
+                }
+            }
+        });
+        Some(Server { path })
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn handle(stream: UnixStream, tx: &Sender<Action>) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let name = line.trim();
+        let reply = match parse_action(name) {
+            Some(action) => {
+                info!("ipc: received {}", name);
+                // The TUI is gone if sending fails.
+                match tx.send(action) {
+                    Ok(()) => "ok".to_owned(),
+                    Err(_) => "error: fust is exiting".to_owned(),
+                }
+            }
+            None => format!("error: unknown action {:?}", name),
+        };
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(())
+}
+
+// Send an action to the running instance.
+pub fn send(action: &str) -> Result<(), Box<dyn Error>> {
+    let path = paths::socket_file();
+    let mut stream =
+        UnixStream::connect(&path).map_err(|e| format!("fust is not running: {}", e))?;
+    writeln!(stream, "{}", action)?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    match reply.trim() {
+        "ok" => Ok(()),
+        reply => Err(reply.trim_start_matches("error: ").to_owned().into()),
+    }
+}
@@ -0,0 +1,588 @@
+use crate::config::ConfigError;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    PlayPause,
+    Next,
+    Previous,
+    SeekForward,
+    SeekBackward,
+    CycleRepeat,
+    ToggleRandom,
+    SpeedUp,
+    SlowDown,
+    JumpToTime,
+    ShowPlaylist,
+    SelectNext,
+    SelectPrevious,
+    SelectCurrent,
+    PlaySelected,
+    EnqueueNext,
+    Remove,
+    MoveUp,
+    MoveDown,
+    Undo,
+    Filter,
+    Command,
+    ToggleLogs,
+    ToggleInspector,
+    PauseInspector,
+    ClearInspector,
+    Suspend,
+    ToggleLyrics,
+    CycleLyricMode,
+    LyricEarlier,
+    LyricLater,
+    Search,
+    SearchSources,
+    Back,
+    Providers,
+    DailySongs,
+    DailyPlaylists,
+    ToggleFm,
+    Dislike,
+    SimilarSongs,
+    EnqueueAll,
+    ToggleComments,
+    ScrollCommentsDown,
+    ScrollCommentsUp,
+    FocusComments,
+    Charts,
+    PlayAll,
+    Library,
+    AddToCollection,
+    ToggleLike,
+    AddToPlaylist,
+    OpenWebPage,
+    PlayMv,
+    SleepTimer,
+    AbRepeat,
+    AudioDevice,
+    ToggleBookmark,
+    Bookmarks,
+    ToggleMark,
+    ArtistRadio,
+    Shuffle,
+    Stats,
+    History,
+    Album,
+    Artist,
+    ToggleRemaining,
+    ScrollLyricDown,
+    ScrollLyricUp,
+    Help,
+}
+
+// Action names used in the `[keys]` section of config.
+const ACTIONS: [(&str, Action); 70] = [
+    ("quit", Action::Quit),
+    ("play_pause", Action::PlayPause),
+    ("next", Action::Next),
+    ("previous", Action::Previous),
+    ("seek_forward", Action::SeekForward),
+    ("seek_backward", Action::SeekBackward),
+    ("cycle_repeat", Action::CycleRepeat),
+    ("toggle_random", Action::ToggleRandom),
+    ("speed_up", Action::SpeedUp),
+    ("slow_down", Action::SlowDown),
+    ("jump_to_time", Action::JumpToTime),
+    ("show_playlist", Action::ShowPlaylist),
+    ("select_next", Action::SelectNext),
+    ("select_previous", Action::SelectPrevious),
+    ("select_current", Action::SelectCurrent),
+    ("play_selected", Action::PlaySelected),
+    ("enqueue_next", Action::EnqueueNext),
+    ("remove", Action::Remove),
+    ("move_up", Action::MoveUp),
+    ("move_down", Action::MoveDown),
+    ("undo", Action::Undo),
+    ("filter", Action::Filter),
+    ("command", Action::Command),
+    ("toggle_logs", Action::ToggleLogs),
+    ("toggle_inspector", Action::ToggleInspector),
+    ("pause_inspector", Action::PauseInspector),
+    ("clear_inspector", Action::ClearInspector),
+    ("suspend", Action::Suspend),
+    ("toggle_lyrics", Action::ToggleLyrics),
+    ("cycle_lyric_mode", Action::CycleLyricMode),
+    ("lyric_earlier", Action::LyricEarlier),
+    ("lyric_later", Action::LyricLater),
+    ("search", Action::Search),
+    ("search_sources", Action::SearchSources),
+    ("back", Action::Back),
+    ("providers", Action::Providers),
+    ("daily_songs", Action::DailySongs),
+    ("daily_playlists", Action::DailyPlaylists),
+    ("toggle_fm", Action::ToggleFm),
+    ("dislike", Action::Dislike),
+    ("similar_songs", Action::SimilarSongs),
+    ("enqueue_all", Action::EnqueueAll),
+    ("toggle_comments", Action::ToggleComments),
+    ("scroll_comments_down", Action::ScrollCommentsDown),
+    ("scroll_comments_up", Action::ScrollCommentsUp),
+    ("focus_comments", Action::FocusComments),
+    ("charts", Action::Charts),
+    ("play_all", Action::PlayAll),
+    ("library", Action::Library),
+    ("add_to_collection", Action::AddToCollection),
+    ("toggle_like", Action::ToggleLike),
+    ("add_to_playlist", Action::AddToPlaylist),
+    ("open_web_page", Action::OpenWebPage),
+    ("play_mv", Action::PlayMv),
+    ("sleep_timer", Action::SleepTimer),
+    ("ab_repeat", Action::AbRepeat),
+    ("audio_device", Action::AudioDevice),
+    ("toggle_bookmark", Action::ToggleBookmark),
+    ("bookmarks", Action::Bookmarks),
+    ("toggle_mark", Action::ToggleMark),
+    ("artist_radio", Action::ArtistRadio),
+    ("shuffle", Action::Shuffle),
+    ("stats", Action::Stats),
+    ("history", Action::History),
+    ("album", Action::Album),
+    ("artist", Action::Artist),
+    ("toggle_remaining", Action::ToggleRemaining),
+    ("scroll_lyric_down", Action::ScrollLyricDown),
+    ("scroll_lyric_up", Action::ScrollLyricUp),
+    ("help", Action::Help),
+];
+
+fn default_bindings() -> Vec<(Action, &'static [&'static str])> {
+    vec![
+        (Action::Quit, &["q"]),
+        (Action::PlayPause, &["p", "space"]),
+        (Action::Next, &["n"]),
+        (Action::Previous, &["N"]),
+        (Action::SeekForward, &["right"]),
+        (Action::SeekBackward, &["left"]),
+        (Action::CycleRepeat, &["r"]),
+        (Action::ToggleRandom, &["z"]),
+        (Action::SpeedUp, &["]"]),
+        (Action::SlowDown, &["["]),
+        (Action::JumpToTime, &["t"]),
+        (Action::ShowPlaylist, &["l"]),
+        (Action::SelectNext, &["j", "down"]),
+        (Action::SelectPrevious, &["k", "up"]),
+        (Action::SelectCurrent, &["g c", "'"]),
+        (Action::PlaySelected, &["enter"]),
+        (Action::EnqueueNext, &["a"]),
+        (Action::Remove, &["d d"]),
+        (Action::MoveUp, &["K", "alt+up"]),
+        (Action::MoveDown, &["J", "alt+down"]),
+        (Action::Undo, &["u"]),
+        (Action::Filter, &["/"]),
+        (Action::Command, &[":"]),
+        (Action::ToggleLogs, &["~"]),
+        (Action::ToggleInspector, &["`"]),
+        (Action::PauseInspector, &["P"]),
+        (Action::ClearInspector, &["C"]),
+        (Action::Suspend, &["ctrl+z"]),
+        (Action::ToggleLyrics, &["L"]),
+        (Action::CycleLyricMode, &["T"]),
+        (Action::LyricEarlier, &[","]),
+        (Action::LyricLater, &["."]),
+        (Action::Search, &["s"]),
+        (Action::SearchSources, &["S"]),
+        (Action::Back, &["esc"]),
+        (Action::Providers, &["g p"]),
+        (Action::DailySongs, &["g d"]),
+        (Action::DailyPlaylists, &["g D"]),
+        (Action::ToggleFm, &["g f"]),
+        (Action::Dislike, &["X"]),
+        (Action::SimilarSongs, &["g s"]),
+        (Action::EnqueueAll, &["ctrl+a"]),
+        (Action::ToggleComments, &["c"]),
+        (Action::ScrollCommentsDown, &["}"]),
+        (Action::ScrollCommentsUp, &["{"]),
+        (Action::FocusComments, &["tab"]),
+        (Action::Charts, &["g t"]),
+        (Action::PlayAll, &["ctrl+p"]),
+        (Action::Library, &["g l"]),
+        (Action::AddToCollection, &["+"]),
+        (Action::ToggleLike, &["*"]),
+        (Action::AddToPlaylist, &["A"]),
+        (Action::OpenWebPage, &["O"]),
+        (Action::PlayMv, &["M"]),
+        (Action::SleepTimer, &["g z"]),
+        (Action::AbRepeat, &["b"]),
+        (Action::AudioDevice, &["g o"]),
+        (Action::ToggleBookmark, &["m"]),
+        (Action::Bookmarks, &["g b"]),
+        (Action::ToggleMark, &["v"]),
+        (Action::ArtistRadio, &["g r"]),
+        (Action::Shuffle, &["Z"]),
+        (Action::Stats, &["g S"]),
+        (Action::History, &["g h"]),
+        (Action::Album, &["g a"]),
+        (Action::Artist, &["g A"]),
+        (Action::ToggleRemaining, &["="]),
+        (Action::Help, &["?"]),
+    ]
+}
+
+// Where keys are typed, from the whole app to the view on top and then the
+// focused widget in it. Bindings of a context are only active while it is
+// shown.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Context {
+    Global,
+    Page,
+    Lyrics,
+    Logs,
+    Inspector,
+    // The focused comments pane.
+    Comments,
+}
+
+// Context names of the tables in the `[keys]` section, like `[keys.lyrics]`.
+const CONTEXTS: [(&str, Context); 5] = [
+    ("page", Context::Page),
+    ("lyrics", Context::Lyrics),
+    ("logs", Context::Logs),
+    ("inspector", Context::Inspector),
+    ("comments", Context::Comments),
+];
+
+// Default bindings which are only active in a context other than the global
+// one.
+fn context_bindings() -> Vec<(Context, Action, &'static [&'static str])> {
+    vec![
+        (Context::Lyrics, Action::ScrollLyricDown, &["j", "down"]),
+        (Context::Lyrics, Action::ScrollLyricUp, &["k", "up"]),
+        (
+            Context::Comments,
+            Action::ScrollCommentsDown,
+            &["j", "down"],
+        ),
+        (Context::Comments, Action::ScrollCommentsUp, &["k", "up"]),
+        (Context::Comments, Action::FocusComments, &["esc"]),
+    ]
+}
+
+pub const PROFILES: [&str; 1] = ["default"];
+
+// Key specs of an action in config, either `"ctrl+n"` or `["j", "down"]`,
+// or a table of them for a context like `[keys.lyrics]`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum KeySpecs {
+    One(String),
+    Many(Vec<String>),
+    Context(HashMap<String, KeySpecs>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub fn from_event(event: KeyEvent) -> Key {
+        let mut modifiers = event.modifiers;
+        // The shift state is already a part of chars, like `J`.
+        if let KeyCode::Char(_) = event.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Key {
+            code: event.code,
+            modifiers,
+        }
+    }
+}
+
+// Parse a key like `j`, `ctrl+n`, `alt+down` or `space`.
+fn parse_key(s: &str) -> Option<Key> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut parts: Vec<&str> = s.split('+').collect();
+    // `+` itself is a valid key.
+    let name = match parts.pop()? {
+        "" if s.ends_with('+') => {
+            parts.pop();
+            "+"
+        }
+        name => name,
+    };
+    for part in parts {
+        match part.to_lowercase().as_str() {
+            "ctrl" => modifiers.insert(KeyModifiers::CONTROL),
+            "alt" => modifiers.insert(KeyModifiers::ALT),
+            "shift" => modifiers.insert(KeyModifiers::SHIFT),
+            _ => return None,
+        }
+    }
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match name.to_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "delete" => KeyCode::Delete,
+            f => KeyCode::F(f.strip_prefix('f')?.parse().ok()?),
+        },
+    };
+    // Same as `Key::from_event`, `shift+j` is `J`.
+    if let KeyCode::Char(c) = code {
+        if modifiers.contains(KeyModifiers::SHIFT) {
+            modifiers.remove(KeyModifiers::SHIFT);
+            return Some(Key {
+                code: KeyCode::Char(c.to_ascii_uppercase()),
+                modifiers,
+            });
+        }
+    }
+    Some(Key { code, modifiers })
+}
+
+// The spec of a key, the other way round of `parse_key`.
+fn fmt_key(key: &Key) -> String {
+    let mut s = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        s.push_str("ctrl+");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        s.push_str("alt+");
+    }
+    if key.modifiers.contains(KeyModifiers::SHIFT) {
+        s.push_str("shift+");
+    }
+    match key.code {
+        KeyCode::Char(' ') => s.push_str("space"),
+        KeyCode::Char(c) => s.push(c),
+        KeyCode::F(n) => s.push_str(&format!("f{}", n)),
+        code => s.push_str(match code {
+            KeyCode::Enter => "enter",
+            KeyCode::Esc => "esc",
+            KeyCode::Tab => "tab",
+            KeyCode::Backspace => "backspace",
+            KeyCode::Up => "up",
+            KeyCode::Down => "down",
+            KeyCode::Left => "left",
+            KeyCode::Right => "right",
+            KeyCode::PageUp => "pageup",
+            KeyCode::PageDown => "pagedown",
+            KeyCode::Home => "home",
+            KeyCode::End => "end",
+            KeyCode::Delete => "delete",
+            _ => "?",
+        }),
+    }
+    s
+}
+
+// Parse a key sequence like `g g`.
+fn parse_keys(s: &str) -> Result<Vec<Key>, ConfigError> {
+    let keys: Option<Vec<Key>> = s.split_whitespace().map(parse_key).collect();
+    match keys {
+        Some(keys) if !keys.is_empty() => Ok(keys),
+        _ => Err(ConfigError::Invalid(format!("invalid key {:?}", s))),
+    }
+}
+
+fn is_prefix(a: &[Key], b: &[Key]) -> bool {
+    a.len() <= b.len() && b[..a.len()] == *a
+}
+
+// Parse the bindings of an action in the context, checking that they do not
+// conflict with those parsed before in the same context.
+fn add_user_bindings<'a>(
+    bindings: &mut Vec<(Context, Vec<Key>, Action, &'a str)>,
+    context: Context,
+    name: &str,
+    specs: &'a KeySpecs,
+) -> Result<(), ConfigError> {
+    let action = match parse_action(name) {
+        Some(action) => action,
+        None => {
+            return Err(ConfigError::Invalid(format!(
+                "unknown action {:?} in [keys]",
+                name
+            )))
+        }
+    };
+    let specs = match specs {
+        KeySpecs::One(spec) => vec![spec.as_str()],
+        KeySpecs::Many(specs) => specs.iter().map(|spec| spec.as_str()).collect(),
+        KeySpecs::Context(_) => {
+            return Err(ConfigError::Invalid(format!(
+                "keys of {:?} should be a key or a list of keys",
+                name
+            )))
+        }
+    };
+    for spec in specs {
+        let seq = parse_keys(spec)?;
+        if let Some((_, _, other, other_spec)) = bindings
+            .iter()
+            .find(|(c, s, _, _)| *c == context && (is_prefix(s, &seq) || is_prefix(&seq, s)))
+        {
+            return Err(ConfigError::Invalid(format!(
+                "key {:?} of {:?} conflicts with key {:?} of {:?}",
+                spec,
+                name,
+                other_spec,
+                action_name(*other)
+            )));
+        }
+        bindings.push((context, seq, action, spec));
+    }
+    Ok(())
+}
+
+// Dispatch table from key sequences to actions.
+pub struct Keymap {
+    bindings: Vec<(Context, Vec<Key>, Action)>,
+    // Keys typed so far which are a prefix of some binding.
+    pending: Vec<Key>,
+}
+
+impl Keymap {
+    pub fn new(profile: &str, keys: &HashMap<String, KeySpecs>) -> Result<Keymap, ConfigError> {
+        if !PROFILES.contains(&profile) {
+            return Err(ConfigError::Invalid(format!(
+                "unknown keymap {:?}, expected one of {:?}",
+                profile, PROFILES
+            )));
+        }
+
+        let mut user_bindings: Vec<(Context, Vec<Key>, Action, &str)> = vec![];
+        for (name, specs) in keys {
+            let table = match specs {
+                KeySpecs::Context(table) => table,
+                specs => {
+                    add_user_bindings(&mut user_bindings, Context::Global, name, specs)?;
+                    continue;
+                }
+            };
+            let context = match CONTEXTS.iter().find(|(n, _)| n == name) {
+                Some((_, context)) => *context,
+                None => {
+                    let names: Vec<&str> = CONTEXTS.iter().map(|(n, _)| *n).collect();
+                    return Err(ConfigError::Invalid(format!(
+                        "unknown context [keys.{}], expected one of {:?}",
+                        name, names
+                    )));
+                }
+            };
+            for (name, specs) in table {
+                add_user_bindings(&mut user_bindings, context, name, specs)?;
+            }
+        }
+
+        // User bindings replace the default ones of the same action in every
+        // context, and shadow default ones in the same context which would
+        // conflict with them.
+        let defaults = default_bindings()
+            .into_iter()
+            .map(|(action, specs)| (Context::Global, action, specs))
+            .chain(context_bindings());
+        let mut bindings: Vec<(Context, Vec<Key>, Action)> = vec![];
+        for (context, action, specs) in defaults {
+            if user_bindings.iter().any(|(_, _, a, _)| *a == action) {
+                continue;
+            }
+            for spec in specs {
+                let seq = parse_keys(spec).unwrap();
+                if !user_bindings
+                    .iter()
+                    .any(|(c, s, _, _)| *c == context && (is_prefix(s, &seq) || is_prefix(&seq, s)))
+                {
+                    bindings.push((context, seq, action));
+                }
+            }
+        }
+        bindings.extend(
+            user_bindings
+                .into_iter()
+                .map(|(context, seq, action, _)| (context, seq, action)),
+        );
+        Ok(Keymap {
+            bindings,
+            pending: vec![],
+        })
+    }
+
+    // Keys of every action which has some, with the context if it is not
+    // the global one, for the help.
+    pub fn describe(&self) -> Vec<(String, &'static str)> {
+        ACTIONS
+            .iter()
+            .filter_map(|(name, action)| {
+                let keys: Vec<String> = self
+                    .bindings
+                    .iter()
+                    .filter(|(_, _, a)| a == action)
+                    .map(|(context, seq, _)| {
+                        let seq: Vec<String> = seq.iter().map(fmt_key).collect();
+                        match CONTEXTS.iter().find(|(_, c)| c == context) {
+                            Some((context, _)) => format!("{} ({})", seq.join(" "), context),
+                            None => seq.join(" "),
+                        }
+                    })
+                    .collect();
+                (!keys.is_empty()).then(|| (keys.join(", "), *name))
+            })
+            .collect()
+    }
+
+    // Feed a key event, returns the action once a whole binding is typed.
+    // `contexts` are the active ones from the global one to the innermost.
+    pub fn feed(&mut self, event: KeyEvent, contexts: &[Context]) -> Option<Action> {
+        self.pending.push(Key::from_event(event));
+        if let Some(action) = self.lookup(contexts) {
+            return action;
+        }
+        // Start over from the last key if the sequence matches nothing.
+        let last = self.pending.pop().unwrap();
+        self.pending = vec![last];
+        self.lookup(contexts).unwrap_or_else(|| {
+            self.pending.clear();
+            None
+        })
+    }
+
+    // `None` if nothing matches, `Some(None)` if waiting for more keys.
+    fn lookup(&mut self, contexts: &[Context]) -> Option<Option<Action>> {
+        // The innermost context which binds the keys, or a longer sequence
+        // starting with them, decides. So `j` of the lyrics view wins over
+        // the global one while it is shown.
+        for context in contexts.iter().rev() {
+            let mut bindings = self.bindings.iter().filter(|(c, _, _)| c == context);
+            if let Some((_, _, action)) = bindings.clone().find(|(_, s, _)| *s == self.pending) {
+                self.pending.clear();
+                return Some(Some(*action));
+            }
+            if bindings.any(|(_, s, _)| is_prefix(&self.pending, s)) {
+                return Some(None);
+            }
+        }
+        None
+    }
+}
+
+// Parse an action name used in config, like `play_pause`.
+pub fn parse_action(name: &str) -> Option<Action> {
+    ACTIONS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, action)| *action)
+}
+
+fn action_name(action: Action) -> &'static str {
+    ACTIONS.iter().find(|(_, a)| *a == action).unwrap().0
+}
@@ -0,0 +1,255 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Log levels like `info,rpc=debug,zbus=warn`, where module names of fust
+// may omit the `fust::` prefix.
+#[derive(Debug, Clone)]
+pub struct LogFilter {
+    default: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    pub fn parse(s: &str) -> Option<LogFilter> {
+        let mut filter = LogFilter {
+            default: LevelFilter::Info,
+            modules: vec![],
+        };
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => filter
+                    .modules
+                    .push((module.trim().to_owned(), level.trim().parse().ok()?)),
+                None => filter.default = directive.parse().ok()?,
+            }
+        }
+        Some(filter)
+    }
+
+    // Set the default level to info raised by `n` like `-vv`, or lowered if it
+    // is negative, instead of the configured one. Nothing changes if it is 0.
+    pub fn verbosity(mut self, n: i32) -> LogFilter {
+        if n == 0 {
+            return self;
+        }
+        let level = (LevelFilter::Info as i32 + n).clamp(0, LevelFilter::max() as i32);
+        self.default = LevelFilter::iter()
+            .nth(level as usize)
+            .unwrap_or(self.default);
+        self
+    }
+
+    fn matches(module: &str, target: &str) -> bool {
+        let is_prefix_of = |target: &str| {
+            target
+                .strip_prefix(module)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        };
+        is_prefix_of(target) || target.strip_prefix("fust::").is_some_and(is_prefix_of)
+    }
+
+    // The level of the most specific module matching the target.
+    fn level(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| LogFilter::matches(module, target))
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+// Format a unix timestamp as `YYYY-MM-DD HH:MM:SS.mmm` in UTC.
+pub fn fmt_timestamp(ts: SystemTime) -> String {
+    let elapsed = ts.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = elapsed.as_secs();
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // Civil date from days since epoch, see Howard Hinnant's date algorithms.
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+// Number of records kept in memory for the log panel.
+const RECENT_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub time: String,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+struct Recent {
+    entries: VecDeque<Entry>,
+    // Number of records ever logged, used to tell how many are new.
+    total: usize,
+}
+
+static RECENT: Mutex<Recent> = Mutex::new(Recent {
+    entries: VecDeque::new(),
+    total: 0,
+});
+
+// The most recent records, oldest first, and the number of records ever
+// logged.
+pub fn recent() -> (Vec<Entry>, usize) {
+    let recent = RECENT.lock().unwrap();
+    (recent.entries.iter().cloned().collect(), recent.total)
+}
+
+fn push_recent(entry: Entry) {
+    let mut recent = RECENT.lock().unwrap();
+    if recent.entries.len() >= RECENT_CAPACITY {
+        recent.entries.pop_front();
+    }
+    recent.entries.push_back(entry);
+    recent.total += 1;
+}
+
+struct LogFile {
+    file: File,
+    size: u64,
+}
+
+// Log to a file, which is rotated to `fust.log.1`, `fust.log.2` and so on
+// when it grows larger than `max_size`. Nothing is written to stdout since
+// the TUI owns the terminal.
+pub struct FileLogger {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    filter: LogFilter,
+    file: Mutex<LogFile>,
+}
+
+fn open(path: &Path) -> io::Result<LogFile> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok(LogFile { file, size })
+}
+
+fn rotated(path: &Path, i: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", i));
+    PathBuf::from(name)
+}
+
+impl FileLogger {
+    pub fn init(
+        path: PathBuf,
+        filter: LogFilter,
+        max_size: u64,
+        max_files: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let file = open(&path).map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+        log::set_max_level(filter.max_level());
+        log::set_boxed_logger(Box::new(FileLogger {
+            path,
+            max_size,
+            max_files,
+            filter,
+            file: Mutex::new(file),
+        }))?;
+        Ok(())
+    }
+
+    fn rotate(&self, file: &mut LogFile) -> io::Result<()> {
+        if self.max_files == 0 {
+            file.file.set_len(0)?;
+        } else {
+            for i in (1..self.max_files).rev() {
+                let _ = fs::rename(rotated(&self.path, i), rotated(&self.path, i + 1));
+            }
+            fs::rename(&self.path, rotated(&self.path, 1))?;
+        }
+        *file = open(&self.path)?;
+        Ok(())
+    }
+}
+
+// A structured field like `cmd=status`, which is quoted if it is empty or
+// has spaces, like `cmd="show fuo://fake/songs/1"`.
+pub struct Field<'a>(pub &'a str);
+
+impl fmt::Display for Field<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() || self.0.contains(char::is_whitespace) {
+            write!(f, "{:?}", self.0)
+        } else {
+            f.write_str(self.0)
+        }
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let ts = fmt_timestamp(SystemTime::now());
+        let message = record.args().to_string();
+        let line = format!(
+            "{} {:<5} [{}] ({}) {}\n",
+            ts,
+            record.level(),
+            record.target(),
+            thread::current().name().unwrap_or("-"),
+            message
+        );
+        push_recent(Entry {
+            // Only `HH:MM:SS` of the timestamp.
+            time: ts[11..19].to_owned(),
+            level: record.level(),
+            target: record.target().to_owned(),
+            message,
+        });
+        let mut file = self.file.lock().unwrap();
+        if file.size + line.len() as u64 > self.max_size && file.size > 0 {
+            // Keep logging to the current file if rotation fails.
+            let _ = self.rotate(&mut file);
+        }
+        if file.file.write_all(line.as_bytes()).is_ok() {
+            file.size += line.len() as u64;
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self.file.lock().unwrap().file.flush();
+    }
+}
@@ -0,0 +1,405 @@
+use crate::commands::Command;
+use crate::i18n::tr;
+use crate::models::Lyric;
+use crate::paths;
+use crate::rpc::Client;
+use crate::store;
+use crate::uri::FuoUri;
+use log::{error, info};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Error, ErrorKind};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+// The lyric sentence of the playing song.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LyricState {
+    // The song has no lyric, or nothing is known yet.
+    None,
+    // The song has a lyric, but no sentence is being sung, like in interludes.
+    Unsynced,
+    Line(String),
+}
+
+impl LyricState {
+    // The sentence, empty if there is none.
+    pub fn text(&self) -> &str {
+        match self {
+            LyricState::Line(line) => line,
+            LyricState::None | LyricState::Unsynced => "",
+        }
+    }
+}
+
+// Fetch the full lyric of a song.
+pub fn fetch(client: &Client, uri: &FuoUri) -> Result<Lyric, Error> {
+    let resp = client.send_request(Command::Show(format!("{}/lyric", uri)))?;
+    if !resp.ok {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            String::from_utf8_lossy(&resp.body).trim().to_owned(),
+        ));
+    }
+    // Some fuo versions dump the lyric as a plain string.
+    serde_json::from_slice::<Lyric>(&resp.body)
+        .or_else(|_| {
+            serde_json::from_slice::<String>(&resp.body).map(|content| Lyric {
+                content,
+                trans_content: String::new(),
+            })
+        })
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+// Fetched lyrics in `$XDG_CACHE_HOME/fust/lyrics`, a file per song named by
+// the md5 of its uri. Modification times tell which ones are used recently.
+pub struct Cache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl Cache {
+    pub fn new(max_size: u64) -> Cache {
+        Cache {
+            dir: paths::cache_dir().join("lyrics"),
+            max_size,
+        }
+    }
+
+    fn path(&self, uri: &FuoUri) -> PathBuf {
+        let name = format!("{:x}.json", md5::compute(uri.to_string()));
+        self.dir.join(name)
+    }
+
+    pub fn get(&self, uri: &FuoUri) -> Option<Lyric> {
+        let path = self.path(uri);
+        let lyric = serde_json::from_slice(&fs::read(&path).ok()?).ok()?;
+        let _ = File::options()
+            .append(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        Some(lyric)
+    }
+
+    pub fn put(&self, uri: &FuoUri, lyric: &Lyric) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(uri), serde_json::to_vec(lyric)?)?;
+        self.evict()
+    }
+
+    // Remove the least recently used lyrics until they fit in the cap.
+    fn evict(&self) -> io::Result<()> {
+        let mut files = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            files.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+        files.sort();
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        for (_, len, path) in files {
+            if total <= self.max_size {
+                break;
+            }
+            fs::remove_file(&path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+
+    // Remove all cached lyrics and return how many there were.
+    pub fn clear(&self) -> io::Result<usize> {
+        let mut count = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            fs::remove_file(entry?.path())?;
+            count += 1;
+        }
+        info!("cleared {} cached lyrics", count);
+        Ok(count)
+    }
+}
+
+// Fetch the full lyric of a song unless it is cached.
+pub fn fetch_cached(client: &Client, cache: &Cache, uri: &FuoUri) -> Result<Lyric, Error> {
+    if let Some(lyric) = cache.get(uri) {
+        return Ok(lyric);
+    }
+    let lyric = fetch(client, uri)?;
+    // An empty lyric is fetched again next time, the provider may have one
+    // later or the request may have failed quietly.
+    if lyric.content.is_empty() && lyric.trans_content.is_empty() {
+        return Ok(lyric);
+    }
+    if let Err(e) = cache.put(uri, &lyric) {
+        error!("failed to cache the lyric of {}: {}", uri, e);
+    }
+    Ok(lyric)
+}
+
+// Parse a timestamp tag like `01:02.50`.
+fn parse_timestamp(tag: &str) -> Option<Duration> {
+    let (mins, secs) = tag.split_once(':')?;
+    let mins: u64 = mins.trim().parse().ok()?;
+    // Some lyrics use a colon before the fraction, like `01:02:50`.
+    let secs: f64 = secs.trim().replacen(':', ".", 1).parse().ok()?;
+    // Lyrics come from providers, huge numbers are taken as broken tags.
+    let secs = Duration::try_from_secs_f64(secs).ok()?;
+    Duration::from_secs(mins.checked_mul(60)?).checked_add(secs)
+}
+
+// Timestamped lines of a LRC lyric sorted by time. A line can have many
+// timestamps like `[00:12.00][01:30.00]chorus`, and lines without any, like
+// `[ar:artist]`, are left out. An `[offset:500]` tag in milliseconds shows
+// the lines earlier.
+pub fn parse_lrc(content: &str) -> Vec<(Duration, String)> {
+    let mut lines = vec![];
+    let mut offset: i64 = 0;
+    for line in content.lines() {
+        let mut rest = line.trim();
+        if let Some(value) = rest
+            .strip_prefix("[offset:")
+            .and_then(|s| s.strip_suffix(']'))
+        {
+            offset = value.trim().parse().unwrap_or(0);
+            continue;
+        }
+        let mut times = vec![];
+        while let Some((tag, after)) = rest.strip_prefix('[').and_then(|s| s.split_once(']')) {
+            match parse_timestamp(tag) {
+                Some(time) => times.push(time),
+                None => break,
+            }
+            rest = after;
+        }
+        for time in times {
+            lines.push((time, rest.trim().to_owned()));
+        }
+    }
+    if offset != 0 {
+        for (time, _) in lines.iter_mut() {
+            *time = shift(*time, offset.saturating_neg());
+        }
+    }
+    lines.sort_by_key(|(time, _)| *time);
+    lines
+}
+
+// Shift the time later by milliseconds, or earlier if it is negative.
+fn shift(time: Duration, millis: i64) -> Duration {
+    let delta = Duration::from_millis(millis.unsigned_abs());
+    if millis >= 0 {
+        time.saturating_add(delta)
+    } else {
+        time.saturating_sub(delta)
+    }
+}
+
+// Which tracks of lyrics with translations are shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LyricMode {
+    Original,
+    Translation,
+    // The translation follows each line.
+    Bilingual,
+}
+
+impl LyricMode {
+    pub fn next(self) -> LyricMode {
+        match self {
+            LyricMode::Original => LyricMode::Translation,
+            LyricMode::Translation => LyricMode::Bilingual,
+            LyricMode::Bilingual => LyricMode::Original,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LyricMode::Original => tr!("原文"),
+            LyricMode::Translation => tr!("译文"),
+            LyricMode::Bilingual => tr!("双语"),
+        }
+    }
+}
+
+// The line being sung at the position.
+pub fn line_at(lines: &[(Duration, String)], position: Duration) -> LyricState {
+    let i = lines.partition_point(|(time, _)| *time <= position);
+    match i.checked_sub(1).map(|i| &lines[i].1) {
+        Some(line) if !line.is_empty() => LyricState::Line(line.clone()),
+        _ => LyricState::Unsynced,
+    }
+}
+
+const OFFSETS: &str = "lyric_offsets";
+
+// Lyric offsets of songs in milliseconds, remembered in the local store.
+// Positive ones delay lyrics which run early.
+pub struct Offsets(HashMap<String, i64>);
+
+impl Offsets {
+    pub fn load() -> Offsets {
+        Offsets(store::load(OFFSETS))
+    }
+
+    pub fn get(&self, uri: &FuoUri) -> i64 {
+        self.0.get(&uri.to_string()).copied().unwrap_or(0)
+    }
+
+    pub fn set(&mut self, uri: &FuoUri, offset: i64) {
+        if offset == 0 {
+            self.0.remove(&uri.to_string());
+        } else {
+            self.0.insert(uri.to_string(), offset);
+        }
+        store::save(OFFSETS, &self.0);
+    }
+}
+
+// The full lyric of the playing song with its offset, whose lines are mapped
+// from the progress locally. So the lyrics view follows the song between
+// sentences pushed by the server, and so does the adjusted lyric, as pushed
+// ones are sent at the timing of the server.
+pub struct Timed {
+    pub uri: FuoUri,
+    pub offset: i64,
+    // Fetched when it is needed, empty if the lyric has no timestamps.
+    pub lines: Option<Vec<(Duration, String)>>,
+    // Translated lines by their timestamps.
+    translations: HashMap<Duration, String>,
+}
+
+impl Timed {
+    pub fn new(uri: FuoUri, offset: i64) -> Timed {
+        Timed {
+            uri,
+            offset,
+            lines: None,
+            translations: HashMap::new(),
+        }
+    }
+
+    pub fn load(&mut self, lyric: &Lyric) {
+        self.lines = Some(parse_lrc(&lyric.content));
+        self.translations = parse_lrc(&lyric.trans_content)
+            .into_iter()
+            .filter(|(_, line)| !line.is_empty())
+            .collect();
+    }
+
+    pub fn has_translations(&self) -> bool {
+        !self.translations.is_empty()
+    }
+
+    // The translation of the line, which shares its timestamp.
+    pub fn translation(&self, i: usize) -> Option<&str> {
+        let (time, _) = self.lines.as_ref()?.get(i)?;
+        self.translations.get(time).map(String::as_str)
+    }
+
+    // Position in the lyric, which is behind the song if it is delayed.
+    fn position(&self, position: Duration) -> Duration {
+        shift(position, -self.offset)
+    }
+
+    // Index of the line being sung, `None` before the first one.
+    pub fn index_at(&self, position: Duration) -> Option<usize> {
+        let position = self.position(position);
+        let lines = self.lines.as_ref()?;
+        lines
+            .partition_point(|(time, _)| *time <= position)
+            .checked_sub(1)
+    }
+
+    // How much of the line being sung is done, from the time until the next
+    // line, or until the end of the song for the last one.
+    pub fn sung_ratio(&self, position: Duration, duration: Duration) -> f64 {
+        let lines = match self.lines.as_ref() {
+            Some(lines) => lines,
+            None => return 0.0,
+        };
+        let i = match self.index_at(position) {
+            Some(i) => i,
+            None => return 0.0,
+        };
+        let start = lines[i].0;
+        let end = lines.get(i + 1).map_or(duration, |(time, _)| *time);
+        let elapsed = self.position(position).saturating_sub(start);
+        if end <= start {
+            return 1.0;
+        }
+        (elapsed.as_secs_f64() / (end - start).as_secs_f64()).min(1.0)
+    }
+
+    // The line at the position, `None` if there are no timestamps.
+    pub fn line_at(&self, position: Duration) -> Option<LyricState> {
+        let lines = self.lines.as_ref().filter(|lines| !lines.is_empty())?;
+        Some(line_at(lines, self.position(position)))
+    }
+
+    // Progress until the next line, so that it is shown in time.
+    pub fn next_line_in(&self, position: Duration) -> Option<Duration> {
+        let position = self.position(position);
+        let lines = self.lines.as_ref()?;
+        let i = lines.partition_point(|(time, _)| *time <= position);
+        lines.get(i).map(|(time, _)| *time - position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn line(millis: u64, text: &str) -> (Duration, String) {
+        (ms(millis), text.to_owned())
+    }
+
+    #[test]
+    fn lrc() {
+        let lrc = "[ti:Hello]\n\
+                   [ar:Adele]\n\
+                   [00:01.50]Hello, it's me\n\
+                   [00:10.00][01:30.25] chorus \n\
+                   [00:05:20]colon before the fraction\n\
+                   no timestamp\n\
+                   [01:00.00]";
+        assert_eq!(
+            parse_lrc(lrc),
+            vec![
+                line(1500, "Hello, it's me"),
+                line(5200, "colon before the fraction"),
+                line(10000, "chorus"),
+                line(60000, ""),
+                line(90250, "chorus"),
+            ]
+        );
+    }
+
+    #[test]
+    fn lrc_offset() {
+        let lrc = "[offset:500]\n[00:00.20]first\n[00:02.00]second";
+        assert_eq!(parse_lrc(lrc), vec![line(0, "first"), line(1500, "second")]);
+        let lrc = "[offset:-500]\n[00:02.00]second";
+        assert_eq!(parse_lrc(lrc), vec![line(2500, "second")]);
+        // Huge offsets from providers saturate.
+        let lrc = "[offset:-9223372036854775808]\n[00:02.00]second";
+        assert_eq!(parse_lrc(lrc)[0].0, ms(2000) + ms(i64::MAX as u64));
+        let lrc = "[offset:9223372036854775807]\n[00:02.00]second";
+        assert_eq!(parse_lrc(lrc), vec![line(0, "second")]);
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp("01:02.50"), Some(ms(62500)));
+        assert_eq!(parse_timestamp("01:02:50"), Some(ms(62500)));
+        assert_eq!(parse_timestamp(" 1 : 2 "), Some(ms(62000)));
+        assert_eq!(parse_timestamp("ar:Adele"), None);
+        assert_eq!(parse_timestamp("00:-1"), None);
+        assert_eq!(parse_timestamp("18446744073709551615:00"), None);
+        assert_eq!(parse_timestamp("00:1e300"), None);
+    }
+}
@@ -0,0 +1,19 @@
+use crate::models::BriefSong;
+use crate::player::parse_position;
+use std::io::{self, Write};
+
+// Write songs as an extended M3U playlist, with fuo uris as locations.
+pub fn write(w: &mut impl Write, songs: &[BriefSong]) -> io::Result<()> {
+    writeln!(w, "#EXTM3U")?;
+    for song in songs {
+        // -1 means the duration is unknown.
+        let secs = parse_position(&song.duration_ms)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(-1);
+        // Commas are fine in the title, but newlines would break the file.
+        let title = format!("{} - {}", song.artists_name, song.title).replace(['\r', '\n'], " ");
+        writeln!(w, "#EXTINF:{},{}", secs, title)?;
+        writeln!(w, "{}", song.uri())?;
+    }
+    Ok(())
+}
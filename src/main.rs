@@ -1,52 +1,247 @@
 mod app;
+mod bookmarks;
+mod cli;
+mod clock;
+mod colors;
+mod commands;
+mod config;
+#[cfg(unix)]
+mod discord;
+mod event;
+mod glyphs;
+mod headless;
+mod history;
+mod i18n;
+mod inspector;
+#[cfg(unix)]
+mod ipc;
+mod keymap;
+mod logger;
+mod lyric;
+mod m3u;
+#[cfg(all(feature = "mpris", target_os = "linux"))]
+mod media_keys;
 mod models;
+#[cfg(feature = "mpris")]
+mod mpris;
+mod nowplaying;
+mod osc;
+mod page;
+mod paths;
 mod player;
+mod qr;
+mod repl;
 mod rpc;
+mod scrobbler;
+mod search;
+mod session;
+mod smart;
+mod store;
+mod terminal;
 mod ui;
+mod uri;
 use app::App;
-use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
-use log::LevelFilter;
+use clap::Parser;
+use cli::Args;
+use commands::Command;
+use config::{Config, ConfigError, Theme, ThemeSource};
+use crossterm::{event::Event, execute, terminal::SetTitle};
+use event::AppEvent;
+use i18n::Locale;
+use keymap::{Action, Keymap};
+use logger::FileLogger;
 use std::{
+    backtrace::Backtrace,
     error::Error,
-    io,
-    time::{Duration, Instant},
+    io::{self, Write},
+    panic, thread,
 };
 use tui::{
     backend::{Backend, CrosstermBackend},
+    layout::Rect,
     Terminal,
 };
 use ui::ui;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    simple_logging::log_to_file("fust.log", LevelFilter::Info).unwrap();
+    let args = Args::parse();
+
+    // Forward the action instead of opening another TUI.
+    if let Some(action) = &args.send {
+        #[cfg(unix)]
+        let result = ipc::send(action);
+        #[cfg(not(unix))]
+        let result: Result<(), Box<dyn Error>> =
+            Err(format!("can not send {}, --send is only supported on unix", action).into());
+        if let Err(e) = result {
+            eprintln!("fust: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Report config errors before the terminal is taken over.
+    let (config, keymap) = match load_config(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("fust: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let log_file = args.log_file.clone().unwrap_or_else(paths::log_file);
+    if let Err(e) = FileLogger::init(
+        log_file,
+        config
+            .log_filter()?
+            .verbosity(i32::from(args.verbose) - i32::from(args.quiet)),
+        config.log.max_size_kb * 1024,
+        config.log.max_files,
+    ) {
+        eprintln!("fust: {}", e);
+    }
+    set_panic_hook();
+
+    let result = match args.command {
+        None | Some(cli::Command::Tui) => run_tui(config, keymap),
+        Some(cli::Command::Nowplaying { format, follow }) => {
+            headless::nowplaying(&config, &format, follow)
+        }
+        Some(cli::Command::Bar { format, tooltip }) => headless::bar(&config, &format, &tooltip),
+        Some(cli::Command::Pipe) => headless::pipe(&config),
+        Some(cli::Command::Status) => headless::status(&config),
+        Some(cli::Command::Daemon) => run_daemon(config),
+        Some(cli::Command::Repl) => repl::run(&config),
+        Some(cli::Command::Playlist { format }) => headless::playlist(&config, format),
+        Some(cli::Command::Lyric { follow, timestamps }) => {
+            headless::lyric(&config, follow, timestamps)
+        }
+        Some(cli::Command::Play { uri: None }) => headless::control(&config, Command::Resume),
+        Some(cli::Command::Play { uri: Some(uri) }) => {
+            headless::control(&config, Command::Play(uri))
+        }
+        Some(cli::Command::Pause) => headless::control(&config, Command::Pause),
+        Some(cli::Command::Toggle) => headless::control(&config, Command::Toggle),
+        Some(cli::Command::Next) => headless::control(&config, Command::Next),
+        Some(cli::Command::Prev) => headless::control(&config, Command::Previous),
+        Some(cli::Command::Seek { position }) => headless::seek(&config, position),
+    };
+    // Scripts rely on the exit code, e.g. when the server is unreachable.
+    if let Err(e) = result {
+        // The reader of stdout is gone, like `fust playlist | head`.
+        let broken_pipe = e
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe);
+        if !broken_pipe {
+            eprintln!("fust: {}", e);
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+// Load config and apply overrides from command line and environment
+// variables, which are parsed by clap, to it.
+fn load_config(args: &Args) -> Result<(Config, Keymap), ConfigError> {
+    let mut config = match &args.config {
+        Some(path) => Config::load_from(path.clone())?,
+        None => Config::load()?,
+    };
+    config.select_profile(args.profile.as_deref())?;
+    if let Some(server) = &args.server {
+        let (host, port) = cli::parse_server(server).map_err(ConfigError::Invalid)?;
+        config.server.host = host;
+        if let Some(port) = port {
+            // The pubsub port is the next one.
+            if port == u16::MAX {
+                return Err(ConfigError::Invalid(format!(
+                    "port of --server should be less than {}",
+                    u16::MAX
+                )));
+            }
+            config.server.rpc_port = port;
+            config.server.pubsub_port = None;
+        }
+    }
+    if let Some(path) = &args.theme {
+        config.theme = Theme::load_from(path.clone())?;
+        config.theme_source = Some(ThemeSource::ThemeFile(path.clone()));
+    }
+    if args.mini {
+        config.layout.mini = true;
+    }
+    if let Some(level) = &args.log_level {
+        config.log_level = level.clone();
+        config.log_filter()?;
+    }
+    let keymap = Keymap::new(&config.keymap, &config.keys)?;
+    config.theme = config.theme.degrade(config.color_depth());
+    let locale = config.locale.as_deref().and_then(Locale::from_name);
+    i18n::set_locale(locale.unwrap_or_else(Locale::detect));
+    Ok((config, keymap))
+}
 
+// Give the terminal back and stop like other jobs on Ctrl-Z, until the shell
+// continues it with `fg`.
+#[cfg(unix)]
+fn suspend<B: Backend>(
+    terminal: &mut Terminal<B>,
+    guard: &terminal::Guard,
+    app: &mut App,
+) -> io::Result<()> {
+    // SAFETY: raising a signal has no memory safety requirements.
+    guard.suspend(|| unsafe {
+        libc::raise(libc::SIGTSTP);
+    })?;
+    terminal.clear()?;
+    app.on_resumed();
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn suspend<B: Backend>(
+    _terminal: &mut Terminal<B>,
+    _guard: &terminal::Guard,
+    app: &mut App,
+) -> io::Result<()> {
+    app.show_toast(i18n::tr!("不支持挂起"));
+    Ok(())
+}
+
+// Log panics with a backtrace. A panic of the main thread restores the
+// terminal first so that the message is readable, while the ones of
+// background threads are only logged as printing would garble the TUI.
+fn set_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let thread = thread::current();
+        let name = thread.name().unwrap_or("<unnamed>");
+        log::error!("thread '{}' {}\n{}", name, info, Backtrace::force_capture());
+        if name == "main" {
+            terminal::restore();
+        } else if terminal::is_active() {
+            return;
+        }
+        default_hook(info);
+    }));
+}
+
+fn run_tui(config: Config, keymap: Keymap) -> Result<(), Box<dyn Error>> {
     // setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    let guard = terminal::Guard::enter(config.title.enabled)?;
+    let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
     // create app and run it
-    let tick_rate = Duration::from_millis(250);
-    let mut app = App::new();
+    let mut app = App::new(config);
     app.sync_player_status();
-    app.subscribe_msgs();
-    let res = run_app(&mut terminal, app, tick_rate);
-
-    // restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    app.sync_audio_device();
+    app.apply_quality();
+    app.watch_theme();
+    app.start_services();
+    let res = run_app(&mut terminal, &guard, app, keymap);
 
+    drop(guard);
     if let Err(err) = res {
         println!("{:?}", err)
     }
@@ -54,35 +249,103 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+// Run the background services of the TUI without drawing anything.
+fn run_daemon(config: Config) -> Result<(), Box<dyn Error>> {
+    let mut app = App::new(config);
+    app.sync_player_status();
+    app.apply_quality();
+    app.start_services();
+    loop {
+        // Actions sent by `fust --send` are still handled.
+        match app.next_event(app.next_timeout()) {
+            Some(AppEvent::Remote(Action::Quit)) => return Ok(()),
+            Some(AppEvent::Remote(action)) => app.on_action(action),
+            Some(AppEvent::Reconnected) => app.sync_player_status(),
+            _ => {}
+        }
+        app.on_tick();
+    }
+}
+
+// Handle an event of the TUI, `false` if it should quit.
+fn on_event<B: Backend>(
+    terminal: &mut Terminal<B>,
+    guard: &terminal::Guard,
+    app: &mut App,
+    keymap: &mut Keymap,
+    event: AppEvent,
+) -> io::Result<bool> {
+    match event {
+        AppEvent::Input(Event::Key(key)) => {
+            if app.input.is_some() {
+                app.on_input_key(key.code);
+            } else if !app.modals.is_empty() {
+                app.on_modal_key(key.code);
+            } else {
+                match keymap.feed(key, &app.key_contexts()) {
+                    Some(Action::Quit) => return Ok(false),
+                    Some(Action::Suspend) => suspend(terminal, guard, app)?,
+                    Some(Action::Help) => app.open_help(keymap.describe()),
+                    Some(action) => app.on_action(action),
+                    None => {}
+                }
+            }
+        }
+        AppEvent::Input(Event::Mouse(mouse)) => app.on_mouse(mouse),
+        AppEvent::Remote(Action::Quit) => return Ok(false),
+        AppEvent::Remote(Action::Help) => app.open_help(keymap.describe()),
+        AppEvent::Remote(action) => app.on_action(action),
+        AppEvent::Theme(theme) => app.on_theme(theme),
+        AppEvent::Playlist(songs) => app.on_playlist(songs),
+        AppEvent::Songs(title, songs) => app.on_songs(title, songs),
+        AppEvent::Playlists(title, playlists) => app.on_playlists(title, playlists),
+        AppEvent::Comments(uri, comments) => app.on_comments(uri, comments),
+        AppEvent::Liked(uri, liked) => app.on_liked(uri, liked),
+        AppEvent::VideoExited(resume) => app.on_video_exited(resume),
+        AppEvent::AudioDevices(devices) => app.on_audio_devices(devices),
+        AppEvent::Media(uri, media) => app.on_media(uri, media),
+        AppEvent::EnqueueProgress(name, added, total) => {
+            app.on_enqueue_progress(name, added, total)
+        }
+        AppEvent::Enqueued(name, replace, songs) => app.on_enqueued(name, replace, songs),
+        AppEvent::Collection(name, songs) => app.on_collection(name, songs),
+        AppEvent::Login(provider, status) => app.on_login(provider, status),
+        AppEvent::Reconnected => app.on_reconnected(),
+        // Buffers are resized and cleared right away, so that the next frame
+        // is drawn from scratch instead of being diffed against a stale one.
+        AppEvent::Resize(cols, rows) => terminal.resize(Rect::new(0, 0, cols, rows))?,
+        AppEvent::Input(_) | AppEvent::Message => {}
+    }
+    Ok(true)
+}
+
 fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
+    guard: &terminal::Guard,
     mut app: App,
-    tick_rate: Duration,
+    mut keymap: Keymap,
 ) -> io::Result<()> {
-    let mut last_tick = Instant::now();
+    event::spawn_input(app.event_sender());
     loop {
-        terminal.draw(|f| ui(f, &app))?;
-
-        let timeout = tick_rate
-            .checked_sub(last_tick.elapsed())
-            .unwrap_or_else(|| Duration::from_secs(0));
-        if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') => return Ok(()),
-
-                    KeyCode::Char('n') => app.play_next(),
-                    KeyCode::Char('N') => app.play_previous(),
-
-                    KeyCode::Char('p') | KeyCode::Char(' ') => app.toggle_playpause(),
-                    KeyCode::Char('l') => app.sync_current_playlist(),
-                    _ => (),
-                }
-            }
+        terminal.draw(|f| ui(f, &mut app))?;
+        if let Some(title) = app.terminal_title() {
+            execute!(io::stdout(), SetTitle(title))?;
         }
-        if last_tick.elapsed() >= tick_rate {
-            app.on_tick();
-            last_tick = Instant::now();
+        if let Some(seq) = app.song_notification() {
+            let mut stdout = io::stdout();
+            stdout.write_all(seq.as_bytes())?;
+            stdout.flush()?;
+        }
+
+        // Sleep until something happens, or the screen changes by itself.
+        // Events which arrive meanwhile are handled before the next redraw.
+        let mut event = app.next_event(app.next_timeout());
+        while let Some(e) = event {
+            if !on_event(terminal, guard, &mut app, &mut keymap, e)? {
+                return Ok(());
+            }
+            event = app.pending_event();
         }
+        app.on_tick();
     }
 }
@@ -0,0 +1,55 @@
+use crate::app::AppInner;
+use crate::commands::Command;
+use crate::player::PlayerState;
+use crate::rpc::Client;
+use log::{error, info};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use zbus::blocking::{Connection, Proxy};
+
+const APP_NAME: &str = "fust";
+
+// Grab media keys from gnome-settings-daemon in a background thread.
+//
+// Most desktops route media keys to MPRIS players by themselves, while
+// GNOME (and Cinnamon, MATE) only sends them to applications which grab them.
+pub fn grab(inner: Arc<RwLock<AppInner>>, client: Client) {
+    thread::spawn(move || {
+        if let Err(e) = run(inner, client) {
+            error!("media keys: {}", e);
+        }
+    });
+}
+
+fn run(inner: Arc<RwLock<AppInner>>, client: Client) -> zbus::Result<()> {
+    let conn = Connection::session()?;
+    let proxy = Proxy::new(
+        &conn,
+        "org.gnome.SettingsDaemon.MediaKeys",
+        "/org/gnome/SettingsDaemon/MediaKeys",
+        "org.gnome.SettingsDaemon.MediaKeys",
+    )?;
+    let signals = proxy.receive_signal("MediaPlayerKeyPressed")?;
+    // The time is 0 since fust has no window to tell the event time of.
+    proxy.call_method("GrabMediaPlayerKeys", &(APP_NAME, 0u32))?;
+    info!("media keys: grabbed");
+
+    for msg in signals {
+        let (app, key): (String, String) = msg.body().deserialize()?;
+        if app != APP_NAME {
+            continue;
+        }
+        let playing = inner.read().unwrap().state == PlayerState::Playing;
+        let cmd = match key.as_str() {
+            "Play" => Command::Toggle,
+            "Pause" | "Stop" if playing => Command::Toggle,
+            "Next" => Command::Next,
+            "Previous" => Command::Previous,
+            _ => continue,
+        };
+        if let Err(e) = client.send_request(cmd) {
+            error!("media keys: failed to send request: {}", e);
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,6 @@
-use serde::{Deserialize, Serialize};
+use crate::uri::FuoUri;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BriefSong {
@@ -9,3 +11,480 @@ pub struct BriefSong {
     pub artists_name: String,
     pub duration_ms: String,
 }
+
+impl BriefSong {
+    pub fn uri(&self) -> FuoUri {
+        FuoUri::new(&self.provider, "songs", &self.identifier)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BriefArtist {
+    pub provider: String,
+    pub identifier: String,
+    #[serde(default)]
+    pub name: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BriefAlbum {
+    pub provider: String,
+    pub identifier: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub artists_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BriefPlaylist {
+    pub provider: String,
+    pub identifier: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub creator_name: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct User {
+    pub provider: String,
+    pub identifier: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+}
+
+// Lyric of a song, `content` is in LRC format.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Lyric {
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub trans_content: String,
+}
+
+// The MV of a song, from `show <song uri>/mv`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Video {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default, deserialize_with = "lenient")]
+    pub media: Option<Media>,
+}
+
+impl Video {
+    // The stream to play, which is the media if there is one.
+    pub fn stream_url(&self) -> Option<&str> {
+        let url = match &self.media {
+            Some(media) => media.url.as_str(),
+            None => self.url.as_str(),
+        };
+        (!url.is_empty()).then_some(url)
+    }
+}
+
+// Audio output devices listed by mpv, like `pulse/alsa_output.usb-...` with
+// its description.
+#[derive(Debug, Deserialize, Clone)]
+pub struct AudioDevice {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AudioDevices {
+    pub current: String,
+    pub devices: Vec<AudioDevice>,
+}
+
+impl AudioDevices {
+    // The description of the current device, or its name if there is none.
+    pub fn current_description(&self) -> String {
+        self.devices
+            .iter()
+            .find(|device| device.name == self.current && !device.description.is_empty())
+            .map_or_else(|| self.current.clone(), |device| device.description.clone())
+    }
+}
+
+// A comment of a song, from `show <song uri>/hot_comments`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Comment {
+    #[serde(default, deserialize_with = "lenient")]
+    pub user: Option<User>,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub liked_count: u64,
+}
+
+// Media of a song that the player can actually play.
+#[allow(dead_code)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Media {
+    pub url: String,
+    #[serde(default)]
+    pub format: Option<String>,
+    // In kbps.
+    #[serde(default)]
+    pub bitrate: Option<u64>,
+}
+
+impl Media {
+    // The quality of fuo which the bitrate falls in, like `hq 320k`.
+    pub fn quality(&self) -> Option<String> {
+        let format = self.format.as_deref().unwrap_or_default();
+        if ["flac", "ape", "wav"].contains(&format) {
+            return Some(format!("shq {}", format));
+        }
+        let bitrate = self.bitrate.filter(|&bitrate| bitrate > 0)?;
+        let quality = match bitrate {
+            320.. => "hq",
+            192.. => "sq",
+            _ => "lq",
+        };
+        Some(format!("{} {}k", quality, bitrate))
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Song {
+    pub provider: String,
+    pub identifier: String,
+    pub title: String,
+    // In milliseconds.
+    #[serde(default)]
+    pub duration: u64,
+    #[serde(default)]
+    pub artists: Vec<BriefArtist>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub album: Option<BriefAlbum>,
+    #[serde(default)]
+    pub url: String,
+    // The page of the song on the website of the provider.
+    #[serde(default)]
+    pub web_url: String,
+    #[serde(default, deserialize_with = "lenient")]
+    pub media: Option<Media>,
+}
+
+impl BriefArtist {
+    pub fn uri(&self) -> FuoUri {
+        FuoUri::new(&self.provider, "artists", &self.identifier)
+    }
+}
+
+impl BriefAlbum {
+    pub fn uri(&self) -> FuoUri {
+        FuoUri::new(&self.provider, "albums", &self.identifier)
+    }
+}
+
+impl BriefPlaylist {
+    pub fn uri(&self) -> FuoUri {
+        FuoUri::new(&self.provider, "playlists", &self.identifier)
+    }
+}
+
+#[allow(dead_code)]
+impl User {
+    pub fn uri(&self) -> FuoUri {
+        FuoUri::new(&self.provider, "users", &self.identifier)
+    }
+}
+
+#[allow(dead_code)]
+impl Song {
+    pub fn uri(&self) -> FuoUri {
+        FuoUri::new(&self.provider, "songs", &self.identifier)
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration)
+    }
+
+    pub fn artists_name(&self) -> String {
+        self.artists
+            .iter()
+            .map(|artist| artist.name.as_str())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    pub fn to_brief(&self) -> BriefSong {
+        let secs = self.duration / 1000;
+        BriefSong {
+            provider: self.provider.clone(),
+            identifier: self.identifier.clone(),
+            title: self.title.clone(),
+            album_name: self
+                .album
+                .as_ref()
+                .map(|album| album.name.clone())
+                .unwrap_or_default(),
+            artists_name: self.artists_name(),
+            duration_ms: format!("{:0>2}:{:0>2}", secs / 60, secs % 60),
+        }
+    }
+}
+
+// Fields of different types, like `null` when nothing is playing, are taken
+// as missing.
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: serde::de::DeserializeOwned,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(serde_json::from_value(value).ok())
+}
+
+// Response of the `status` command.
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct Status {
+    #[serde(deserialize_with = "lenient")]
+    pub state: Option<String>,
+    #[serde(deserialize_with = "lenient")]
+    pub song: Option<StatusSong>,
+    #[serde(deserialize_with = "lenient")]
+    pub duration: Option<f64>,
+    #[serde(deserialize_with = "lenient")]
+    pub position: Option<f64>,
+    #[serde(deserialize_with = "lenient")]
+    pub repeat: Option<u64>,
+    #[serde(deserialize_with = "lenient")]
+    pub random: Option<u64>,
+    #[serde(deserialize_with = "lenient")]
+    pub volume: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct StatusSong {
+    #[serde(deserialize_with = "lenient")]
+    pub title: Option<String>,
+    #[serde(deserialize_with = "lenient")]
+    pub album_name: Option<String>,
+    #[serde(deserialize_with = "lenient")]
+    pub artists_name: Option<String>,
+    #[serde(deserialize_with = "lenient")]
+    pub provider: Option<String>,
+    #[serde(deserialize_with = "lenient")]
+    pub identifier: Option<String>,
+}
+
+// Songs in a response, which is either a list of songs like the one of
+// `list`, a list of search results of providers like
+// `[{"source": "qq", "songs": [...]}]`, or an object with songs like an album.
+// Songs which can not be parsed are left out.
+pub fn parse_songs(body: &[u8]) -> serde_json::Result<Vec<BriefSong>> {
+    let value: serde_json::Value = serde_json::from_slice(body)?;
+    let mut songs = vec![];
+    collect_songs(value, &mut songs);
+    Ok(songs)
+}
+
+fn collect_songs(value: serde_json::Value, songs: &mut Vec<BriefSong>) {
+    match value {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_songs(item, songs);
+            }
+        }
+        serde_json::Value::Object(mut object) if object.contains_key("songs") => {
+            collect_songs(object.remove("songs").unwrap_or_default(), songs);
+        }
+        value => {
+            let song = serde_json::from_value::<BriefSong>(value.clone())
+                .or_else(|_| serde_json::from_value::<Song>(value).map(|song| song.to_brief()));
+            songs.extend(song.ok());
+        }
+    }
+}
+
+// Output of python code run by `exec`, which is a json string when the
+// response is in json.
+pub fn exec_output(body: &[u8]) -> Vec<u8> {
+    match serde_json::from_slice::<String>(body) {
+        Ok(output) => output.into_bytes(),
+        Err(_) => body.to_vec(),
+    }
+}
+
+// A source of music on the server, like `netease` or `local`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Provider {
+    pub identifier: String,
+    #[serde(default)]
+    pub name: String,
+}
+
+// Providers listed by `show fuo://`, which are objects in json, or lines
+// like `fuo://netease` in plain text.
+pub fn parse_providers(body: &[u8]) -> Vec<Provider> {
+    if let Ok(providers) = serde_json::from_slice::<Vec<Provider>>(body) {
+        return providers;
+    }
+    String::from_utf8_lossy(body)
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim().strip_prefix("fuo://")?;
+            let identifier = line.split(|c: char| c == '/' || c.is_whitespace()).next()?;
+            (!identifier.is_empty()).then(|| Provider {
+                identifier: identifier.to_owned(),
+                name: String::new(),
+            })
+        })
+        .collect()
+}
+
+// The QR code to log in a provider, from `show fuo://<provider>/login/qrcode`.
+#[derive(Debug, Deserialize)]
+pub struct LoginQrcode {
+    pub url: String,
+    // Identifies the QR code when its status is polled.
+    pub key: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LoginState {
+    #[default]
+    Waiting,
+    // Scanned but not confirmed on the phone yet.
+    Scanned,
+    Expired,
+    Ok,
+    #[serde(other)]
+    Unknown,
+}
+
+// Polled from `show fuo://<provider>/login/qrcode/<key>`.
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct LoginStatus {
+    pub state: LoginState,
+    #[serde(deserialize_with = "lenient")]
+    pub user: Option<User>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Captured from `show fuo://netease/songs/1859245776`.
+    const SONG: &str = r#"{
+        "provider": "netease",
+        "identifier": "1859245776",
+        "uri": "fuo://netease/songs/1859245776",
+        "title": "Hello",
+        "duration": 295502,
+        "url": "",
+        "web_url": "https://music.163.com/#/song?id=1859245776",
+        "artists": [
+            {
+                "provider": "netease",
+                "identifier": "46487",
+                "uri": "fuo://netease/artists/46487",
+                "name": "Adele"
+            }
+        ],
+        "album": {
+            "provider": "netease",
+            "identifier": "3377030",
+            "uri": "fuo://netease/albums/3377030",
+            "name": "25",
+            "artists_name": "Adele"
+        },
+        "media": {
+            "url": "http://m701.music.126.net/hello.mp3",
+            "format": "mp3",
+            "bitrate": 320
+        }
+    }"#;
+
+    #[test]
+    fn song() {
+        let song: Song = serde_json::from_str(SONG).unwrap();
+        assert_eq!(song.uri().to_string(), "fuo://netease/songs/1859245776");
+        assert_eq!(song.duration(), Duration::from_millis(295502));
+        assert_eq!(song.artists_name(), "Adele");
+        assert_eq!(
+            song.artists[0].uri().to_string(),
+            "fuo://netease/artists/46487"
+        );
+        let album = song.album.as_ref().unwrap();
+        assert_eq!(album.uri().to_string(), "fuo://netease/albums/3377030");
+        assert_eq!(album.artists_name, "Adele");
+        assert_eq!(song.web_url, "https://music.163.com/#/song?id=1859245776");
+        let media = song.media.as_ref().unwrap();
+        assert_eq!(media.quality().as_deref(), Some("hq 320k"));
+        let brief = song.to_brief();
+        assert_eq!(brief.album_name, "25");
+        assert_eq!(brief.duration_ms, "04:55");
+    }
+
+    // Local songs have only a few fields.
+    #[test]
+    fn song_missing_fields() {
+        let song: Song =
+            serde_json::from_str(r#"{"provider": "local", "identifier": "a1b2", "title": "Demo"}"#)
+                .unwrap();
+        assert_eq!(song.duration, 0);
+        assert!(song.artists.is_empty());
+        assert!(song.album.is_none());
+        assert!(song.media.is_none());
+        assert_eq!(song.to_brief().duration_ms, "00:00");
+    }
+
+    #[test]
+    fn song_null_fields() {
+        let song: Song = serde_json::from_str(
+            r#"{
+                "provider": "netease",
+                "identifier": "1",
+                "title": "Hello",
+                "album": null,
+                "media": null
+            }"#,
+        )
+        .unwrap();
+        assert!(song.album.is_none());
+        assert!(song.media.is_none());
+    }
+
+    // An album or media of an unexpected shape is taken as missing, rather
+    // than failing the whole song.
+    #[test]
+    fn song_malformed_fields() {
+        let song: Song = serde_json::from_str(
+            r#"{
+                "provider": "netease",
+                "identifier": "1",
+                "title": "Hello",
+                "album": "25",
+                "media": {"format": "mp3"}
+            }"#,
+        )
+        .unwrap();
+        assert!(song.album.is_none());
+        assert!(song.media.is_none());
+    }
+
+    #[test]
+    fn song_without_identifier() {
+        assert!(
+            serde_json::from_str::<Song>(r#"{"provider": "netease", "title": "Hello"}"#).is_err()
+        );
+    }
+}
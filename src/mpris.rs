@@ -0,0 +1,301 @@
+use crate::app::AppInner;
+use crate::commands::Command;
+use crate::player::{PlaybackMode, PlayerState};
+use crate::rpc::Client;
+use log::{error, info};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, Value};
+use zbus::{blocking::connection, interface};
+
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.fust";
+
+// The root interface, fust can neither be raised nor quit by others.
+struct MediaPlayer2;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl MediaPlayer2 {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        "fust"
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec![]
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+// The player interface mirrors state of fuo and forwards controls to it.
+struct Player {
+    inner: Arc<RwLock<AppInner>>,
+    client: Client,
+}
+
+impl Player {
+    fn send(&self, cmd: Command) {
+        if let Err(e) = self.client.send_request(cmd) {
+            error!("mpris: failed to send request: {}", e);
+        }
+    }
+
+    fn seek_to(&self, position: Duration) {
+        self.send(Command::Seek(position));
+    }
+}
+
+// Track id must be a valid object path, so keep only safe chars of the uri.
+fn track_id(uri: &str) -> OwnedObjectPath {
+    let id: String = uri
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let path = format!("/org/feeluown/fust/track/{}", id);
+    ObjectPath::try_from(path)
+        .map(OwnedObjectPath::from)
+        .unwrap_or_else(|_| {
+            ObjectPath::from_static_str_unchecked("/org/mpris/MediaPlayer2/TrackList/NoTrack")
+                .into()
+        })
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn next(&self) {
+        self.send(Command::Next);
+    }
+
+    fn previous(&self) {
+        self.send(Command::Previous);
+    }
+
+    fn pause(&self) {
+        if self.inner.read().unwrap().state == PlayerState::Playing {
+            self.send(Command::Toggle);
+        }
+    }
+
+    fn play_pause(&self) {
+        self.send(Command::Toggle);
+    }
+
+    fn stop(&self) {
+        self.pause();
+    }
+
+    fn play(&self) {
+        if self.inner.read().unwrap().state != PlayerState::Playing {
+            self.send(Command::Toggle);
+        }
+    }
+
+    // Offset is in microseconds.
+    fn seek(&self, offset: i64) {
+        let (position, duration) = {
+            let inner = self.inner.read().unwrap();
+            (inner.progress.current(), inner.duration)
+        };
+        let position = (position.as_micros() as i64 + offset).max(0) as u64;
+        let position = Duration::from_micros(position).min(duration);
+        self.seek_to(position);
+    }
+
+    fn set_position(&self, _track_id: ObjectPath<'_>, position: i64) {
+        if position >= 0 {
+            self.seek_to(Duration::from_micros(position as u64));
+        }
+    }
+
+    fn open_uri(&self, _uri: &str) {}
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &str {
+        match self.inner.read().unwrap().state {
+            PlayerState::Playing => "Playing",
+            PlayerState::Paused => "Paused",
+            PlayerState::Stopped => "Stopped",
+        }
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> &str {
+        match self.inner.read().unwrap().playback_mode {
+            PlaybackMode::OneLoop => "Track",
+            PlaybackMode::Loop => "Playlist",
+            PlaybackMode::Sequential | PlaybackMode::Random => "None",
+        }
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.inner.read().unwrap().playback_mode == PlaybackMode::Random
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        self.inner.read().unwrap().playback_rate
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<&str, Value<'_>> {
+        let inner = self.inner.read().unwrap();
+        let uri = match &inner.current_song_uri {
+            Some(uri) => uri.to_string(),
+            None => inner.metadata.title.clone(),
+        };
+        let mut metadata = HashMap::new();
+        metadata.insert("mpris:trackid", Value::from(track_id(&uri)));
+        metadata.insert(
+            "mpris:length",
+            Value::from(inner.duration.as_micros() as i64),
+        );
+        metadata.insert("xesam:title", Value::from(inner.metadata.title.clone()));
+        metadata.insert("xesam:artist", Value::from(inner.metadata.artists.clone()));
+        if let Some(album) = &inner.metadata.album {
+            metadata.insert("xesam:album", Value::from(album.clone()));
+        }
+        metadata
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        self.inner.read().unwrap().progress.current().as_micros() as i64
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        0.25
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        4.0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+// What is exposed by properties, used to find out which ones changed.
+#[derive(PartialEq, Clone)]
+struct Snapshot {
+    state: PlayerState,
+    playback_mode: PlaybackMode,
+    title: String,
+    duration: Duration,
+}
+
+impl Snapshot {
+    fn new(inner: &AppInner) -> Snapshot {
+        Snapshot {
+            state: inner.state,
+            playback_mode: inner.playback_mode,
+            title: inner.metadata.title.clone(),
+            duration: inner.duration,
+        }
+    }
+}
+
+// Serve MPRIS on the session bus in a background thread.
+pub fn serve(inner: Arc<RwLock<AppInner>>, client: Client) {
+    thread::spawn(move || {
+        if let Err(e) = run(inner, client) {
+            error!("mpris: {}", e);
+        }
+    });
+}
+
+fn run(inner: Arc<RwLock<AppInner>>, client: Client) -> zbus::Result<()> {
+    let player = Player {
+        inner: inner.clone(),
+        client,
+    };
+    let conn = connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, MediaPlayer2)?
+        .serve_at(OBJECT_PATH, player)?
+        .build()?;
+    info!("mpris: serving as {}", BUS_NAME);
+
+    // Properties are read from the shared state on demand, so only
+    // changes need to be announced.
+    let iface_ref = conn.object_server().interface::<_, Player>(OBJECT_PATH)?;
+    let mut last = Snapshot::new(&inner.read().unwrap());
+    loop {
+        thread::sleep(Duration::from_millis(500));
+        let current = Snapshot::new(&inner.read().unwrap());
+        if current == last {
+            continue;
+        }
+        let iface = iface_ref.get();
+        let ctxt = iface_ref.signal_context();
+        zbus::block_on(async {
+            if current.state != last.state {
+                iface.playback_status_changed(ctxt).await?;
+            }
+            if current.playback_mode != last.playback_mode {
+                iface.loop_status_changed(ctxt).await?;
+                iface.shuffle_changed(ctxt).await?;
+            }
+            if current.title != last.title || current.duration != last.duration {
+                iface.metadata_changed(ctxt).await?;
+            }
+            Ok::<(), zbus::Error>(())
+        })?;
+        last = current;
+    }
+}
@@ -0,0 +1,68 @@
+use crate::app::AppInner;
+use crate::player::{fmt_duration, PlayerState};
+use std::time::Duration;
+
+// The playing song, rendered by format strings like `{title} - {artists}`.
+pub struct NowPlaying {
+    pub state: PlayerState,
+    pub title: String,
+    pub artists: String,
+    pub album: String,
+    pub position: Duration,
+    pub duration: Duration,
+}
+
+impl NowPlaying {
+    pub fn new(inner: &AppInner) -> NowPlaying {
+        NowPlaying {
+            state: inner.state,
+            title: inner.metadata.title.clone(),
+            artists: inner.metadata.artists.join(", "),
+            album: inner.metadata.album.clone().unwrap_or_default(),
+            position: inner.progress.current(),
+            duration: inner.duration,
+        }
+    }
+
+    fn state_symbol(&self) -> &'static str {
+        match self.state {
+            PlayerState::Playing => "▶",
+            PlayerState::Paused => "⏸",
+            PlayerState::Stopped => "■",
+        }
+    }
+
+    // Replace placeholders, unknown ones are kept as they are.
+    pub fn render(&self, format: &str) -> String {
+        let mut out = String::new();
+        let mut rest = format;
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = match rest.find('}') {
+                Some(end) => end,
+                None => break,
+            };
+            match self.field(&rest[1..end]) {
+                Some(value) => out.push_str(&value),
+                None => out.push_str(&rest[..=end]),
+            }
+            rest = &rest[end + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "state" => self.state_symbol().to_owned(),
+            "title" => self.title.clone(),
+            "artists" => self.artists.clone(),
+            "album" => self.album.clone(),
+            "position" => fmt_duration(self.position),
+            "duration" => fmt_duration(self.duration),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
@@ -0,0 +1,32 @@
+use std::env;
+
+// Strip control chars which would end the escape sequence early.
+fn sanitize(s: &str) -> String {
+    s.chars().filter(|c| !c.is_control()).collect()
+}
+
+// tmux swallows unknown sequences unless they are wrapped for passthrough,
+// which also requires `set -g allow-passthrough on`.
+fn passthrough(seq: String) -> String {
+    if env::var_os("TMUX").is_some() {
+        format!("\x1bPtmux;{}\x1b\\", seq.replace('\x1b', "\x1b\x1b"))
+    } else {
+        seq
+    }
+}
+
+// A desktop notification sent by the terminal, OSC 777 is supported by
+// foot, wezterm and urxvt, while OSC 9 is supported by kitty, iTerm2 and
+// Windows Terminal, which only show the body.
+pub fn notification(protocol: &str, title: &str, body: &str) -> String {
+    let seq = match protocol {
+        "osc9" => format!("\x1b]9;{}: {}\x1b\\", sanitize(title), sanitize(body)),
+        // Fields of OSC 777 are separated by `;`.
+        _ => format!(
+            "\x1b]777;notify;{};{}\x1b\\",
+            sanitize(title).replace(';', ","),
+            sanitize(body)
+        ),
+    };
+    passthrough(seq)
+}
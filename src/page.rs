@@ -0,0 +1,255 @@
+use crate::history::Stats;
+use crate::i18n::tr;
+use crate::models::{BriefPlaylist, BriefSong, LoginStatus, Provider};
+use crate::qr::QrCode;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Pages shown instead of the playlist, the last one of `App::pages` is on
+// top and `back` returns to the one below.
+pub enum Page {
+    Songs(SongsPage),
+    Playlists(PlaylistsPage),
+    Collections(CollectionsPage),
+    Providers(ProvidersPage),
+    Login(LoginPage),
+    Stats(StatsPage),
+}
+
+impl Page {
+    // Move the selection down, or up if it is negative.
+    pub fn select(&mut self, offset: isize) {
+        match self {
+            Page::Songs(page) => page.cursor.move_by(offset, page.songs.len()),
+            Page::Playlists(page) => page.cursor.move_by(offset, page.playlists.len()),
+            Page::Collections(page) => page.cursor.move_by(offset, page.len()),
+            Page::Providers(page) => page.cursor.move_by(offset, page.providers.len()),
+            Page::Login(_) | Page::Stats(_) => {}
+        }
+    }
+
+    pub fn selected_song(&self) -> Option<&BriefSong> {
+        match self {
+            Page::Songs(page) => page.songs.get(page.cursor.selected),
+            _ => None,
+        }
+    }
+}
+
+// The selected row of a list, and the first row shown on the screen.
+#[derive(Default)]
+pub struct Cursor {
+    pub selected: usize,
+    pub offset: usize,
+}
+
+impl Cursor {
+    fn move_by(&mut self, offset: isize, len: usize) {
+        self.selected = self
+            .selected
+            .saturating_add_signed(offset)
+            .min(len.saturating_sub(1));
+    }
+
+    // Scroll so that the selected row is among the `height` rows shown, the
+    // same way as `TableState` does.
+    pub fn scroll(&mut self, height: usize, len: usize) -> usize {
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+        self.offset = self.offset.min(len.saturating_sub(height));
+        self.offset
+    }
+}
+
+// Songs fetched from the server, like search results.
+pub struct SongsPage {
+    pub title: String,
+    pub songs: Vec<BriefSong>,
+    pub cursor: Cursor,
+    // Name of the collection which the songs are of, where they can be
+    // removed from.
+    pub collection: Option<String>,
+    // The songs are the local bookmarks, which are shown again when they
+    // change.
+    pub bookmarks: bool,
+    // Indices of the songs marked to be added together.
+    pub marked: BTreeSet<usize>,
+    // When the songs were played, formatted once the history is loaded, in
+    // the page of the history.
+    pub played_at: Option<Vec<String>>,
+}
+
+impl SongsPage {
+    pub fn new(title: String, songs: Vec<BriefSong>) -> SongsPage {
+        SongsPage {
+            title,
+            songs,
+            cursor: Cursor::default(),
+            collection: None,
+            bookmarks: false,
+            marked: BTreeSet::new(),
+            played_at: None,
+        }
+    }
+
+    // The marked songs in order, or all of them if none is marked.
+    pub fn chosen_songs(&self) -> Vec<BriefSong> {
+        if self.marked.is_empty() {
+            return self.songs.clone();
+        }
+        self.marked
+            .iter()
+            .filter_map(|&i| self.songs.get(i).cloned())
+            .collect()
+    }
+}
+
+// Local collections of fuo, followed by smart playlists in the config.
+pub struct CollectionsPage {
+    pub names: Vec<String>,
+    pub smart: Vec<String>,
+    pub cursor: Cursor,
+}
+
+pub enum LibraryItem<'a> {
+    Collection(&'a String),
+    // The index of the smart playlist.
+    Smart(usize),
+}
+
+impl CollectionsPage {
+    pub fn new(names: Vec<String>, smart: Vec<String>) -> CollectionsPage {
+        CollectionsPage {
+            names,
+            smart,
+            cursor: Cursor::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len() + self.smart.len()
+    }
+
+    pub fn selected(&self) -> Option<LibraryItem<'_>> {
+        let i = self.cursor.selected;
+        match self.names.get(i) {
+            Some(name) => Some(LibraryItem::Collection(name)),
+            None => (i - self.names.len() < self.smart.len())
+                .then(|| LibraryItem::Smart(i - self.names.len())),
+        }
+    }
+}
+
+// Playlists fetched from the server, whose songs are opened in a page.
+pub struct PlaylistsPage {
+    pub title: String,
+    pub playlists: Vec<BriefPlaylist>,
+    pub cursor: Cursor,
+}
+
+impl PlaylistsPage {
+    pub fn new(title: String, playlists: Vec<BriefPlaylist>) -> PlaylistsPage {
+        PlaylistsPage {
+            title,
+            playlists,
+            cursor: Cursor::default(),
+        }
+    }
+
+    pub fn selected(&self) -> Option<&BriefPlaylist> {
+        self.playlists.get(self.cursor.selected)
+    }
+}
+
+pub struct ProviderItem {
+    pub provider: Provider,
+    // Name of the user who logged in from fust.
+    pub user: Option<String>,
+}
+
+// Providers of the server, where logging in starts.
+pub struct ProvidersPage {
+    pub providers: Vec<ProviderItem>,
+    pub cursor: Cursor,
+}
+
+impl ProvidersPage {
+    pub fn new(providers: Vec<Provider>) -> ProvidersPage {
+        ProvidersPage {
+            providers: providers
+                .into_iter()
+                .map(|provider| ProviderItem {
+                    provider,
+                    user: None,
+                })
+                .collect(),
+            cursor: Cursor::default(),
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Provider> {
+        self.providers
+            .get(self.cursor.selected)
+            .map(|item| &item.provider)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum StatsRange {
+    Week,
+    Month,
+    All,
+}
+
+impl StatsRange {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatsRange::Week => tr!("最近 7 天"),
+            StatsRange::Month => tr!("最近 30 天"),
+            StatsRange::All => tr!("全部"),
+        }
+    }
+
+    pub fn next(self) -> StatsRange {
+        match self {
+            StatsRange::Week => StatsRange::Month,
+            StatsRange::Month => StatsRange::All,
+            StatsRange::All => StatsRange::Week,
+        }
+    }
+
+    // Unix time of the start of the range.
+    pub fn since(&self, now: u64) -> u64 {
+        const DAY: u64 = 24 * 60 * 60;
+        match self {
+            StatsRange::Week => now.saturating_sub(7 * DAY),
+            StatsRange::Month => now.saturating_sub(30 * DAY),
+            StatsRange::All => 0,
+        }
+    }
+}
+
+// Statistics of the local history of plays.
+pub struct StatsPage {
+    pub range: StatsRange,
+    pub stats: Stats,
+}
+
+// Logging in a provider by scanning the QR code with its phone app.
+pub struct LoginPage {
+    pub provider: String,
+    pub qr: Option<QrCode>,
+    pub status: LoginStatus,
+    // Tell the thread which polls the status to stop.
+    pub cancelled: Arc<AtomicBool>,
+}
+
+impl Drop for LoginPage {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,68 @@
+use directories::{BaseDirs, ProjectDirs};
+use log::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Directories of fust, which follow the XDG base directory spec on Linux,
+// and the platform conventions on macOS and Windows.
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "fust")
+}
+
+// Create the directory if it does not exist.
+fn ensure_dir(dir: PathBuf) -> PathBuf {
+    if let Err(e) = fs::create_dir_all(&dir) {
+        error!("failed to create {}: {}", dir.display(), e);
+    }
+    dir
+}
+
+// Fallback to the working directory when there is no home directory.
+fn or_cwd(dir: Option<&Path>) -> PathBuf {
+    dir.map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+// `$XDG_CONFIG_HOME/fust`, which is not created since fust never writes to it.
+pub fn config_dir() -> PathBuf {
+    or_cwd(project_dirs().as_ref().map(ProjectDirs::config_dir))
+}
+
+// `$XDG_DATA_HOME/fust`, for logs, history and other persistent states.
+pub fn data_dir() -> PathBuf {
+    ensure_dir(or_cwd(project_dirs().as_ref().map(ProjectDirs::data_dir)))
+}
+
+// `$XDG_CACHE_HOME/fust`, for things which can be fetched again.
+pub fn cache_dir() -> PathBuf {
+    ensure_dir(or_cwd(project_dirs().as_ref().map(ProjectDirs::cache_dir)))
+}
+
+pub fn config_file() -> PathBuf {
+    config_dir().join("config.toml")
+}
+
+pub fn log_file() -> PathBuf {
+    data_dir().join("fust.log")
+}
+
+// `$XDG_RUNTIME_DIR/fust/fust.sock`, which the running TUI listens on, or
+// `fust.sock` in the data dir if there is no runtime dir.
+#[cfg(unix)]
+pub fn socket_file() -> PathBuf {
+    match project_dirs().as_ref().and_then(ProjectDirs::runtime_dir) {
+        Some(dir) => ensure_dir(dir.to_path_buf()).join("fust.sock"),
+        None => data_dir().join("fust.sock"),
+    }
+}
+
+// Expand a leading `~` of paths typed by the user.
+pub fn expand_home(path: &str) -> PathBuf {
+    let home = BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            home.join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
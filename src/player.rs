@@ -1,7 +1,8 @@
+use crate::i18n::tr;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use std::convert::TryFrom;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant};
 
 #[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug, Copy, Clone)]
 #[repr(u64)]
@@ -11,6 +12,25 @@ pub enum PlayerState {
     Playing = 2,
 }
 
+impl PlayerState {
+    pub fn name(self) -> &'static str {
+        match self {
+            PlayerState::Stopped => "stopped",
+            PlayerState::Paused => "paused",
+            PlayerState::Playing => "playing",
+        }
+    }
+
+    // The name shown to the user.
+    pub fn label(self) -> &'static str {
+        match self {
+            PlayerState::Stopped => tr!("已停止"),
+            PlayerState::Paused => tr!("已暂停"),
+            PlayerState::Playing => tr!("播放中"),
+        }
+    }
+}
+
 impl TryFrom<u64> for PlayerState {
     type Error = ();
 
@@ -29,6 +49,8 @@ pub struct PlayerMetadata {
     pub title: String,
     pub artists: Vec<String>,
     pub album: Option<String>,
+    #[serde(default)]
+    pub uri: Option<String>,
 }
 
 impl PlayerMetadata {
@@ -37,54 +59,188 @@ impl PlayerMetadata {
             title: "".to_owned(),
             artists: vec!["".to_owned()],
             album: Some("".to_owned()),
+            uri: None,
         }
     }
 }
 
+// Format a position as `mm:ss`, or `hh:mm:ss` if it is longer than an hour.
+pub fn fmt_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs() % 60;
+    let minutes = (duration.as_secs() / 60) % 60;
+    let hours = (duration.as_secs() / 60) / 60;
+    if hours > 0 {
+        format!("{:0>2}:{:0>2}:{:0>2}", hours, minutes, seconds)
+    } else {
+        format!("{:0>2}:{:0>2}", minutes, seconds)
+    }
+}
+
+// Parse a position like `ss`, `mm:ss` or `hh:mm:ss`.
+pub fn parse_position(s: &str) -> Option<Duration> {
+    let mut secs: u64 = 0;
+    let parts: Vec<&str> = s.trim().split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    for (i, part) in parts.iter().enumerate() {
+        let n: u64 = part.parse().ok()?;
+        // Only the leading part may exceed 59, like `90:00`.
+        if i > 0 && n >= 60 {
+            return None;
+        }
+        secs = secs.checked_mul(60)?.checked_add(n)?;
+    }
+    Some(Duration::from_secs(secs))
+}
+
 pub struct Progress {
-    ts: SystemTime,
+    ts: Instant,
     position: Duration,
 
     paused: bool,
-    paused_ts: SystemTime,
+    paused_ts: Instant,
+
+    // Playback rate, 1.0 means normal speed.
+    rate: f64,
 }
 
 impl Default for Progress {
     fn default() -> Progress {
-        let now = SystemTime::now();
+        let now = Instant::now();
         Progress {
             ts: now,
             position: Duration::new(0, 0),
             paused_ts: now,
             paused: false,
+            rate: 1.0,
         }
     }
 }
 
 impl Progress {
     pub fn on_seeked(&mut self, position: Duration) {
-        self.ts = SystemTime::now();
+        self.ts = Instant::now();
         self.position = position;
     }
 
     pub fn pause(&mut self) {
         self.position = self.current();
-        self.paused_ts = SystemTime::now();
+        self.paused_ts = Instant::now();
         self.ts = self.paused_ts;
         self.paused = true;
     }
 
     pub fn resume(&mut self) {
         self.position = self.current();
-        self.ts = SystemTime::now();
+        self.ts = Instant::now();
         self.paused = false;
     }
 
+    pub fn set_rate(&mut self, rate: f64) {
+        self.position = self.current();
+        self.ts = Instant::now();
+        self.rate = rate;
+    }
+
     pub fn current(&self) -> Duration {
         if self.paused {
             self.position
         } else {
-            self.position + self.ts.elapsed().unwrap()
+            self.position + self.ts.elapsed().mul_f64(self.rate)
+        }
+    }
+}
+
+// Values are the same as fuo's `PlaybackMode`.
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug, Copy, Clone)]
+#[repr(u64)]
+pub enum PlaybackMode {
+    OneLoop = 0,
+    Sequential = 1,
+    Loop = 2,
+    Random = 3,
+}
+
+impl TryFrom<u64> for PlaybackMode {
+    type Error = ();
+
+    fn try_from(v: u64) -> Result<Self, Self::Error> {
+        match v {
+            x if x == PlaybackMode::OneLoop as u64 => Ok(PlaybackMode::OneLoop),
+            x if x == PlaybackMode::Sequential as u64 => Ok(PlaybackMode::Sequential),
+            x if x == PlaybackMode::Loop as u64 => Ok(PlaybackMode::Loop),
+            x if x == PlaybackMode::Random as u64 => Ok(PlaybackMode::Random),
+            _ => Err(()),
+        }
+    }
+}
+
+impl PlaybackMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            PlaybackMode::OneLoop => tr!("单曲循环"),
+            PlaybackMode::Sequential => tr!("顺序播放"),
+            PlaybackMode::Loop => tr!("列表循环"),
+            PlaybackMode::Random => tr!("随机播放"),
         }
     }
+
+    // Repeat cycles sequential -> loop -> one_loop -> sequential.
+    pub fn next_repeat(self) -> PlaybackMode {
+        match self {
+            PlaybackMode::Sequential | PlaybackMode::Random => PlaybackMode::Loop,
+            PlaybackMode::Loop => PlaybackMode::OneLoop,
+            PlaybackMode::OneLoop => PlaybackMode::Sequential,
+        }
+    }
+
+    pub fn toggle_random(self) -> PlaybackMode {
+        match self {
+            PlaybackMode::Random => PlaybackMode::Sequential,
+            _ => PlaybackMode::Random,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PlaybackMode::OneLoop => "one_loop",
+            PlaybackMode::Sequential => "sequential",
+            PlaybackMode::Loop => "loop",
+            PlaybackMode::Random => "random",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        assert_eq!(parse_position("42"), Some(Duration::from_secs(42)));
+        assert_eq!(parse_position(" 1:30 "), Some(Duration::from_secs(90)));
+        assert_eq!(parse_position("01:02:03"), Some(Duration::from_secs(3723)));
+        // The leading part may exceed 59.
+        assert_eq!(parse_position("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_position("90:00"), Some(Duration::from_secs(5400)));
+    }
+
+    #[test]
+    fn invalid_positions() {
+        for s in [
+            "", "1:", ":30", "1:60", "1:00:60", "1:2:3:4", "-1", "1.5", "a:b",
+        ] {
+            assert_eq!(parse_position(s), None, "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn overflowing_positions() {
+        assert_eq!(parse_position("999999999999999999:00"), None);
+        assert_eq!(parse_position("99999999999999999999"), None);
+        let max = u64::MAX.to_string();
+        assert_eq!(parse_position(&max), Some(Duration::from_secs(u64::MAX)));
+        assert_eq!(parse_position(&format!("{}:00", max)), None);
+    }
 }
@@ -0,0 +1,396 @@
+// A minimal QR code encoder for login urls: byte mode, error correction
+// level L and versions 1 to 10, which hold up to 271 bytes.
+
+// Total codewords, error correction codewords per block, and the data
+// codewords of blocks, of versions 1 to 10 at level L.
+const VERSIONS: [(usize, usize, &[usize]); 10] = [
+    (26, 7, &[19]),
+    (44, 10, &[34]),
+    (70, 15, &[55]),
+    (100, 20, &[80]),
+    (134, 26, &[108]),
+    (172, 18, &[68, 68]),
+    (196, 20, &[78, 78]),
+    (242, 24, &[97, 97]),
+    (292, 30, &[116, 116]),
+    (346, 18, &[68, 68, 69, 69]),
+];
+
+// Centers of alignment patterns of versions 1 to 10.
+const ALIGNMENTS: [&[usize]; 10] = [
+    &[],
+    &[6, 18],
+    &[6, 22],
+    &[6, 26],
+    &[6, 30],
+    &[6, 34],
+    &[6, 22, 38],
+    &[6, 24, 42],
+    &[6, 26, 46],
+    &[6, 28, 50],
+];
+
+pub struct QrCode {
+    size: usize,
+    // Dark modules by rows.
+    modules: Vec<Vec<bool>>,
+}
+
+impl QrCode {
+    // Encode the bytes, `None` if they are too long.
+    pub fn encode(data: &[u8]) -> Option<QrCode> {
+        let version = (1..=10).find(|&v| {
+            let count_bits = if v < 10 { 8 } else { 16 };
+            let data_codewords: usize = VERSIONS[v - 1].2.iter().sum();
+            4 + count_bits + data.len() * 8 <= data_codewords * 8
+        })?;
+        let codewords = add_ecc(version, &data_codewords(version, data));
+        let mut code = QrCode::new(version);
+        code.draw_codewords(&codewords);
+        // Use the mask which is the easiest to scan.
+        let mask = (0..8)
+            .min_by_key(|&mask| {
+                let mut masked = code.clone_with(version, mask);
+                masked.draw_format(mask);
+                masked.penalty()
+            })
+            .unwrap_or(0);
+        let mut code = code.clone_with(version, mask);
+        code.draw_format(mask);
+        Some(code)
+    }
+
+    fn new(version: usize) -> QrCode {
+        let size = version * 4 + 17;
+        let mut code = QrCode {
+            size,
+            modules: vec![vec![false; size]; size],
+        };
+        code.draw_function_patterns(version);
+        code
+    }
+
+    // Whether the module is a part of the patterns instead of the data.
+    fn is_function(&self, version: usize, x: usize, y: usize) -> bool {
+        let size = self.size;
+        // Finders, separators and format info.
+        if (x < 9 && (y < 9 || y >= size - 8)) || (x >= size - 8 && y < 9) {
+            return true;
+        }
+        if x == 6 || y == 6 {
+            return true;
+        }
+        // Version info.
+        if version >= 7 && ((x >= size - 11 && y < 6) || (y >= size - 11 && x < 6)) {
+            return true;
+        }
+        alignment_centers(version).any(|(cx, cy)| x.abs_diff(cx) <= 2 && y.abs_diff(cy) <= 2)
+    }
+
+    fn draw_function_patterns(&mut self, version: usize) {
+        let size = self.size;
+        for i in 0..size {
+            self.modules[6][i] = i % 2 == 0;
+            self.modules[i][6] = i % 2 == 0;
+        }
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4i32..=4 {
+                for dx in -4i32..=4 {
+                    let (x, y) = (cx as i32 + dx, cy as i32 + dy);
+                    if x < 0 || y < 0 || x >= size as i32 || y >= size as i32 {
+                        continue;
+                    }
+                    let dist = dx.abs().max(dy.abs());
+                    self.modules[y as usize][x as usize] = dist != 2 && dist != 4;
+                }
+            }
+        }
+        for (cx, cy) in alignment_centers(version) {
+            for dy in -2i32..=2 {
+                for dx in -2i32..=2 {
+                    let (x, y) = ((cx as i32 + dx) as usize, (cy as i32 + dy) as usize);
+                    self.modules[y][x] = dx.abs().max(dy.abs()) != 1;
+                }
+            }
+        }
+        if version >= 7 {
+            let mut rem = version;
+            for _ in 0..12 {
+                rem = (rem << 1) ^ ((rem >> 11) * 0x1f25);
+            }
+            let bits = version << 12 | rem;
+            for i in 0..18 {
+                let bit = (bits >> i) & 1 != 0;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                self.modules[b][a] = bit;
+                self.modules[a][b] = bit;
+            }
+        }
+        // The dark module.
+        self.modules[size - 8][8] = true;
+    }
+
+    // Place the bits in the zigzag order, from the bottom right corner.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let version = (self.size - 17) / 4;
+        let size = self.size;
+        let mut i = 0;
+        let mut right = size - 1;
+        loop {
+            if right == 6 {
+                right = 5;
+            }
+            for vert in 0..size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward { size - 1 - vert } else { vert };
+                    if !self.is_function(version, x, y) && i < codewords.len() * 8 {
+                        self.modules[y][x] = (codewords[i >> 3] >> (7 - (i & 7))) & 1 != 0;
+                        i += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    fn clone_with(&self, version: usize, mask: u8) -> QrCode {
+        let mut modules = self.modules.clone();
+        for (y, row) in modules.iter_mut().enumerate() {
+            for (x, module) in row.iter_mut().enumerate() {
+                if !self.is_function(version, x, y) && is_masked(mask, x, y) {
+                    *module = !*module;
+                }
+            }
+        }
+        QrCode {
+            size: self.size,
+            modules,
+        }
+    }
+
+    fn draw_format(&mut self, mask: u8) {
+        // Level L is 0b01.
+        let data = (1 << 3 | mask) as u32;
+        let mut rem = data;
+        for _ in 0..10 {
+            rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+        }
+        let bits = (data << 10 | rem) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 != 0;
+        let size = self.size;
+        let mut set = |x: usize, y: usize, dark: bool| self.modules[y][x] = dark;
+        for i in 0..6 {
+            set(8, i, bit(i));
+        }
+        set(8, 7, bit(6));
+        set(8, 8, bit(7));
+        set(7, 8, bit(8));
+        for i in 9..15 {
+            set(14 - i, 8, bit(i));
+        }
+        for i in 0..8 {
+            set(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            set(8, size - 15 + i, bit(i));
+        }
+        set(8, size - 8, true);
+    }
+
+    // Penalty of patterns which confuse scanners, lower is better.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let mut penalty = 0;
+        let rows = (0..size).map(|y| (0..size).map(|x| self.modules[y][x]).collect::<Vec<_>>());
+        let cols = (0..size).map(|x| (0..size).map(|y| self.modules[y][x]).collect::<Vec<_>>());
+        const FINDER: [bool; 11] = [
+            true, false, true, true, true, false, true, false, false, false, false,
+        ];
+        for line in rows.chain(cols) {
+            // Runs of the same color.
+            let mut run = 1;
+            for i in 1..=size {
+                if i < size && line[i] == line[i - 1] {
+                    run += 1;
+                    continue;
+                }
+                if run >= 5 {
+                    penalty += run - 2;
+                }
+                run = 1;
+            }
+            // Patterns which look like finders.
+            for window in line.windows(11) {
+                if window == FINDER || window.iter().rev().eq(FINDER.iter()) {
+                    penalty += 40;
+                }
+            }
+        }
+        // Blocks of the same color.
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let c = self.modules[y][x];
+                if self.modules[y][x + 1] == c
+                    && self.modules[y + 1][x] == c
+                    && self.modules[y + 1][x + 1] == c
+                {
+                    penalty += 3;
+                }
+            }
+        }
+        // Balance of dark and light modules.
+        let dark = self.modules.iter().flatten().filter(|&&m| m).count();
+        let percent = dark * 100 / (size * size);
+        penalty + percent.abs_diff(50) / 5 * 10
+    }
+
+    // Lines of half blocks, each of which is two rows of modules, with a
+    // quiet zone around. Light modules are drawn so that it scans on dark
+    // terminals, the caller should draw them light on dark.
+    pub fn to_lines(&self) -> Vec<String> {
+        const QUIET: usize = 2;
+        let size = self.size as isize;
+        let light = |x: isize, y: isize| {
+            x < 0 || y < 0 || x >= size || y >= size || !self.modules[y as usize][x as usize]
+        };
+        let begin = -(QUIET as isize);
+        let end = size + QUIET as isize;
+        (begin..end)
+            .step_by(2)
+            .map(|y| {
+                (begin..end)
+                    .map(|x| match (light(x, y), y + 1 < end && light(x, y + 1)) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+fn alignment_centers(version: usize) -> impl Iterator<Item = (usize, usize)> {
+    let centers = ALIGNMENTS[version - 1];
+    let last = centers.len().saturating_sub(1);
+    centers.iter().enumerate().flat_map(move |(i, &cy)| {
+        centers.iter().enumerate().filter_map(move |(j, &cx)| {
+            // Finders are at the three corners.
+            let on_finder = (i == 0 && (j == 0 || j == last)) || (i == last && j == 0);
+            (!on_finder).then_some((cx, cy))
+        })
+    })
+}
+
+fn is_masked(mask: u8, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+// Mode, length, data and padding, in codewords.
+fn data_codewords(version: usize, data: &[u8]) -> Vec<u8> {
+    let capacity: usize = VERSIONS[version - 1].2.iter().sum();
+    let mut bits: Vec<bool> = vec![];
+    let mut push = |value: usize, len: usize| {
+        for i in (0..len).rev() {
+            bits.push((value >> i) & 1 != 0);
+        }
+    };
+    push(0b0100, 4);
+    push(data.len(), if version < 10 { 8 } else { 16 });
+    for &byte in data {
+        push(byte as usize, 8);
+    }
+    let terminator = (capacity * 8 - bits.len()).min(4);
+    bits.extend(std::iter::repeat_n(false, terminator));
+    while !bits.len().is_multiple_of(8) {
+        bits.push(false);
+    }
+    let mut codewords: Vec<u8> = bits
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit as u8))
+        .collect();
+    for pad in [0xec, 0x11].into_iter().cycle() {
+        if codewords.len() >= capacity {
+            break;
+        }
+        codewords.push(pad);
+    }
+    codewords
+}
+
+// Split data into blocks, append their error correction codewords and
+// interleave them.
+fn add_ecc(version: usize, data: &[u8]) -> Vec<u8> {
+    let (total, ecc_len, lens) = VERSIONS[version - 1];
+    let divisor = rs_divisor(ecc_len);
+    let mut blocks = vec![];
+    let mut start = 0;
+    for &len in lens {
+        let block = &data[start..start + len];
+        blocks.push((block, rs_remainder(block, &divisor)));
+        start += len;
+    }
+    let mut result = Vec::with_capacity(total);
+    let max_len = lens.iter().copied().max().unwrap_or(0);
+    for i in 0..max_len {
+        result.extend(blocks.iter().filter_map(|(block, _)| block.get(i)));
+    }
+    for i in 0..ecc_len {
+        result.extend(blocks.iter().map(|(_, ecc)| ecc[i]));
+    }
+    result
+}
+
+// Multiply in GF(2^8) with the polynomial 0x11d.
+fn gf_mul(x: u8, y: u8) -> u8 {
+    let mut z: u8 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x1d);
+        z ^= ((y >> i) & 1) * x;
+    }
+    z
+}
+
+// Coefficients of the generator polynomial without the leading one.
+fn rs_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0; degree];
+    result[degree - 1] = 1;
+    let mut root: u8 = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_mul(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_mul(root, 0x02);
+    }
+    result
+}
+
+fn rs_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ result.remove(0);
+        result.push(0);
+        for (r, &d) in result.iter_mut().zip(divisor) {
+            *r ^= gf_mul(d, factor);
+        }
+    }
+    result
+}
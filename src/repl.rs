@@ -0,0 +1,86 @@
+use crate::config::Config;
+use crate::paths;
+use crate::rpc::Client;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{Context, Editor, Helper, Highlighter, Hinter, Validator};
+use std::error::Error;
+
+// Commands of the fuo rpc server, for tab completion.
+const COMMANDS: [&str; 18] = [
+    "status", "list", "play", "pause", "resume", "toggle", "stop", "next", "previous", "add",
+    "remove", "clear", "search", "show", "exec", "set", "sub", "help",
+];
+
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct FuoHelper;
+
+impl Completer for FuoHelper {
+    type Candidate = String;
+
+    // Only the command name, which is the first word, is completed.
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let word = &line[..pos];
+        if word.contains(char::is_whitespace) {
+            return Ok((pos, vec![]));
+        }
+        let candidates = COMMANDS
+            .iter()
+            .filter(|cmd| cmd.starts_with(word))
+            .map(|cmd| cmd.to_string())
+            .collect();
+        Ok((0, candidates))
+    }
+}
+
+// Print the response body, json bodies are pretty printed.
+fn print_body(body: &[u8]) {
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(value) => println!("{}", serde_json::to_string_pretty(&value).unwrap()),
+        Err(_) => println!("{}", String::from_utf8_lossy(body).trim_end()),
+    }
+}
+
+// Send raw commands to the fuo server, like `fuo` does.
+pub fn run(config: &Config) -> Result<(), Box<dyn Error>> {
+    let client = Client::new(&config.server);
+    let history = paths::data_dir().join("repl_history");
+    let mut editor: Editor<FuoHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(FuoHelper));
+    // There is no history the first time.
+    let _ = editor.load_history(&history);
+
+    loop {
+        let line = match editor.readline("fuo> ") {
+            Ok(line) => line,
+            // Ctrl-C clears the line while Ctrl-D quits.
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+        if line == "exit" || line == "quit" {
+            break;
+        }
+        match client.send_request(line) {
+            Ok(resp) if resp.ok => print_body(&resp.body),
+            Ok(resp) => {
+                print!("error: ");
+                print_body(&resp.body);
+            }
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
+    editor.save_history(&history)?;
+    Ok(())
+}
@@ -1,7 +1,13 @@
-use log::{error, info};
+use crate::config::ServerConfig;
+use crate::inspector::{self, Kind};
+use crate::logger::Field;
+use std::cell::Cell;
 use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Write};
 use std::net::TcpStream;
 use std::result::Result;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, trace, warn};
 
 #[allow(dead_code)]
 pub struct Response {
@@ -14,112 +20,259 @@ pub struct Message {
     pub body: Vec<u8>,
 }
 
+// What subscribers receive.
+pub enum Pubsub {
+    // Messages which arrive together, like the ones of a queue rebuild.
+    Messages(Vec<Message>),
+    // The connection is set up again after it was lost.
+    Reconnected,
+}
+
 pub enum RespOrMsg {
     Response(Response),
     Message(Message),
 }
 
-pub fn read_response(reader: &mut BufReader<TcpStream>) -> Result<RespOrMsg, Error> {
-    let mut status_line = String::new();
-    if reader.read_line(&mut status_line).unwrap() == 0 {
-        return Err(Error::new(ErrorKind::ConnectionAborted, "disconnected"));
-    }
-    let mut words = status_line.split_whitespace();
-    let ack_or_msg = words.next().unwrap();
-    let body_len_s = words.clone().last().unwrap();
-    let body_len = body_len_s.parse::<usize>().unwrap();
-
-    // Consume \r\n.
-    let mut body = vec![0; body_len + 2];
-    reader.read_exact(&mut body)?;
-    body.truncate(body_len);
-
-    // Response looks like::
-    //   ACK OK 5
-    //   hello
-    // While message looks like::
-    //   MSG topic_name 5
-    //   hello
-    if ack_or_msg.to_lowercase() == "ack" {
-        let word = words.next().unwrap();
-        let ok = word.to_lowercase() == *"ok";
-        Ok(RespOrMsg::Response(Response { ok, body }))
-    } else {
-        let topic = words.next().unwrap().to_string();
-        Ok(RespOrMsg::Message(Message { topic, body }))
+const RECONNECT_DELAY_MIN: Duration = Duration::from_millis(500);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(10);
+
+// Bodies larger than it are taken as a garbled length.
+const MAX_BODY_LEN: usize = 256 * 1024 * 1024;
+
+enum Header {
+    Response { ok: bool, len: usize },
+    Message { topic: String, len: usize },
+}
+
+// Status lines look like `ACK OK 5` or `MSG topic_name 5`.
+fn parse_header(line: &str) -> Option<Header> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        [ack, status, .., len] if ack.eq_ignore_ascii_case("ack") => Some(Header::Response {
+            ok: status.eq_ignore_ascii_case("ok"),
+            len: len.parse().ok()?,
+        }),
+        [msg, topic, len] if msg.eq_ignore_ascii_case("msg") => Some(Header::Message {
+            topic: topic.to_string(),
+            len: len.parse().ok()?,
+        }),
+        _ => None,
     }
 }
 
-pub fn send_request(cmd: impl std::fmt::Display) -> Result<Response, Error> {
-    match TcpStream::connect("127.0.0.1:23333") {
-        Ok(stream) => {
-            info!("Successfully connected to fuo pubsub server in port 23333");
-            let mut reader = BufReader::new(stream.try_clone().unwrap());
-            let mut writer = BufWriter::new(stream);
-            let mut line = String::new();
-
-            // Receive the welcome message.
-            if reader.read_line(&mut line).unwrap() > 0 {
-                info!("{}", line);
+// Read the next response or message.
+//
+// Response looks like::
+//   ACK OK 5
+//   hello
+// While message looks like::
+//   MSG topic_name 5
+//   hello
+//
+// Lines which are not status lines are skipped, so that the reader gets back
+// in sync at the next one.
+pub fn read_response(reader: &mut BufReader<TcpStream>) -> Result<RespOrMsg, Error> {
+    loop {
+        let mut line = vec![];
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Err(Error::new(ErrorKind::ConnectionAborted, "disconnected"));
+        }
+        let line = String::from_utf8_lossy(&line);
+        let header = match parse_header(&line) {
+            Some(header) => header,
+            None => {
+                if !line.trim().is_empty() {
+                    warn!("skip malformed line {:?}", line.trim_end());
+                }
+                continue;
             }
+        };
+        let len = match header {
+            Header::Response { len, .. } | Header::Message { len, .. } => len,
+        };
+        if len > MAX_BODY_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("body of {} bytes is too large", len),
+            ));
+        }
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body)?;
+        // Consume \r\n, and whatever is left if the length was wrong.
+        let mut rest = vec![];
+        reader.read_until(b'\n', &mut rest)?;
+        if !rest.iter().all(u8::is_ascii_whitespace) {
+            warn!("skip {} bytes after the body", rest.len());
+        }
+        return Ok(match header {
+            Header::Response { ok, .. } => RespOrMsg::Response(Response { ok, body }),
+            Header::Message { topic, .. } => RespOrMsg::Message(Message { topic, body }),
+        });
+    }
+}
+
+// Client of a fuo server.
+#[derive(Clone)]
+pub struct Client {
+    pub host: String,
+    pub rpc_port: u16,
+    // Always set in validated configs.
+    pub pubsub_port: Option<u16>,
+    // Token sent right after connecting, for servers that require it.
+    pub auth: Option<String>,
+    // Format of response bodies, fust only understands json for now.
+    pub format: String,
+}
+
+impl Client {
+    pub fn new(server: &ServerConfig) -> Client {
+        Client {
+            host: server.host.clone(),
+            rpc_port: server.rpc_port,
+            pubsub_port: server.pubsub_port(),
+            auth: server.auth.clone(),
+            format: server.format.clone(),
+        }
+    }
+
+    // Connect to the port, receive the welcome message and authenticate.
+    fn connect(&self, port: u16) -> Result<(BufReader<TcpStream>, BufWriter<TcpStream>), Error> {
+        let stream = TcpStream::connect((self.host.as_str(), port))?;
+        debug!(port, "connected to fuo server");
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+        let mut line = String::new();
 
-            writer
-                .write_all(format!("{cmd} --format=json\n").as_bytes())
-                .unwrap();
-            writer.flush().unwrap();
+        // Receive the welcome message.
+        if reader.read_line(&mut line)? > 0 {
+            info!("{}", line.trim_end());
+        }
+
+        if let Some(token) = &self.auth {
+            writer.write_all(format!("auth {}\n", token).as_bytes())?;
+            writer.flush()?;
             match read_response(&mut reader)? {
-                RespOrMsg::Response(resp) => Ok(resp),
-                RespOrMsg::Message(_) => panic!(""),
+                RespOrMsg::Response(resp) if resp.ok => {}
+                _ => return Err(Error::new(ErrorKind::PermissionDenied, "auth failed")),
             }
         }
-        Err(e) => {
-            log::error!("Failed to connect: {}", e);
-            Err(e)
+        Ok((reader, writer))
+    }
+
+    pub fn send_request(&self, cmd: impl std::fmt::Display) -> Result<Response, Error> {
+        let (mut reader, mut writer) = self.connect(self.rpc_port).map_err(|e| {
+            error!(port = self.rpc_port, "failed to connect: {}", e);
+            e
+        })?;
+        let cmd = cmd.to_string();
+        let start = Instant::now();
+        inspector::record(Kind::Request, &cmd, b"");
+        writer.write_all(format!("{cmd} --format={}\n", self.format).as_bytes())?;
+        writer.flush()?;
+        loop {
+            match read_response(&mut reader)? {
+                RespOrMsg::Response(resp) => {
+                    debug!(
+                        cmd = %Field(&cmd),
+                        ok = resp.ok,
+                        size = resp.body.len(),
+                        latency_ms = start.elapsed().as_millis() as u64,
+                        "response"
+                    );
+                    inspector::record(Kind::Response { ok: resp.ok }, &cmd, &resp.body);
+                    return Ok(resp);
+                }
+                // The rpc port should never send messages.
+                RespOrMsg::Message(msg) => warn!("skip message of {} from the rpc port", msg.topic),
+            }
         }
     }
-}
 
-// TODO: exit and reconnect properly.
-pub fn subscribe_topics(topics: Vec<&str>, cb: &dyn Fn(Message)) {
-    // TODO: use port as a paramter.
-    match TcpStream::connect("127.0.0.1:23334") {
-        Ok(stream) => {
-            info!("Successfully connected to fuo pubsub server in port 23334");
-            let mut reader = BufReader::new(stream.try_clone().unwrap());
-            let mut writer = BufWriter::new(stream);
-            let mut line = String::new();
-
-            // Receive the welcome message.
-            if reader.read_line(&mut line).unwrap() > 0 {
-                info!("{}", line);
+    pub fn subscribe_topics(&self, topics: Vec<&str>, cb: &dyn Fn(Message)) {
+        self.subscribe_pubsub(topics, &|event| {
+            if let Pubsub::Messages(msgs) = event {
+                msgs.into_iter().for_each(cb);
             }
+        });
+    }
 
-            // Subscribe topics and consume responses.
-            // TODO: handle request error.
-            writer.write_all(b"set --pubsub-version 2.0\n").unwrap();
-            let mut req_count = 1;
-            for topic in topics.iter() {
-                writer
-                    .write_all(format!("sub {}\n", topic).as_bytes())
-                    .unwrap();
-                req_count += 1;
+    // Subscribe topics forever. The connection is set up again when it is
+    // lost, like when the server restarts, and `cb` is told about it since
+    // messages may be missed meanwhile.
+    pub fn subscribe_pubsub(&self, topics: Vec<&str>, cb: &dyn Fn(Pubsub)) {
+        let subscribed_once = Cell::new(false);
+        let mut delay = RECONNECT_DELAY_MIN;
+        loop {
+            let subscribed = Cell::new(false);
+            let on_subscribed = || {
+                subscribed.set(true);
+                if subscribed_once.replace(true) {
+                    info!(port = self.pubsub_port, "resubscribed");
+                    cb(Pubsub::Reconnected);
+                }
+            };
+            let result =
+                self.subscribe(&topics, &on_subscribed, &|msgs| cb(Pubsub::Messages(msgs)));
+            if let Err(e) = result {
+                warn!(port = self.pubsub_port, "subscription ends: {}", e);
             }
-            writer.flush().unwrap();
-            for _ in 0..req_count {
-                read_response(&mut reader).unwrap();
+            // Back off while the server is down.
+            delay = if subscribed.get() {
+                RECONNECT_DELAY_MIN
+            } else {
+                (delay * 2).min(RECONNECT_DELAY_MAX)
+            };
+            thread::sleep(delay);
+        }
+    }
+
+    fn subscribe(
+        &self,
+        topics: &[&str],
+        on_subscribed: &dyn Fn(),
+        cb: &dyn Fn(Vec<Message>),
+    ) -> Result<(), Error> {
+        let port = self
+            .pubsub_port
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "no pubsub port"))?;
+        let (mut reader, mut writer) = self.connect(port)?;
+        // Subscribe topics and consume responses.
+        writer.write_all(b"set --pubsub-version 2.0\n")?;
+        for topic in topics.iter() {
+            writer.write_all(format!("sub {}\n", topic).as_bytes())?;
+        }
+        writer.flush()?;
+        for topic in ["set --pubsub-version 2.0"].iter().chain(topics) {
+            if let RespOrMsg::Response(resp) = read_response(&mut reader)? {
+                if !resp.ok {
+                    error!(
+                        "`{}` failed: {}",
+                        topic,
+                        String::from_utf8_lossy(&resp.body).trim()
+                    );
+                }
             }
+        }
+
+        on_subscribed();
 
-            // Wait for messages.
+        // Wait for messages, and take the ones which are already buffered as
+        // well.
+        loop {
+            let mut batch = vec![];
             loop {
-                let resp_or_msg = read_response(&mut reader).unwrap();
-                match resp_or_msg {
-                    RespOrMsg::Message(msg) => cb(msg),
-                    RespOrMsg::Response(_) => {}
+                if let RespOrMsg::Message(msg) = read_response(&mut reader)? {
+                    trace!(topic = %Field(&msg.topic), size = msg.body.len(), "message");
+                    inspector::record(Kind::Message, &msg.topic, &msg.body);
+                    batch.push(msg);
+                }
+                if reader.buffer().is_empty() {
+                    break;
                 }
             }
-        }
-        Err(e) => {
-            error!("Failed to connect: {}", e);
+            if !batch.is_empty() {
+                cb(batch);
+            }
         }
     }
 }
@@ -0,0 +1,365 @@
+use crate::app::AppInner;
+use crate::config::{LastfmConfig, ListenBrainzConfig, ScrobblerConfig};
+use crate::paths;
+use crate::player::PlayerState;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const LASTFM_API: &str = "https://ws.audioscrobbler.com/2.0/";
+const LISTENBRAINZ_API: &str = "https://api.listenbrainz.org/1/submit-listens";
+
+// Tracks shorter than this are never scrobbled, same as Last.fm's rule.
+const MIN_TRACK_DURATION: Duration = Duration::from_secs(30);
+const MAX_PLAYED_DURATION: Duration = Duration::from_secs(4 * 60);
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct Track {
+    title: String,
+    artist: String,
+    album: Option<String>,
+    duration_secs: u64,
+}
+
+// A scrobble which failed to be submitted and will be retried.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Pending {
+    service: String,
+    track: Track,
+    timestamp: u64,
+}
+
+trait Service {
+    fn name(&self) -> &'static str;
+    fn now_playing(&self, track: &Track) -> Result<(), Box<dyn Error>>;
+    fn scrobble(&self, track: &Track, timestamp: u64) -> Result<(), Box<dyn Error>>;
+}
+
+struct Lastfm {
+    config: LastfmConfig,
+}
+
+impl Lastfm {
+    // Sign and post a method call, see https://www.last.fm/api/authspec.
+    fn call(
+        &self,
+        method: &str,
+        track: &Track,
+        extra: &[(&str, String)],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut params: Vec<(&str, String)> = vec![
+            ("method", method.to_owned()),
+            ("api_key", self.config.api_key.clone()),
+            ("sk", self.config.session_key.clone()),
+            ("artist", track.artist.clone()),
+            ("track", track.title.clone()),
+            ("duration", track.duration_secs.to_string()),
+        ];
+        if let Some(album) = &track.album {
+            params.push(("album", album.clone()));
+        }
+        params.extend(extra.iter().cloned());
+        params.sort_by(|a, b| a.0.cmp(b.0));
+        let mut sig = String::new();
+        for (k, v) in params.iter() {
+            sig.push_str(k);
+            sig.push_str(v);
+        }
+        sig.push_str(&self.config.api_secret);
+        let sig = format!("{:x}", md5::compute(sig));
+        params.push(("api_sig", sig));
+        params.push(("format", "json".to_owned()));
+
+        let form: Vec<(&str, &str)> = params.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        ureq::post(LASTFM_API).send_form(&form)?;
+        Ok(())
+    }
+}
+
+impl Service for Lastfm {
+    fn name(&self) -> &'static str {
+        "lastfm"
+    }
+
+    fn now_playing(&self, track: &Track) -> Result<(), Box<dyn Error>> {
+        self.call("track.updateNowPlaying", track, &[])
+    }
+
+    fn scrobble(&self, track: &Track, timestamp: u64) -> Result<(), Box<dyn Error>> {
+        self.call(
+            "track.scrobble",
+            track,
+            &[("timestamp", timestamp.to_string())],
+        )
+    }
+}
+
+struct ListenBrainz {
+    config: ListenBrainzConfig,
+}
+
+impl ListenBrainz {
+    fn submit(&self, listen_type: &str, listen: serde_json::Value) -> Result<(), Box<dyn Error>> {
+        let body = serde_json::json!({
+            "listen_type": listen_type,
+            "payload": [listen],
+        });
+        ureq::post(LISTENBRAINZ_API)
+            .set("Authorization", &format!("Token {}", self.config.token))
+            .set("Content-Type", "application/json")
+            .send_string(&body.to_string())?;
+        Ok(())
+    }
+
+    fn track_metadata(track: &Track) -> serde_json::Value {
+        let mut metadata = serde_json::json!({
+            "artist_name": track.artist,
+            "track_name": track.title,
+            "additional_info": {"duration": track.duration_secs},
+        });
+        if let Some(album) = &track.album {
+            metadata["release_name"] = serde_json::Value::from(album.clone());
+        }
+        metadata
+    }
+}
+
+impl Service for ListenBrainz {
+    fn name(&self) -> &'static str {
+        "listenbrainz"
+    }
+
+    fn now_playing(&self, track: &Track) -> Result<(), Box<dyn Error>> {
+        let listen = serde_json::json!({ "track_metadata": ListenBrainz::track_metadata(track) });
+        self.submit("playing_now", listen)
+    }
+
+    fn scrobble(&self, track: &Track, timestamp: u64) -> Result<(), Box<dyn Error>> {
+        let listen = serde_json::json!({
+            "listened_at": timestamp,
+            "track_metadata": ListenBrainz::track_metadata(track),
+        });
+        self.submit("single", listen)
+    }
+}
+
+// The track being played and how long it has actually been played.
+struct Playing {
+    track: Track,
+    started_at: u64,
+    played: Duration,
+    last_position: Duration,
+    scrobbled: bool,
+}
+
+impl Playing {
+    // Half of the track or 4 minutes, whichever comes first.
+    fn should_scrobble(&self) -> bool {
+        let duration = Duration::from_secs(self.track.duration_secs);
+        !self.scrobbled
+            && duration >= MIN_TRACK_DURATION
+            && self.played >= (duration / 2).min(MAX_PLAYED_DURATION)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// What the scrobbler needs from the app state, taken while holding the
+// lock so that network requests are sent without it.
+struct Snapshot {
+    track: Option<Track>,
+    position: Duration,
+    duration: Duration,
+    playing: bool,
+}
+
+impl Snapshot {
+    fn new(inner: &AppInner) -> Snapshot {
+        let track = if inner.metadata.title.is_empty() || inner.loading {
+            None
+        } else {
+            Some(Track {
+                title: inner.metadata.title.clone(),
+                artist: inner.metadata.artists.join(", "),
+                album: inner
+                    .metadata
+                    .album
+                    .clone()
+                    .filter(|album| !album.is_empty()),
+                duration_secs: inner.duration.as_secs(),
+            })
+        };
+        Snapshot {
+            track,
+            position: inner.progress.current(),
+            duration: inner.duration,
+            playing: inner.state == PlayerState::Playing,
+        }
+    }
+}
+
+struct Scrobbler {
+    services: Vec<Box<dyn Service + Send>>,
+    pending: Vec<Pending>,
+    playing: Option<Playing>,
+}
+
+impl Scrobbler {
+    fn queue_path() -> std::path::PathBuf {
+        paths::data_dir().join("scrobbles.json")
+    }
+
+    fn load_pending() -> Vec<Pending> {
+        fs::read(Scrobbler::queue_path())
+            .ok()
+            .and_then(|s| serde_json::from_slice(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_pending(&self) {
+        let path = Scrobbler::queue_path();
+        let result = if self.pending.is_empty() {
+            fs::remove_file(&path).or(Ok(()))
+        } else {
+            fs::write(&path, serde_json::to_vec(&self.pending).unwrap())
+        };
+        if let Err(e) = result {
+            error!("scrobbler: failed to save {}: {}", path.display(), e);
+        }
+    }
+
+    fn on_tick(&mut self, snapshot: Snapshot) {
+        let track = snapshot.track;
+        let position = snapshot.position;
+
+        let changed = match (&self.playing, &track) {
+            (Some(playing), Some(track)) => {
+                // The same song played again, like in repeat-one, starts
+                // over near the beginning once it is scrobbled.
+                let replayed = playing.scrobbled
+                    && position < playing.last_position
+                    && position < Duration::from_secs(5);
+                playing.track.title != track.title
+                    || playing.track.artist != track.artist
+                    || replayed
+            }
+            (None, None) => false,
+            _ => true,
+        };
+        if changed {
+            self.playing = track.map(|track| {
+                for service in self.services.iter() {
+                    if let Err(e) = service.now_playing(&track) {
+                        error!("scrobbler: {} now playing failed: {}", service.name(), e);
+                    }
+                }
+                Playing {
+                    track,
+                    started_at: unix_now(),
+                    played: Duration::ZERO,
+                    last_position: position,
+                    scrobbled: false,
+                }
+            });
+        }
+
+        let mut to_scrobble = None;
+        if let Some(playing) = self.playing.as_mut() {
+            // Duration may arrive after metadata.
+            playing.track.duration_secs = snapshot.duration.as_secs();
+            // Only count normal playback, jumps are seeks.
+            if snapshot.playing && position > playing.last_position {
+                let delta = position - playing.last_position;
+                if delta < Duration::from_secs(5) {
+                    playing.played += delta;
+                }
+            }
+            playing.last_position = position;
+            if playing.should_scrobble() {
+                playing.scrobbled = true;
+                to_scrobble = Some((playing.track.clone(), playing.started_at));
+            }
+        }
+        if let Some((track, timestamp)) = to_scrobble {
+            for service in self.services.iter() {
+                match service.scrobble(&track, timestamp) {
+                    Ok(()) => info!("scrobbler: scrobbled {} to {}", track.title, service.name()),
+                    Err(e) => {
+                        error!("scrobbler: {} scrobble failed: {}", service.name(), e);
+                        self.pending.push(Pending {
+                            service: service.name().to_owned(),
+                            track: track.clone(),
+                            timestamp,
+                        });
+                        self.save_pending();
+                    }
+                }
+            }
+        }
+    }
+
+    fn retry_pending(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let pending = std::mem::take(&mut self.pending);
+        for p in pending {
+            // Drop scrobbles of services which are not configured anymore.
+            let service = match self.services.iter().find(|s| s.name() == p.service) {
+                Some(service) => service,
+                None => continue,
+            };
+            if let Err(e) = service.scrobble(&p.track, p.timestamp) {
+                error!("scrobbler: {} retry failed: {}", service.name(), e);
+                self.pending.push(p);
+            }
+        }
+        self.save_pending();
+    }
+}
+
+// Run the scrobbler in a background thread if any service is configured.
+pub fn start(config: &ScrobblerConfig, inner: Arc<RwLock<AppInner>>) {
+    let mut services: Vec<Box<dyn Service + Send>> = vec![];
+    if let Some(config) = &config.lastfm {
+        services.push(Box::new(Lastfm {
+            config: config.clone(),
+        }));
+    }
+    if let Some(config) = &config.listenbrainz {
+        services.push(Box::new(ListenBrainz {
+            config: config.clone(),
+        }));
+    }
+    if services.is_empty() {
+        return;
+    }
+
+    thread::spawn(move || {
+        let mut scrobbler = Scrobbler {
+            services,
+            pending: Scrobbler::load_pending(),
+            playing: None,
+        };
+        let mut last_retry = Instant::now();
+        loop {
+            let snapshot = Snapshot::new(&inner.read().unwrap());
+            scrobbler.on_tick(snapshot);
+            if last_retry.elapsed() >= RETRY_INTERVAL {
+                scrobbler.retry_pending();
+                last_retry = Instant::now();
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+    });
+}
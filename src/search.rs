@@ -0,0 +1,35 @@
+use crate::models::BriefSong;
+
+// Lowercased title, artists and album of songs, built once when the playlist
+// changes so that filtering does not allocate per song and keystroke.
+#[derive(Default)]
+pub struct Index {
+    keys: Vec<String>,
+}
+
+impl Index {
+    pub fn new(songs: &[BriefSong]) -> Index {
+        let mut index = Index::default();
+        index.extend(songs);
+        index
+    }
+
+    // Index songs appended to the playlist.
+    pub fn extend(&mut self, songs: &[BriefSong]) {
+        self.keys.extend(songs.iter().map(|song| {
+            // Fields are separated by a char which can not be typed, so that
+            // keywords never match across them.
+            format!("{}\n{}\n{}", song.title, song.artists_name, song.album_name).to_lowercase()
+        }));
+    }
+
+    // Indices of the songs which contain the lowercased keyword, only among
+    // `within` if it is given, like the results of a shorter keyword.
+    pub fn search(&self, keyword: &str, within: Option<&[usize]>) -> Vec<usize> {
+        let matches = |i: &usize| self.keys.get(*i).is_some_and(|key| key.contains(keyword));
+        match within {
+            Some(indices) => indices.iter().copied().filter(matches).collect(),
+            None => (0..self.keys.len()).filter(matches).collect(),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use crate::store;
+use serde::{Deserialize, Serialize};
+
+const SESSION: &str = "session";
+
+// Preferences of the UI which are changed while running, rather than in the
+// config, and kept for the next run.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Session {
+    // Show the remaining time instead of the elapsed one in the progress.
+    pub remaining_time: bool,
+}
+
+impl Session {
+    pub fn load() -> Session {
+        store::load(SESSION)
+    }
+
+    pub fn save(&self) {
+        store::save(SESSION, self);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::models::BriefSong;
+use crate::player::parse_position;
+use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+
+// A playlist of the songs in the library which match all of its rules.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SmartPlaylist {
+    pub name: String,
+    pub rules: Vec<Rule>,
+}
+
+impl SmartPlaylist {
+    pub fn matches(&self, song: &BriefSong) -> bool {
+        self.rules.iter().all(|rule| rule.matches(song))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Title,
+    Artist,
+    Album,
+    Provider,
+    Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Contains,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+// Longer ones first so that `>=` is not taken as `>`.
+const OPS: [(&str, Op); 7] = [
+    ("!=", Op::Ne),
+    (">=", Op::Ge),
+    ("<=", Op::Le),
+    ("=", Op::Eq),
+    ("~", Op::Contains),
+    (">", Op::Gt),
+    ("<", Op::Lt),
+];
+
+// A rule like `artist ~ adele` or `duration > 5:00`. Texts are compared
+// case-insensitively, and `~` means containing.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Rule {
+    field: Field,
+    op: Op,
+    value: String,
+    // The value of a duration rule.
+    duration: Duration,
+}
+
+#[derive(Debug)]
+pub struct ParseRuleError(String);
+
+impl fmt::Display for ParseRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl TryFrom<String> for Rule {
+    type Error = ParseRuleError;
+
+    fn try_from(s: String) -> Result<Rule, ParseRuleError> {
+        let invalid = |reason: &str| ParseRuleError(format!("invalid rule {:?}: {}", s, reason));
+        let trimmed = s.trim();
+        let (field, rest) = trimmed
+            .split_once(char::is_whitespace)
+            .unwrap_or((trimmed, ""));
+        let field = match field {
+            "title" => Field::Title,
+            "artist" => Field::Artist,
+            "album" => Field::Album,
+            "provider" => Field::Provider,
+            "duration" => Field::Duration,
+            _ => {
+                return Err(invalid(
+                    "expected title, artist, album, provider or duration",
+                ))
+            }
+        };
+        let rest = rest.trim_start();
+        let (op, value) = OPS
+            .iter()
+            .find_map(|(name, op)| rest.strip_prefix(name).map(|value| (*op, value.trim())))
+            .ok_or_else(|| invalid("expected one of =, !=, ~, >, >=, < and <="))?;
+        let mut duration = Duration::ZERO;
+        if field == Field::Duration {
+            if op == Op::Contains {
+                return Err(invalid("~ is only for texts"));
+            }
+            duration = parse_position(value).ok_or_else(|| invalid("expected mm:ss"))?;
+        } else if ![Op::Eq, Op::Ne, Op::Contains].contains(&op) {
+            return Err(invalid("only =, != and ~ are for texts"));
+        }
+        Ok(Rule {
+            field,
+            op,
+            value: value.to_lowercase(),
+            duration,
+        })
+    }
+}
+
+impl Rule {
+    pub fn matches(&self, song: &BriefSong) -> bool {
+        let text = match self.field {
+            Field::Title => &song.title,
+            Field::Artist => &song.artists_name,
+            Field::Album => &song.album_name,
+            Field::Provider => &song.provider,
+            Field::Duration => {
+                let duration = match parse_position(&song.duration_ms) {
+                    Some(duration) => duration,
+                    None => return false,
+                };
+                return match self.op {
+                    Op::Eq => duration == self.duration,
+                    Op::Ne => duration != self.duration,
+                    Op::Gt => duration > self.duration,
+                    Op::Ge => duration >= self.duration,
+                    Op::Lt => duration < self.duration,
+                    Op::Le => duration <= self.duration,
+                    Op::Contains => false,
+                };
+            }
+        };
+        let text = text.to_lowercase();
+        match self.op {
+            Op::Eq => text == self.value,
+            Op::Ne => text != self.value,
+            Op::Contains => text.contains(&self.value),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use crate::paths;
+use log::error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// Small states remembered across runs, a json file per name in
+// `$XDG_DATA_HOME/fust`.
+fn path(name: &str) -> PathBuf {
+    paths::data_dir().join(format!("{}.json", name))
+}
+
+// Load the state, which is the default one if it is missing or broken.
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+    let path = path(name);
+    match fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            error!("failed to parse {}: {}", path.display(), e);
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+pub fn save<T: Serialize>(name: &str, value: &T) {
+    let path = path(name);
+    let result = serde_json::to_vec_pretty(value)
+        .map_err(io::Error::from)
+        .and_then(|bytes| fs::write(&path, bytes));
+    if let Err(e) = result {
+        error!("failed to save {}: {}", path.display(), e);
+    }
+}
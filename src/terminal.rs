@@ -0,0 +1,82 @@
+use crossterm::{
+    cursor::Show,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Save and restore the terminal title with the xterm title stack, which is
+// ignored by terminals that do not support it.
+const PUSH_TITLE: &[u8] = b"\x1b[22;0t";
+const POP_TITLE: &[u8] = b"\x1b[23;0t";
+
+// Whether the terminal is taken over by the TUI, and whether the title is
+// pushed, so that it is restored exactly once whoever gets there first.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static TITLE_PUSHED: AtomicBool = AtomicBool::new(false);
+
+// The terminal taken over by the TUI, which is given back when dropped, so
+// that returning, failing and panicking all leave it usable.
+pub struct Guard {
+    set_title: bool,
+}
+
+impl Guard {
+    pub fn enter(set_title: bool) -> io::Result<Guard> {
+        if let Err(e) = setup(set_title) {
+            // Undo whatever is done before the failure.
+            restore();
+            return Err(e);
+        }
+        Ok(Guard { set_title })
+    }
+
+    // Give the terminal back while `f` runs, for stopping on Ctrl-Z or
+    // running other programs, and take it over again afterwards. The screen
+    // should be cleared then as it is not known what is left on it.
+    pub fn suspend<T>(&self, f: impl FnOnce() -> T) -> io::Result<T> {
+        restore();
+        let value = f();
+        Guard::enter(self.set_title)?;
+        Ok(value)
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+// Whether terminal events are for the TUI, instead of a program it runs.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+// Give the terminal back, which does nothing if it is not taken over, so that
+// it is safe to call from anywhere like the panic hook.
+pub fn restore() {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    let mut stdout = io::stdout();
+    let _ = disable_raw_mode();
+    let _ = execute!(stdout, LeaveAlternateScreen, DisableMouseCapture, Show);
+    if TITLE_PUSHED.swap(false, Ordering::SeqCst) {
+        let _ = stdout.write_all(POP_TITLE);
+    }
+    let _ = stdout.flush();
+}
+
+fn setup(set_title: bool) -> io::Result<()> {
+    ACTIVE.store(true, Ordering::SeqCst);
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    if set_title {
+        stdout.write_all(PUSH_TITLE)?;
+        TITLE_PUSHED.store(true, Ordering::SeqCst);
+    }
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)
+}
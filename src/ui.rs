@@ -1,98 +1,913 @@
-use crate::app::App;
-use crate::player::PlayerState;
-use std::time::Duration;
+use crate::app::{
+    AbRepeat, App, AppInner, CommentsPane, Confirm, Help, InspectorView, LogPanel, Modal, Picker,
+    ANNOUNCEMENTS,
+};
+use crate::clock;
+use crate::config::ProgressStyle;
+use crate::glyphs::AsciiBorders;
+use crate::i18n::tr;
+use crate::inspector::{self, Kind};
+use crate::logger;
+use crate::lyric::{LyricMode, LyricState};
+use crate::models::{BriefSong, LoginState};
+use crate::page::{
+    CollectionsPage, Cursor, LoginPage, Page, PlaylistsPage, ProvidersPage, StatsPage,
+};
+use crate::player::{fmt_duration, PlayerMetadata, PlayerState};
+use log::Level;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tui::{
     backend::Backend,
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    symbols::line::THICK,
-    symbols::DOT,
     text::{Span, Spans},
-    widgets::{LineGauge, Paragraph, Row, Table, TableState, Wrap},
+    widgets::{
+        Block, Borders, Cell, Clear, Gauge, LineGauge, Paragraph, Row, Table, TableState, Wrap,
+    },
     Frame,
 };
 
-fn fmt_duration(duration: Duration) -> String {
-    let seconds = duration.as_secs() % 60;
-    let minutes = (duration.as_secs() / 60) % 60;
-    let hours = (duration.as_secs() / 60) / 60;
-    if hours > 0 {
-        format!("{:0>2}:{:0>2}:{:0>2}", hours, minutes, seconds)
-    } else {
-        format!("{:0>2}:{:0>2}", minutes, seconds)
+// Characters of the audio output device shown in the status bar.
+const AUDIO_DEVICE_WIDTH: usize = 12;
+
+fn spinner(frames: &[&'static str]) -> &'static str {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    frames[(millis / 100) as usize % frames.len()]
+}
+
+// Put the cursor on the focused row in the accessible mode, which screen
+// readers follow.
+fn focus<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect, y: u16) {
+    if app.config.accessible && y < area.bottom() {
+        f.set_cursor(area.x, y);
     }
 }
 
-pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
-    let area = f.size();
+// Put A and B on the bar of the gauge.
+fn draw_ab_markers<B: Backend>(
+    f: &mut Frame<B>,
+    app: &App,
+    ab: &AbRepeat,
+    duration: Duration,
+    bar: Rect,
+) {
+    if duration.is_zero() || bar.width == 0 {
+        return;
+    }
+    let (start, width) = (bar.x, bar.width);
+    let style = Style::default()
+        .fg(app.config.theme.accent)
+        .add_modifier(Modifier::BOLD);
+    let markers = [("A", Some(ab.a)), ("B", ab.b)];
+    for (name, position) in markers {
+        let position = match position {
+            Some(position) => position,
+            None => continue,
+        };
+        let ratio = (position.as_secs_f64() / duration.as_secs_f64()).min(1.0);
+        let x = start + ((f64::from(width) * ratio) as u16).min(width - 1);
+        let marker = Paragraph::new(Span::styled(name, style));
+        f.render_widget(marker, Rect::new(x, bar.y, 1, 1));
+    }
+}
+
+// Draw the progress in the style of the theme, returning where the bar and
+// the label are.
+fn draw_progress<B: Backend>(
+    f: &mut Frame<B>,
+    app: &App,
+    label: Span,
+    ratio: f64,
+    color: Color,
+    area: Rect,
+) -> (Rect, Rect) {
+    let glyphs = app.config.glyphs();
+    let label_width = label.width() as u16;
+    let mut label_area = Rect::new(area.x, area.y, label_width.min(area.width), 1);
+    // The bar follows the label and a space in styles other than block.
+    let start = (area.x + label_width + 1).min(area.right());
+    let mut bar = Rect::new(start, area.y, area.right() - start, 1);
+    let width = bar.width as usize;
+    let style = match app.config.theme.progress {
+        // Braille is not ASCII.
+        ProgressStyle::Braille if glyphs.ascii => ProgressStyle::Text,
+        style => style,
+    };
+    match style {
+        ProgressStyle::Line => {
+            let gauge = LineGauge::default()
+                .gauge_style(Style::default().fg(color))
+                .label(label)
+                .line_set(glyphs.line_set.clone())
+                .ratio(ratio);
+            f.render_widget(gauge, area);
+        }
+        ProgressStyle::Block => {
+            // Texts on the filled part take the color as the background.
+            let label = Span::styled(label.content, label.style.fg(Color::Reset));
+            let gauge = Gauge::default()
+                .gauge_style(Style::default().fg(color))
+                .label(label)
+                .use_unicode(!glyphs.ascii)
+                .ratio(ratio);
+            f.render_widget(gauge, area);
+            bar = area;
+            // The label is in the middle of the gauge.
+            label_area.x = area.x + area.width.saturating_sub(label_width) / 2;
+        }
+        ProgressStyle::Braille => {
+            let halves = (ratio * (width * 2) as f64).round() as usize;
+            let mut filled = "⣿".repeat(halves / 2);
+            if halves % 2 == 1 {
+                filled.push('⡇');
+            }
+            let rest = width.saturating_sub(filled.chars().count());
+            let spans = Spans::from(vec![
+                label,
+                Span::raw(" "),
+                Span::styled(filled, Style::default().fg(color)),
+                Span::styled("⣀".repeat(rest), Style::default().fg(app.config.theme.dim)),
+            ]);
+            f.render_widget(Paragraph::new(spans), area);
+        }
+        ProgressStyle::Text => {
+            let inner = width.saturating_sub(2);
+            let filled = ((ratio * inner as f64).round() as usize).min(inner);
+            let mut text = "=".repeat(filled.saturating_sub(1));
+            if filled > 0 {
+                text.push(if filled == inner { '=' } else { '>' });
+            }
+            text.push_str(&" ".repeat(inner - filled));
+            let spans = Spans::from(vec![
+                label,
+                Span::raw(" ["),
+                Span::styled(text, Style::default().fg(color)),
+                Span::raw("]"),
+            ]);
+            f.render_widget(Paragraph::new(spans), area);
+            bar = Rect::new(bar.x + 1, bar.y, inner as u16, 1);
+        }
+    }
+    (bar, label_area)
+}
+
+fn draw_logs<B: Backend>(f: &mut Frame<B>, app: &App, panel: &LogPanel, area: Rect) {
+    let theme = &app.config.theme;
+    let (entries, _) = logger::recent();
+    let height = area.height.saturating_sub(1) as usize;
+    // Fill the panel even if it is scrolled up too far, like after the
+    // terminal grows.
+    let end = entries
+        .len()
+        .saturating_sub(panel.scroll)
+        .max(height.min(entries.len()));
+    let start = end.saturating_sub(height);
+    let lines: Vec<Spans> = entries[start..end]
+        .iter()
+        .map(|entry| {
+            let color = match entry.level {
+                Level::Error => Color::Red,
+                Level::Warn => Color::Yellow,
+                Level::Info => theme.message,
+                Level::Debug | Level::Trace => theme.dim,
+            };
+            Spans::from(vec![
+                Span::styled(format!("{} ", entry.time), Style::default().fg(theme.dim)),
+                Span::styled(format!("{:<5} ", entry.level), Style::default().fg(color)),
+                Span::styled(format!("{} ", entry.target), Style::default().fg(theme.dim)),
+                Span::raw(entry.message.clone()),
+            ])
+        })
+        .collect();
+    let title = if panel.scroll == 0 {
+        tr!("日志 (跟随)").to_owned()
+    } else {
+        tr!("日志 (↑{})", panel.scroll)
+    };
+    let block = Block::default()
+        .borders(Borders::TOP)
+        .title(Span::styled(title, Style::default().fg(theme.accent)));
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn draw_inspector<B: Backend>(f: &mut Frame<B>, app: &App, view: &InspectorView, area: Rect) {
+    let theme = &app.config.theme;
+    let glyphs = app.config.glyphs();
+    let records = inspector::records(&view.filter);
+    let selected = view.selected.unwrap_or(records.len().saturating_sub(1));
     let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .margin(1)
-        .constraints(
-            [
-                Constraint::Min(1),
-                Constraint::Length(1),
-                Constraint::Length(1),
-                Constraint::Length(1),
-            ]
-            .as_ref(),
-        )
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(45), Constraint::Percentage(55)].as_ref())
         .split(area);
 
-    let inner = app.inner.lock().unwrap();
-    let metadata = inner.metadata.clone();
-    let lyric_s = inner.lyric_s.clone();
-    let position = inner.progress.current();
-    let duration = inner.duration;
-    let state = inner.state;
-    drop(inner);
+    // Keep the selected record at the bottom of the list once it scrolls.
+    let height = chunks[0].height.saturating_sub(1) as usize;
+    let end = (selected + 1).max(height.min(records.len()));
+    let start = end.saturating_sub(height);
+    let lines: Vec<Spans> = records[start..end.min(records.len())]
+        .iter()
+        .enumerate()
+        .map(|(i, record)| {
+            let (arrow, color) = match record.kind {
+                Kind::Request => (glyphs.request, theme.dim),
+                Kind::Response { ok: true } => (glyphs.response, theme.message),
+                Kind::Response { ok: false } => (glyphs.response, Color::Red),
+                Kind::Message => (glyphs.message, theme.playing),
+            };
+            let mut style = Style::default();
+            if start + i == selected {
+                style = style.fg(theme.accent).add_modifier(Modifier::REVERSED);
+            }
+            let failed = record.kind == (Kind::Response { ok: false }) && app.config.accessible;
+            let arrow = match failed {
+                true => format!("{} {}", arrow, tr!("失败")),
+                false => arrow.to_owned(),
+            };
+            Spans::from(vec![
+                Span::styled(
+                    format!("{} ", &logger::fmt_timestamp(record.ts)[11..23]),
+                    Style::default().fg(theme.dim),
+                ),
+                Span::styled(format!("{} ", arrow), Style::default().fg(color)),
+                Span::styled(format!("{} ({}B)", record.topic, record.body.len()), style),
+            ])
+        })
+        .collect();
+    let mut title = "RPC".to_owned();
+    if !view.filter.is_empty() {
+        title.push_str(&format!(" /{}", view.filter));
+    }
+    if view.paused {
+        title.push_str(tr!(" (已暂停)"));
+    } else if view.selected.is_none() {
+        title.push_str(tr!(" (跟随)"));
+    }
+    let list = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::TOP)
+            .title(Span::styled(title, Style::default().fg(theme.accent))),
+    );
+    f.render_widget(list, chunks[0]);
 
-    {
-        let inner = app.inner.lock().unwrap();
-        let current_playlist = &inner.current_playlist;
-        if current_playlist.len() > 0 {
-            let items: Vec<Row> = current_playlist
-                .iter()
-                .map(|song| {
-                    Row::new(vec![
-                        song.title.clone(),
-                        song.artists_name.clone(),
-                        song.album_name.clone(),
-                        song.duration_ms.clone(),
-                    ])
-                })
-                .collect();
-            let headers = Row::new(vec!["歌曲标题", "歌手", "专辑", "时长"]);
-            let playlist = Table::new(items)
-                .header(headers)
-                .highlight_symbol(">> ")
-                .widths(&[
-                    Constraint::Percentage(40),
-                    Constraint::Percentage(10),
-                    Constraint::Percentage(30),
-                    Constraint::Percentage(5),
-                ]);
-            let mut state = TableState::default();
-            state.select(Some(1));
-            f.render_stateful_widget(playlist, chunks[0], &mut state);
+    let body = records
+        .get(selected)
+        .map(|record| record.pretty_body())
+        .unwrap_or_default();
+    let body = Paragraph::new(body).block(Block::default().borders(Borders::TOP | Borders::LEFT));
+    f.render_widget(body, chunks[1]);
+}
+
+// The full lyric with the line being sung in the middle.
+fn draw_lyrics<B: Backend>(
+    f: &mut Frame<B>,
+    app: &App,
+    position: Duration,
+    duration: Duration,
+    area: Rect,
+) {
+    let theme = &app.config.theme;
+    let block = Block::default()
+        .borders(Borders::TOP)
+        .title(Span::styled(tr!("歌词"), Style::default().fg(theme.accent)));
+    let timed = app.timed_lyric.as_ref();
+    let (timed, lines) = match timed.and_then(|timed| Some((timed, timed.lines.as_ref()?))) {
+        Some((timed, lines)) if !lines.is_empty() => (timed, lines),
+        _ => {
+            let empty = Paragraph::new(Span::styled(
+                tr!("暂无歌词"),
+                Style::default().fg(theme.dim),
+            ))
+            .alignment(Alignment::Center)
+            .block(block);
+            return f.render_widget(empty, area);
+        }
+    };
+    let current = timed.index_at(position);
+    let mode = app.lyric_mode;
+    // The line in the middle, which is the current one unless scrolled.
+    let middle = (current.unwrap_or(0) as isize + app.lyrics_scroll)
+        .clamp(0, lines.len() as isize - 1) as usize;
+    // Rows of every line, with the translation shown after or instead of it,
+    // and the first row of the middle line.
+    let mut rows: Vec<Spans> = vec![];
+    let mut middle_row = 0;
+    for (i, (_, line)) in lines.iter().enumerate() {
+        let translation = timed.translation(i);
+        let text = match (mode, translation) {
+            (LyricMode::Translation, Some(translation)) => translation,
+            _ => line.as_str(),
+        };
+        if i == middle {
+            middle_row = rows.len();
+        }
+        if Some(i) == current {
+            // Color the part which is sung like karaoke, assuming that every
+            // char takes the same time.
+            let ratio = timed.sung_ratio(position, duration);
+            let sung = (text.chars().count() as f64 * ratio).round() as usize;
+            let split = text.char_indices().nth(sung).map_or(text.len(), |(i, _)| i);
+            let style = Style::default().add_modifier(Modifier::BOLD);
+            rows.push(Spans::from(vec![
+                Span::styled(&text[..split], style.fg(theme.accent)),
+                Span::styled(&text[split..], style),
+            ]));
+        } else {
+            rows.push(Spans::from(Span::styled(
+                text,
+                Style::default().fg(theme.dim),
+            )));
+        }
+        if let (LyricMode::Bilingual, Some(translation)) = (mode, translation) {
+            let style = if Some(i) == current {
+                Style::default()
+            } else {
+                Style::default().fg(theme.dim)
+            };
+            rows.push(Spans::from(Span::styled(translation, style)));
+        }
+    }
+    let height = area.height.saturating_sub(1) as usize;
+    let start = middle_row.saturating_sub(height / 2);
+    let spans: Vec<Spans> = rows.into_iter().skip(start).take(height).collect();
+    let lyric = Paragraph::new(spans)
+        .alignment(Alignment::Center)
+        .block(block);
+    f.render_widget(lyric, area);
+}
+
+// The first row to show so that the selected one is on the screen, which
+// scrolls the same way as `TableState` does.
+fn scroll(offset: usize, selected: Option<usize>, height: usize, len: usize) -> usize {
+    let mut offset = offset;
+    if let Some(row) = selected {
+        if row < offset {
+            offset = row;
+        } else if row >= offset + height {
+            offset = row + 1 - height;
+        }
+    }
+    offset.min(len.saturating_sub(height))
+}
+
+// Rows of a page below the title and the header, which are the only ones
+// built.
+fn rows(area: Rect) -> usize {
+    area.height.saturating_sub(2) as usize
+}
+
+// Only the rows on the screen are given to tables, so the selected row is
+// relative to them. The offsets are kept in the view state, `Cursor` and
+// `App::playlist_offset`, since the one of `TableState` is private.
+fn table_state(selected: Option<usize>) -> TableState {
+    let mut state = TableState::default();
+    state.select(selected);
+    state
+}
+
+struct SongRow<'a> {
+    song: &'a BriefSong,
+    marked: bool,
+    // When the song was played, in the page of the history.
+    played_at: Option<&'a str>,
+}
+
+impl<'a> SongRow<'a> {
+    fn new(song: &'a BriefSong) -> SongRow<'a> {
+        SongRow {
+            song,
+            marked: false,
+            played_at: None,
+        }
+    }
+}
+
+// Rows of songs on the screen, with the selected one among them.
+fn draw_songs<B: Backend>(
+    f: &mut Frame<B>,
+    app: &App,
+    inner: &AppInner,
+    songs: Vec<SongRow>,
+    selected: Option<usize>,
+    title: Option<&str>,
+    area: Rect,
+) {
+    let theme = &app.config.theme;
+    let glyphs = app.config.glyphs();
+    let current_song_uri = inner.current_song_uri.as_ref();
+    let history = songs.iter().any(|row| row.played_at.is_some());
+    let items: Vec<Row> = songs
+        .into_iter()
+        .map(|row| {
+            let song = row.song;
+            let mut title = vec![];
+            if row.marked {
+                title.push(Span::styled(
+                    glyphs.check,
+                    Style::default().fg(theme.accent),
+                ));
+                title.push(Span::raw(" "));
+            }
+            title.push(Span::raw(song.title.as_str()));
+            if let Some(rating) = app.bookmarks.cell(song) {
+                title.push(Span::raw(" "));
+                title.push(Span::styled(rating, Style::default().fg(theme.accent)));
+            }
+            // Mark the song which is being played.
+            let is_current = current_song_uri.is_some_and(|uri| {
+                uri.identifier == song.identifier && uri.provider == song.provider
+            });
+            // Not only by the color.
+            if is_current && app.config.accessible {
+                title.push(Span::raw(tr!("（正在播放）")));
+            }
+            let mut cells = vec![
+                Cell::from(Spans::from(title)),
+                Cell::from(song.artists_name.as_str()),
+                Cell::from(song.album_name.as_str()),
+                Cell::from(song.duration_ms.as_str()),
+            ];
+            if let Some(played_at) = row.played_at {
+                cells.push(Cell::from(played_at));
+            }
+            let row = Row::new(cells);
+            if is_current {
+                row.style(Style::default().fg(theme.accent))
+            } else {
+                row
+            }
+        })
+        .collect();
+    let mut headers = vec![tr!("歌曲标题"), tr!("歌手"), tr!("专辑"), tr!("时长")];
+    let mut widths = vec![
+        Constraint::Percentage(40),
+        Constraint::Percentage(10),
+        Constraint::Percentage(30),
+        Constraint::Percentage(5),
+    ];
+    if history {
+        headers.push(tr!("播放时间"));
+        widths[2] = Constraint::Percentage(20);
+        widths.push(Constraint::Length(16));
+    }
+    let mut table = Table::new(items)
+        .header(Row::new(headers))
+        .highlight_symbol(">> ")
+        .widths(&widths);
+    if let Some(title) = title {
+        let block = Block::default()
+            .borders(Borders::TOP)
+            .title(Span::styled(title, Style::default().fg(theme.accent)));
+        table = table.block(block);
+    }
+    f.render_stateful_widget(table, area, &mut table_state(selected));
+    if let Some(row) = selected {
+        focus(
+            f,
+            app,
+            area,
+            area.y + title.is_some() as u16 + 1 + row as u16,
+        );
+    }
+}
+
+fn draw_comments<B: Backend>(
+    f: &mut Frame<B>,
+    app: &App,
+    pane: &CommentsPane,
+    metadata: &PlayerMetadata,
+    area: Rect,
+) {
+    let theme = &app.config.theme;
+    let glyphs = app.config.glyphs();
+    let lines: Vec<Spans> = match &pane.comments {
+        _ if pane.uri.is_none() => vec![Spans::from(tr!("没有正在播放的歌曲"))],
+        None => vec![Spans::from(tr!("加载中…"))],
+        Some(Err(e)) => vec![Spans::from(tr!("获取评论失败：{}", e))],
+        Some(Ok(comments)) if comments.is_empty() => vec![Spans::from(tr!("暂无评论"))],
+        Some(Ok(comments)) => comments
+            .iter()
+            .flat_map(|comment| {
+                let name = comment.user.as_ref().map(|user| user.name.as_str());
+                vec![
+                    Spans::from(vec![
+                        Span::styled(
+                            name.unwrap_or(tr!("匿名用户")).to_owned(),
+                            Style::default().fg(theme.accent),
+                        ),
+                        Span::styled(
+                            format!(" {} {}", glyphs.heart, comment.liked_count),
+                            Style::default().fg(theme.dim),
+                        ),
+                    ]),
+                    Spans::from(comment.content.as_str()),
+                    Spans::from(""),
+                ]
+            })
+            .collect(),
+    };
+    // The border of the focused pane stands out.
+    let border = match pane.focused {
+        true => Style::default().fg(theme.accent),
+        false => Style::default(),
+    };
+    let block = Block::default()
+        .borders(Borders::TOP | Borders::LEFT)
+        .border_style(border)
+        .title(Span::styled(
+            tr!("热门评论：{}", metadata.title),
+            Style::default().fg(theme.accent),
+        ));
+    let comments = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .scroll((pane.scroll, 0));
+    f.render_widget(comments, area);
+}
+
+fn draw_playlists<B: Backend>(f: &mut Frame<B>, app: &App, page: &PlaylistsPage, area: Rect) {
+    let theme = &app.config.theme;
+    let items: Vec<Row> = page
+        .playlists
+        .iter()
+        .skip(page.cursor.offset)
+        .take(rows(area))
+        .map(|playlist| Row::new(vec![playlist.name.as_str(), playlist.creator_name.as_str()]))
+        .collect();
+    let block = Block::default().borders(Borders::TOP).title(Span::styled(
+        tr!(
+            "{}（enter 查看歌曲，ctrl+a 添加全部，ctrl+p 播放全部）",
+            page.title
+        ),
+        Style::default().fg(theme.accent),
+    ));
+    let table = Table::new(items)
+        .header(Row::new(vec![tr!("歌单"), tr!("创建者")]))
+        .block(block)
+        .highlight_symbol(">> ")
+        .widths(&[Constraint::Percentage(60), Constraint::Percentage(30)]);
+    let selected = (!page.playlists.is_empty()).then(|| page.cursor.selected - page.cursor.offset);
+    f.render_stateful_widget(table, area, &mut table_state(selected));
+    if let Some(row) = selected {
+        focus(f, app, area, area.y + 2 + row as u16);
+    }
+}
+
+// Most played songs and artists as horizontal bars.
+fn draw_stats<B: Backend>(f: &mut Frame<B>, app: &App, page: &StatsPage, area: Rect) {
+    let theme = &app.config.theme;
+    let stats = &page.stats;
+    let block = Block::default().borders(Borders::TOP).title(Span::styled(
+        tr!(
+            "播放统计：{}，播放 {} 次，共收听 {}（g S 切换时间范围）",
+            page.range.name(),
+            stats.plays,
+            fmt_duration(stats.listened)
+        ),
+        Style::default().fg(theme.accent),
+    ));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    if stats.plays == 0 {
+        let empty = Paragraph::new(Span::styled(
+            tr!("还没有播放记录"),
+            Style::default().fg(theme.dim),
+        ));
+        return f.render_widget(empty, inner);
+    }
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+        .split(inner);
+    let songs = stats
+        .songs
+        .iter()
+        .map(|(song, count)| (format!("{} - {}", song.title, song.artists_name), *count));
+    draw_bars(f, app, tr!("歌曲"), songs.collect(), chunks[0]);
+    draw_bars(f, app, tr!("歌手"), stats.artists.clone(), chunks[1]);
+}
+
+fn draw_bars<B: Backend>(
+    f: &mut Frame<B>,
+    app: &App,
+    header: &str,
+    items: Vec<(String, usize)>,
+    area: Rect,
+) {
+    let theme = &app.config.theme;
+    let glyphs = app.config.glyphs();
+    let max = items.first().map_or(1, |(_, count)| *count).max(1);
+    // Half of the width is for names, and the bar is followed by the count.
+    let bar_width = (area.width / 2).saturating_sub(6) as usize;
+    let rows: Vec<Row> = items
+        .into_iter()
+        .take(area.height.saturating_sub(1) as usize)
+        .map(|(name, count)| {
+            let len = (bar_width * count).div_ceil(max);
+            Row::new(vec![
+                Cell::from(name),
+                Cell::from(Spans::from(vec![
+                    Span::styled(glyphs.bar.repeat(len), Style::default().fg(theme.playing)),
+                    Span::raw(format!(" {}", count)),
+                ])),
+            ])
+        })
+        .collect();
+    let table = Table::new(rows)
+        .header(Row::new(vec![header, tr!("播放次数")]).style(Style::default().fg(theme.dim)))
+        .widths(&[Constraint::Percentage(50), Constraint::Percentage(50)]);
+    f.render_widget(table, area);
+}
+
+fn draw_collections<B: Backend>(f: &mut Frame<B>, app: &App, page: &CollectionsPage, area: Rect) {
+    let theme = &app.config.theme;
+    let collections = page
+        .names
+        .iter()
+        .map(|name| Row::new(vec![name.as_str(), tr!("收藏集")]));
+    let smart = page.smart.iter().map(|name| {
+        Row::new(vec![name.as_str(), tr!("智能歌单")]).style(Style::default().fg(theme.dim))
+    });
+    let items: Vec<Row> = collections
+        .chain(smart)
+        .skip(page.cursor.offset)
+        .take(rows(area))
+        .collect();
+    let block = Block::default().borders(Borders::TOP).title(Span::styled(
+        tr!("收藏集（enter 查看歌曲，+ 添加歌曲）"),
+        Style::default().fg(theme.accent),
+    ));
+    let table = Table::new(items)
+        .header(Row::new(vec![tr!("名称"), tr!("类型")]))
+        .block(block)
+        .highlight_symbol(">> ")
+        .widths(&[Constraint::Percentage(70), Constraint::Percentage(20)]);
+    let selected = (page.len() > 0).then(|| page.cursor.selected - page.cursor.offset);
+    f.render_stateful_widget(table, area, &mut table_state(selected));
+    if let Some(row) = selected {
+        focus(f, app, area, area.y + 2 + row as u16);
+    }
+}
+
+fn draw_providers<B: Backend>(f: &mut Frame<B>, app: &App, page: &ProvidersPage, area: Rect) {
+    let theme = &app.config.theme;
+    let items: Vec<Row> = page
+        .providers
+        .iter()
+        .skip(page.cursor.offset)
+        .take(rows(area))
+        .map(|item| {
+            let user = match &item.user {
+                Some(name) => tr!("已登录：{}", name),
+                None => String::new(),
+            };
+            Row::new(vec![
+                Cell::from(item.provider.name.as_str()),
+                Cell::from(item.provider.identifier.as_str()),
+                Cell::from(user),
+            ])
+        })
+        .collect();
+    let block = Block::default().borders(Borders::TOP).title(Span::styled(
+        tr!("音乐来源（enter 扫码登录，g t 排行榜）"),
+        Style::default().fg(theme.accent),
+    ));
+    let table = Table::new(items)
+        .header(Row::new(vec![tr!("名称"), tr!("标识"), tr!("状态")]))
+        .block(block)
+        .highlight_symbol(">> ")
+        .widths(&[
+            Constraint::Percentage(30),
+            Constraint::Percentage(20),
+            Constraint::Percentage(40),
+        ]);
+    let selected = Some(page.cursor.selected - page.cursor.offset);
+    f.render_stateful_widget(table, area, &mut table_state(selected));
+    focus(
+        f,
+        app,
+        area,
+        area.y + 2 + (page.cursor.selected - page.cursor.offset) as u16,
+    );
+}
+
+// The QR code, drawn light on dark whatever the theme is so that it scans.
+fn draw_login<B: Backend>(f: &mut Frame<B>, app: &App, page: &LoginPage, area: Rect) {
+    let theme = &app.config.theme;
+    let hint = match page.status.state {
+        LoginState::Waiting | LoginState::Unknown => {
+            tr!("请使用 {} 的手机客户端扫码", page.provider)
         }
+        LoginState::Scanned => tr!("已扫码，请在手机上确认").to_owned(),
+        LoginState::Expired => tr!("二维码已过期，按 enter 刷新").to_owned(),
+        LoginState::Ok => tr!("已登录").to_owned(),
+    };
+    let mut lines = vec![Spans::from(Span::styled(
+        hint,
+        Style::default().fg(theme.message),
+    ))];
+    match &page.qr {
+        Some(qr) => {
+            let style = Style::default().fg(Color::White).bg(Color::Black);
+            lines.extend(
+                qr.to_lines()
+                    .into_iter()
+                    .map(|line| Spans::from(Span::styled(line, style))),
+            );
+        }
+        None => lines.push(Spans::from(tr!("二维码内容太长"))),
     }
+    let block = Block::default().borders(Borders::TOP).title(Span::styled(
+        tr!("登录 {}", page.provider),
+        Style::default().fg(theme.accent),
+    ));
+    let login = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .block(block);
+    f.render_widget(login, area);
+}
+
+// A popup in the middle of the screen, which is smaller if it does not fit.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+fn modal_block<'a>(app: &App, title: &'a str) -> Block<'a> {
+    Block::default().borders(Borders::ALL).title(Span::styled(
+        title,
+        Style::default().fg(app.config.theme.accent),
+    ))
+}
+
+fn draw_confirm<B: Backend>(f: &mut Frame<B>, app: &App, confirm: &Confirm) {
+    let hint = tr!("y 确定，n 取消");
+    let width = Span::raw(confirm.text.as_str())
+        .width()
+        .max(Span::raw(hint).width()) as u16
+        + 4;
+    let popup = centered(f.size(), width.max(24), 4);
+    let lines = vec![
+        Spans::from(confirm.text.as_str()),
+        Spans::from(Span::styled(
+            hint,
+            Style::default().fg(app.config.theme.dim),
+        )),
+    ];
+    f.render_widget(Clear, popup);
+    let paragraph = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+        .block(modal_block(app, tr!("确认")));
+    f.render_widget(paragraph, popup);
+    focus(f, app, popup, popup.y + 1);
+}
+
+fn draw_help<B: Backend>(f: &mut Frame<B>, app: &App, help: &Help) {
+    let area = f.size();
+    let key_width = help
+        .keys
+        .iter()
+        .map(|(keys, _)| Span::raw(keys.as_str()).width())
+        .max()
+        .unwrap_or(0);
+    let name_width = help
+        .keys
+        .iter()
+        .map(|(_, name)| name.len())
+        .max()
+        .unwrap_or(0);
+    let width = (key_width + name_width + 5) as u16;
+    let popup = centered(area, width, area.height.saturating_sub(2));
+    let lines: Vec<Spans> = help
+        .keys
+        .iter()
+        .skip(help.scroll)
+        .map(|(keys, name)| {
+            Spans::from(vec![
+                Span::styled(
+                    format!("{:width$}  ", keys, width = key_width),
+                    Style::default().fg(app.config.theme.accent),
+                ),
+                Span::raw(*name),
+            ])
+        })
+        .collect();
+    f.render_widget(Clear, popup);
+    f.render_widget(
+        Paragraph::new(lines).block(modal_block(app, tr!("按键"))),
+        popup,
+    );
+    focus(f, app, popup, popup.y + 1);
+}
+
+// A popup in the middle of the screen, checked items are marked.
+fn draw_picker<B: Backend>(f: &mut Frame<B>, app: &App, picker: &Picker) {
+    let theme = &app.config.theme;
+    let width = picker
+        .items
+        .iter()
+        .map(|item| Span::raw(item.as_str()).width() as u16 + 8)
+        .max()
+        .unwrap_or(0)
+        .max(24);
+    let popup = centered(f.size(), width, picker.items.len() as u16 + 2);
+    let height = popup.height;
+    let lines: Vec<Spans> = picker
+        .items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let mark = match picker.checked.as_ref().map(|checked| checked[i]) {
+                Some(true) => "[x] ",
+                Some(false) => "[ ] ",
+                None => "",
+            };
+            let style = if i == picker.selected {
+                Style::default()
+                    .fg(theme.accent)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            // Not only by the color.
+            let focused = if i == picker.selected && app.config.accessible {
+                "> "
+            } else {
+                ""
+            };
+            Spans::from(Span::styled(format!("{}{}{}", focused, mark, item), style))
+        })
+        .collect();
+    // Keep the selected item on the screen.
+    let rows = height.saturating_sub(2) as usize;
+    let start = (picker.selected + 1).saturating_sub(rows);
+    let lines: Vec<Spans> = lines.into_iter().skip(start).collect();
+    f.render_widget(Clear, popup);
+    let block = modal_block(app, picker.title.as_str());
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+    focus(
+        f,
+        app,
+        popup,
+        popup.y + 1 + (picker.selected - start) as u16,
+    );
+}
+
+// The song, progress and lyric at the bottom, which stay under whichever
+// view or page is shown. Return where the time of the progress is.
+fn draw_now_playing<B: Backend>(f: &mut Frame<B>, app: &App, inner: &AppInner, area: Rect) -> Rect {
+    let theme = &app.config.theme;
+    let glyphs = app.config.glyphs();
+    let metadata = &inner.metadata;
+    let position = inner.progress.current();
+    let duration = inner.duration;
+    let state = inner.state;
+    let loading = inner.loading && state != PlayerState::Stopped;
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Length(1)].as_ref())
+        .split(area);
 
     let mut song_spans = vec![
         Span::raw(" ".to_owned()),
-        Span::styled("♫  ", Style::default().fg(Color::Yellow)),
-        Span::raw(metadata.title),
+        Span::styled(
+            format!("{}  ", glyphs.note),
+            Style::default().fg(theme.accent),
+        ),
     ];
+    if metadata.title.is_empty() {
+        song_spans.push(Span::styled(
+            tr!("没有正在播放的歌曲"),
+            Style::default().fg(theme.dim),
+        ));
+    } else {
+        song_spans.push(Span::raw(metadata.title.as_str()));
+    }
+    if app.liked.state == Some(true) {
+        song_spans.push(Span::styled(
+            format!(" {}", glyphs.heart),
+            Style::default().fg(Color::Red),
+        ));
+    }
     if !metadata.artists.is_empty() {
-        song_spans.push(Span::raw(DOT));
-        song_spans.push(Span::styled(DOT, Style::default().fg(Color::Gray)));
+        song_spans.push(Span::raw(glyphs.dot));
+        song_spans.push(Span::styled(glyphs.dot, Style::default().fg(theme.dim)));
         song_spans.push(Span::raw(metadata.artists.join(",")));
     }
 
     let color = match state {
-        PlayerState::Stopped => Color::Gray,
-        PlayerState::Paused => Color::Gray,
-        PlayerState::Playing => Color::LightCyan,
+        PlayerState::Stopped => theme.paused,
+        PlayerState::Paused => theme.paused,
+        PlayerState::Playing => theme.playing,
     };
     let ratio = match duration.as_secs_f64() <= 0.0 {
         true => 0.0,
@@ -105,25 +920,275 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
             }
         }
     };
-    let progress = LineGauge::default()
-        .gauge_style(Style::default().fg(color))
-        .label(Span::styled(
-            format!("[{}/{}]", fmt_duration(position), fmt_duration(duration)),
-            Style::default().fg(color).add_modifier(Modifier::ITALIC),
-        ))
-        .line_set(THICK)
-        .ratio(ratio);
-    f.render_widget(progress, chunks[2]);
-
-    let lyric = Paragraph::new(vec![Spans::from(lyric_s)])
+    let label = Span::styled(
+        if loading {
+            tr!("[{} 加载中]", spinner(glyphs.spinner))
+        } else {
+            let time = match app.session.remaining_time {
+                true => format!(
+                    "-{}/{}",
+                    fmt_duration(duration.saturating_sub(position)),
+                    fmt_duration(duration)
+                ),
+                false => format!("{}/{}", fmt_duration(position), fmt_duration(duration)),
+            };
+            match app.config.accessible {
+                // The state is also told by the color of the gauge.
+                true => format!("[{} {}]", state.label(), time),
+                false => format!("[{}]", time),
+            }
+        },
+        Style::default().fg(color).add_modifier(Modifier::ITALIC),
+    );
+    let mut mode_spans = vec![];
+    if let Some(timer) = &app.sleep_timer {
+        mode_spans.push(Span::styled(
+            format!("{} {} ", glyphs.sleep, fmt_duration(timer.remaining())),
+            Style::default().fg(theme.accent),
+        ));
+    }
+    let quality = app
+        .quality
+        .resolved
+        .as_ref()
+        .filter(|_| app.quality.uri == inner.current_song_uri);
+    if let Some(quality) = quality {
+        mode_spans.push(Span::styled(
+            format!("{} ", quality),
+            Style::default().fg(theme.dim),
+        ));
+    }
+    if let Some(device) = &app.audio_device {
+        // Descriptions of devices are long, like `Built-in Audio Analog Stereo`.
+        let mut name: String = device.chars().take(AUDIO_DEVICE_WIDTH).collect();
+        if name.len() < device.len() {
+            name.push_str(glyphs.ellipsis);
+        }
+        mode_spans.push(Span::styled(
+            format!("[{}] ", name),
+            Style::default().fg(theme.dim),
+        ));
+    }
+    if inner.playback_rate != 1.0 {
+        mode_spans.push(Span::styled(
+            format!("{}x ", inner.playback_rate),
+            Style::default().fg(theme.accent),
+        ));
+    }
+    mode_spans.push(Span::styled(
+        inner.playback_mode.label(),
+        Style::default().fg(theme.dim),
+    ));
+    let mode_spans = Spans::from(mode_spans);
+    let mode_width = (mode_spans.width() as u16 + 1).max(16);
+    let mode = Paragraph::new(mode_spans).alignment(Alignment::Right);
+    let progress_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .margin(0)
+        .constraints([Constraint::Min(1), Constraint::Length(mode_width)].as_ref())
+        .split(rows[0]);
+    let (bar, time_area) = draw_progress(f, app, label, ratio, color, progress_chunks[0]);
+    f.render_widget(mode, progress_chunks[1]);
+    if let Some(ab) = &app.ab_repeat {
+        draw_ab_markers(f, app, ab, duration, bar);
+    }
+
+    let lyric = app.lyric(inner);
+    let lyric = match &lyric {
+        LyricState::Line(line) => Span::raw(line.as_str()),
+        LyricState::Unsynced => Span::styled(glyphs.unsynced, Style::default().fg(theme.dim)),
+        LyricState::None => Span::styled(
+            tr!("暂无歌词"),
+            Style::default()
+                .fg(theme.dim)
+                .add_modifier(Modifier::ITALIC),
+        ),
+    };
+    let lyric = Paragraph::new(vec![Spans::from(lyric)])
         .wrap(Wrap { trim: true })
-        .alignment(Alignment::Right);
+        .alignment(app.config.layout.lyric_alignment);
     let song = Paragraph::new(Spans::from(song_spans)).wrap(Wrap { trim: true });
     let h_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .margin(0)
         .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-        .split(chunks[3]);
+        .split(rows[1]);
     f.render_widget(song, h_chunks[0]);
     f.render_widget(lyric, h_chunks[1]);
+    time_area
+}
+
+pub fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let area = f.size();
+    let theme = &app.config.theme;
+    let glyphs = app.config.glyphs();
+    let mut constraints = vec![
+        Constraint::Min(1),
+        Constraint::Length(1),
+        Constraint::Length(2),
+    ];
+    if app.config.accessible {
+        constraints.push(Constraint::Length(ANNOUNCEMENTS as u16));
+    }
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(app.config.layout.margin)
+        .constraints(constraints)
+        .split(area);
+
+    // Borrow the state for the whole frame instead of cloning it, the lock
+    // is only held while widgets are built.
+    let inner = app.inner.read().unwrap();
+    let metadata = &inner.metadata;
+    let position = inner.progress.current();
+    let duration = inner.duration;
+
+    // The comments pane takes the right side of the main area.
+    let (main, side) = match &app.comments {
+        Some(_) => {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+                .split(chunks[0]);
+            (columns[0], Some(columns[1]))
+        }
+        None => (chunks[0], None),
+    };
+    if let Some(panel) = &app.log_panel {
+        draw_logs(f, app, panel, main);
+    } else if let Some(view) = &app.inspector {
+        draw_inspector(f, app, view, main);
+    } else if app.lyrics_view {
+        draw_lyrics(f, app, position, duration, main);
+    } else if !app.pages.is_empty() {
+        // Rows below the title and the header.
+        let height = (main.height as usize).saturating_sub(2).max(1);
+        if let Some(page) = app.pages.last_mut() {
+            match page {
+                Page::Songs(page) => page.cursor.scroll(height, page.songs.len()),
+                Page::Playlists(page) => page.cursor.scroll(height, page.playlists.len()),
+                Page::Collections(page) => page.cursor.scroll(height, page.len()),
+                Page::Providers(page) => page.cursor.scroll(height, page.providers.len()),
+                Page::Login(_) | Page::Stats(_) => 0,
+            };
+        }
+        match app.pages.last() {
+            Some(Page::Songs(page)) => {
+                let Cursor { selected, offset } = page.cursor;
+                let end = (offset + height).min(page.songs.len());
+                let songs = (offset..end)
+                    .map(|i| SongRow {
+                        song: &page.songs[i],
+                        marked: page.marked.contains(&i),
+                        played_at: page.played_at.as_ref().map(|times| times[i].as_str()),
+                    })
+                    .collect();
+                let selected = (!page.songs.is_empty()).then(|| selected - offset);
+                let title = match page.marked.len() {
+                    0 => page.title.clone(),
+                    n => tr!("{}（已标记 {} 首）", page.title, n),
+                };
+                draw_songs(f, app, &inner, songs, selected, Some(&title), main);
+            }
+            Some(Page::Playlists(page)) => draw_playlists(f, app, page, main),
+            Some(Page::Collections(page)) => draw_collections(f, app, page, main),
+            Some(Page::Stats(page)) => draw_stats(f, app, page, main),
+            Some(Page::Providers(page)) => draw_providers(f, app, page, main),
+            Some(Page::Login(page)) => draw_login(f, app, page, main),
+            None => {}
+        }
+    } else if !app.config.layout.mini {
+        let current_playlist = &inner.current_playlist;
+        if !current_playlist.is_empty() {
+            let len = match &app.playlist_filtered {
+                Some(indices) => indices.len(),
+                None => current_playlist.len(),
+            };
+            let title = app
+                .fm
+                .as_ref()
+                .map(|fm| tr!("私人 FM：{}（即将播放）", fm.provider));
+            // Only rows on the screen are built.
+            let rows = if title.is_some() { 2 } else { 1 };
+            let height = (main.height as usize).saturating_sub(rows).max(1);
+            let selected = app.playlist_state.selected();
+            let offset = scroll(app.playlist_offset, selected, height, len);
+            app.playlist_offset = offset;
+            let end = (offset + height).min(len);
+            let songs: Vec<SongRow> = match &app.playlist_filtered {
+                Some(indices) => indices[offset..end]
+                    .iter()
+                    .map(|&i| SongRow::new(&current_playlist[i]))
+                    .collect(),
+                None => current_playlist[offset..end]
+                    .iter()
+                    .map(SongRow::new)
+                    .collect(),
+            };
+            let selected = selected.map(|row| row - offset);
+            draw_songs(f, app, &inner, songs, selected, title.as_deref(), main);
+        }
+    }
+
+    if let (Some(pane), Some(area)) = (&app.comments, side) {
+        draw_comments(f, app, pane, metadata, area);
+    }
+
+    // The clock takes the right of the status row.
+    let mut status = chunks[1];
+    if let Some(format) = &app.config.clock {
+        let clock = Span::styled(clock::now(format), Style::default().fg(theme.dim));
+        let width = (clock.width() as u16 + 1).min(status.width);
+        status.width -= width;
+        let area = Rect::new(status.right(), status.y, width, 1);
+        f.render_widget(Paragraph::new(clock).alignment(Alignment::Right), area);
+    }
+    if let Some(input) = &app.input {
+        let spans = Spans::from(vec![
+            Span::styled(input.kind.prompt(), Style::default().fg(theme.accent)),
+            Span::raw(input.text.as_str()),
+        ]);
+        let x = status.x + spans.width() as u16;
+        f.render_widget(Paragraph::new(spans), status);
+        f.set_cursor(x.min(status.right()), status.y);
+    } else if let Some(toast) = &app.toast {
+        let toast = Paragraph::new(Span::styled(
+            toast.text.as_str(),
+            Style::default().fg(theme.message),
+        ));
+        f.render_widget(toast, status);
+    } else if let Some(indices) = &app.playlist_filtered {
+        let filter = Paragraph::new(Span::styled(
+            tr!("/{} ({} 首匹配)", app.playlist_filter, indices.len()),
+            Style::default().fg(theme.dim),
+        ));
+        f.render_widget(filter, status);
+    }
+
+    let time_area = draw_now_playing(f, app, &inner, chunks[2]);
+    app.time_area = time_area;
+
+    if let Some(area) = chunks.get(3) {
+        let lines: Vec<Spans> = app
+            .announcements
+            .iter()
+            .map(|text| Spans::from(text.as_str()))
+            .collect();
+        f.render_widget(Paragraph::new(lines), *area);
+    }
+    if !app.modals.is_empty() {
+        // Dim the view under the dialogs.
+        let dim = Style::default().add_modifier(Modifier::DIM);
+        f.render_widget(Block::default().style(dim), area);
+    }
+    for modal in &app.modals {
+        match modal {
+            Modal::Picker(picker) => draw_picker(f, app, picker),
+            Modal::Confirm(confirm) => draw_confirm(f, app, confirm),
+            Modal::Help(help) => draw_help(f, app, help),
+        }
+    }
+    if glyphs.ascii {
+        f.render_widget(AsciiBorders, area);
+    }
 }
@@ -0,0 +1,107 @@
+use std::fmt;
+use std::str::FromStr;
+
+// A fuo resource identifier which looks like `fuo://<provider>/<ns>/<identifier>`.
+//
+// The identifier is everything after the namespace, so it may contain `/`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FuoUri {
+    pub provider: String,
+    pub ns: String,
+    pub identifier: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseFuoUriError(String);
+
+impl fmt::Display for ParseFuoUriError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid fuo uri: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseFuoUriError {}
+
+impl FuoUri {
+    pub fn new(provider: &str, ns: &str, identifier: &str) -> FuoUri {
+        FuoUri {
+            provider: provider.to_owned(),
+            ns: ns.to_owned(),
+            identifier: identifier.to_owned(),
+        }
+    }
+}
+
+impl FromStr for FuoUri {
+    type Err = ParseFuoUriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let path = s
+            .strip_prefix("fuo://")
+            .ok_or_else(|| ParseFuoUriError(s.to_owned()))?;
+        let mut parts = path.splitn(3, '/');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(provider), Some(ns), Some(identifier))
+                if !provider.is_empty() && !ns.is_empty() && !identifier.is_empty() =>
+            {
+                Ok(FuoUri::new(provider, ns, identifier))
+            }
+            _ => Err(ParseFuoUriError(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for FuoUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "fuo://{}/{}/{}", self.provider, self.ns, self.identifier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(provider: &str, ns: &str, identifier: &str) {
+        let uri = FuoUri::new(provider, ns, identifier);
+        let s = uri.to_string();
+        assert_eq!(s, format!("fuo://{}/{}/{}", provider, ns, identifier));
+        assert_eq!(s.parse(), Ok(uri));
+    }
+
+    #[test]
+    fn odd_identifiers() {
+        round_trip("netease", "songs", "1859245776");
+        // Local songs are identified by their paths.
+        round_trip("local", "songs", "music/Adele/25/01 Hello.mp3");
+        round_trip("local", "songs", "it's");
+        round_trip("local", "songs", "周杰伦/晴天.flac");
+        round_trip("qqmusic", "playlists", "a b/c'd/");
+    }
+
+    #[test]
+    fn resources_of_uris() {
+        let uri: FuoUri = "fuo://netease/songs/1/lyric".parse().unwrap();
+        assert_eq!(uri, FuoUri::new("netease", "songs", "1/lyric"));
+    }
+
+    #[test]
+    fn invalid() {
+        for s in [
+            "",
+            "fuo://",
+            "fuo://netease",
+            "fuo://netease/songs",
+            "fuo://netease/songs/",
+            "fuo://netease//1",
+            "fuo:///songs/1",
+            "netease/songs/1",
+        ] {
+            assert_eq!(
+                s.parse::<FuoUri>(),
+                Err(ParseFuoUriError(s.to_owned())),
+                "{:?}",
+                s
+            );
+        }
+    }
+}